@@ -0,0 +1,183 @@
+//! Helpers for programs that stake on behalf of their own PDAs.
+//!
+//! Depend on this crate with the `no-entrypoint` feature and pass the PDA seeds of `user` as
+//! `signer_seeds`. On `stake` the user also pays rent for its user state account, so a PDA user
+//! must hold enough lamports.
+
+use crate::instruction::StakingInstruction;
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, instruction::AccountMeta,
+    program::invoke_signed, pubkey::Pubkey,
+};
+
+pub struct StakeAccounts<'a> {
+    pub user: AccountInfo<'a>,
+    pub user_staking_token_account: AccountInfo<'a>,
+    pub staking_token_escrow: AccountInfo<'a>,
+    pub user_state: AccountInfo<'a>,
+    pub staking_pool: AccountInfo<'a>,
+    pub staking_token_mint: AccountInfo<'a>,
+    pub token_program: AccountInfo<'a>,
+    pub system_program: AccountInfo<'a>,
+    /// Only needed by pools with a receipt mint: the receipt mint and the user's receipt account
+    pub receipt_accounts: Option<(AccountInfo<'a>, AccountInfo<'a>)>,
+}
+
+pub struct UnstakeAccounts<'a> {
+    pub user: AccountInfo<'a>,
+    pub user_staking_token_account: AccountInfo<'a>,
+    pub user_state: AccountInfo<'a>,
+    pub staking_pool: AccountInfo<'a>,
+    pub staking_token_escrow: AccountInfo<'a>,
+    pub staking_token_mint: AccountInfo<'a>,
+    pub token_program: AccountInfo<'a>,
+    /// Only needed while an early unstake penalty is due, see `StakingInstruction::Unstake`
+    pub penalty_destination: Option<AccountInfo<'a>>,
+    /// Only needed by pools with a receipt mint: the receipt mint and the user's receipt account
+    pub receipt_accounts: Option<(AccountInfo<'a>, AccountInfo<'a>)>,
+}
+
+pub struct GetRewardsAccounts<'a> {
+    pub user: AccountInfo<'a>,
+    pub user_rewards_token_account: AccountInfo<'a>,
+    pub user_state: AccountInfo<'a>,
+    pub staking_pool: AccountInfo<'a>,
+    pub rewards_token_escrow: AccountInfo<'a>,
+    pub rewards_token_mint: AccountInfo<'a>,
+    pub token_program: AccountInfo<'a>,
+    /// Only needed while the pool charges a reward fee, see `StakingInstruction::GetRewards`
+    pub fee_token_account: Option<AccountInfo<'a>>,
+    /// Only needed by pools with a receipt mint
+    pub user_receipt_token_account: Option<AccountInfo<'a>>,
+}
+
+pub fn stake(
+    program_id: &Pubkey,
+    accounts: StakeAccounts,
+    amount: u64,
+    lock_duration: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let mut account_metas = vec![
+        AccountMeta::new(*accounts.user.key, true),
+        AccountMeta::new(*accounts.user_staking_token_account.key, false),
+        AccountMeta::new(*accounts.staking_token_escrow.key, false),
+        AccountMeta::new(*accounts.user_state.key, false),
+        AccountMeta::new(*accounts.staking_pool.key, false),
+        AccountMeta::new_readonly(*accounts.staking_token_mint.key, false),
+        AccountMeta::new_readonly(*accounts.token_program.key, false),
+        AccountMeta::new_readonly(*accounts.system_program.key, false),
+    ];
+    let mut account_infos = vec![
+        accounts.user,
+        accounts.user_staking_token_account,
+        accounts.staking_token_escrow,
+        accounts.user_state,
+        accounts.staking_pool,
+        accounts.staking_token_mint,
+        accounts.token_program,
+        accounts.system_program,
+    ];
+    push_receipt_accounts(
+        &mut account_metas,
+        &mut account_infos,
+        accounts.receipt_accounts,
+    );
+    let ix = StakingInstruction::Stake {
+        amount,
+        lock_duration,
+    }
+    .build(program_id, account_metas);
+    invoke_signed(&ix, &account_infos, signer_seeds)
+}
+
+pub fn unstake(
+    program_id: &Pubkey,
+    accounts: UnstakeAccounts,
+    amount: u64,
+    close: bool,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let mut account_metas = vec![
+        AccountMeta::new(*accounts.user.key, true),
+        AccountMeta::new(*accounts.user_staking_token_account.key, false),
+        AccountMeta::new(*accounts.user_state.key, false),
+        AccountMeta::new(*accounts.staking_pool.key, false),
+        AccountMeta::new(*accounts.staking_token_escrow.key, false),
+        AccountMeta::new_readonly(*accounts.staking_token_escrow.key, false),
+        AccountMeta::new_readonly(*accounts.staking_token_mint.key, false),
+        AccountMeta::new_readonly(*accounts.token_program.key, false),
+    ];
+    let mut account_infos = vec![
+        accounts.user,
+        accounts.user_staking_token_account,
+        accounts.user_state,
+        accounts.staking_pool,
+        accounts.staking_token_escrow,
+        accounts.staking_token_mint,
+        accounts.token_program,
+    ];
+    push_receipt_accounts(
+        &mut account_metas,
+        &mut account_infos,
+        accounts.receipt_accounts,
+    );
+    if let Some(penalty_destination) = accounts.penalty_destination {
+        account_metas.push(AccountMeta::new(*penalty_destination.key, false));
+        account_infos.push(penalty_destination);
+    }
+    let ix = StakingInstruction::Unstake { amount, close }.build(program_id, account_metas);
+    invoke_signed(&ix, &account_infos, signer_seeds)
+}
+
+pub fn get_rewards(
+    program_id: &Pubkey,
+    accounts: GetRewardsAccounts,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let mut account_metas = vec![
+        AccountMeta::new(*accounts.user.key, true),
+        AccountMeta::new(*accounts.user_rewards_token_account.key, false),
+        AccountMeta::new(*accounts.user_state.key, false),
+        AccountMeta::new(*accounts.staking_pool.key, false),
+        AccountMeta::new(*accounts.rewards_token_escrow.key, false),
+        AccountMeta::new_readonly(*accounts.rewards_token_escrow.key, false),
+        AccountMeta::new_readonly(*accounts.rewards_token_mint.key, false),
+        AccountMeta::new_readonly(*accounts.token_program.key, false),
+    ];
+    let mut account_infos = vec![
+        accounts.user,
+        accounts.user_rewards_token_account,
+        accounts.user_state,
+        accounts.staking_pool,
+        accounts.rewards_token_escrow,
+        accounts.rewards_token_mint,
+        accounts.token_program,
+    ];
+    if let Some(fee_token_account) = accounts.fee_token_account {
+        account_metas.push(AccountMeta::new(*fee_token_account.key, false));
+        account_infos.push(fee_token_account);
+    }
+    if let Some(user_receipt_token_account) = accounts.user_receipt_token_account {
+        account_metas.push(AccountMeta::new_readonly(
+            *user_receipt_token_account.key,
+            false,
+        ));
+        account_infos.push(user_receipt_token_account);
+    }
+    let ix = StakingInstruction::GetRewards.build(program_id, account_metas);
+    invoke_signed(&ix, &account_infos, signer_seeds)
+}
+
+fn push_receipt_accounts<'a>(
+    account_metas: &mut Vec<AccountMeta>,
+    account_infos: &mut Vec<AccountInfo<'a>>,
+    receipt_accounts: Option<(AccountInfo<'a>, AccountInfo<'a>)>,
+) {
+    if let Some((receipt_mint, user_receipt_token_account)) = receipt_accounts {
+        account_metas.push(AccountMeta::new(*receipt_mint.key, false));
+        account_metas.push(AccountMeta::new(*user_receipt_token_account.key, false));
+        account_infos.push(receipt_mint);
+        account_infos.push(user_receipt_token_account);
+    }
+}
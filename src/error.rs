@@ -0,0 +1,122 @@
+use num_derive::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    msg,
+    program_error::{PrintProgramError, ProgramError},
+};
+use thiserror::Error;
+
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum StakingError {
+    #[error("Staking pool pda does not match the derived address")]
+    InvalidStakingPoolPda = 0,
+    #[error("User state pda does not match the derived address")]
+    InvalidUserStatePda = 1,
+    #[error("Staking token escrow is not owned by the staking token pda")]
+    InvalidStakingTokenEscrow = 2,
+    #[error("Rewards token escrow is not owned by the rewards token pda")]
+    InvalidRewardsTokenEscrow = 3,
+    #[error("Rewards token mint does not match the staking pool")]
+    InvalidRewardsTokenMint = 4,
+    #[error("Account is not owned by the staking program")]
+    InvalidAccountOwner = 5,
+    #[error("Signer is not the staking pool admin")]
+    Unauthorized = 6,
+    #[error("Amount must be greater than zero")]
+    ZeroAmount = 7,
+    #[error("Duration must be greater than zero")]
+    ZeroDuration = 8,
+    #[error("Precision must be greater than zero")]
+    InvalidPrecision = 9,
+    #[error("Cannot unstake more than staked")]
+    UnstakeExceedsBalance = 10,
+    #[error("Rewards token escrow balance is too low for the reward rate")]
+    InsufficientRewardEscrow = 11,
+    #[error("Arithmetic overflow")]
+    ArithmeticOverflow = 12,
+    #[error("Staking pool is already initialized")]
+    AlreadyInitialized = 13,
+    #[error("Staking token mint does not match the staking pool")]
+    InvalidStakingTokenMint = 14,
+    #[error("Token program is not SPL Token or Token-2022, or does not own the mint")]
+    InvalidTokenProgram = 15,
+    #[error("Amount exceeds the rewards not owed to stakers")]
+    InsufficientExcessRewards = 16,
+    #[error("Staking is paused")]
+    StakingPaused = 17,
+    #[error("Claiming rewards is paused")]
+    ClaimingPaused = 18,
+    #[error("User state still holds a balance or rewards")]
+    UserStateNotEmpty = 19,
+    #[error("Staking pool still holds staked tokens")]
+    PoolNotEmpty = 20,
+    #[error("Staking pool still owes rewards to stakers")]
+    RewardsOutstanding = 21,
+    #[error("Staking pool already pays the maximum number of reward tokens")]
+    TooManyRewardStreams = 22,
+    #[error("Staking pool already pays this reward token")]
+    RewardStreamExists = 23,
+    #[error("Staking pool does not pay rewards in the staking token")]
+    CompoundMintMismatch = 24,
+    #[error("Lock multiplier must be at least 1x")]
+    InvalidLockMultiplier = 25,
+    #[error("Lock duration exceeds the maximum of the staking pool")]
+    LockTooLong = 26,
+    #[error("Amount is locked until the lock expires")]
+    StakeLocked = 27,
+    #[error("Staking pool has a cooldown, unstake through RequestUnstake")]
+    UnbondingRequired = 28,
+    #[error("Withdrawal queue is full")]
+    WithdrawalQueueFull = 29,
+    #[error("No unstake request has finished its cooldown")]
+    UnbondingNotFinished = 30,
+    #[error("Withdrawal queue pda does not match the derived address")]
+    InvalidWithdrawalQueuePda = 31,
+    #[error("Early unstake penalty cannot exceed 10000 basis points")]
+    InvalidUnstakePenalty = 32,
+    #[error("Penalty treasury is not a token account of the staking token mint")]
+    InvalidPenaltyTreasury = 33,
+    #[error("Pool has no penalty treasury and no reward stream paying the staking token")]
+    PenaltyDestinationMissing = 34,
+    #[error("Penalty account is neither the pool treasury nor the staking token reward escrow")]
+    InvalidPenaltyDestination = 35,
+    #[error("Reward fee exceeds 1000 basis points")]
+    RewardFeeTooHigh = 36,
+    #[error("Fee account is not a token account of the fee recipient for the rewards mint")]
+    InvalidFeeAccount = 37,
+    #[error("Not supported by pools with a receipt mint")]
+    ReceiptMintUnsupported = 38,
+    #[error("Receipt mint does not match the staking pool")]
+    InvalidReceiptMint = 39,
+    #[error("Receipt token account is not the holder's account of the pool receipt mint")]
+    InvalidReceiptAccount = 40,
+    #[error("User already holds the maximum number of locked positions")]
+    TooManyLockedPositions = 41,
+    #[error("Staking pool tracks the maximum number of lock expiries")]
+    LockScheduleFull = 42,
+}
+
+impl From<StakingError> for ProgramError {
+    fn from(e: StakingError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for StakingError {
+    fn type_of() -> &'static str {
+        "StakingError"
+    }
+}
+
+impl PrintProgramError for StakingError {
+    fn print<E>(&self)
+    where
+        E: 'static
+            + std::error::Error
+            + DecodeError<E>
+            + PrintProgramError
+            + num_traits::FromPrimitive,
+    {
+        msg!("Error: {}", self);
+    }
+}
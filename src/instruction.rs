@@ -1,4 +1,10 @@
+use crate::pda_helper::PdaHelper;
 use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
 
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug)]
 pub enum StakingInstruction {
@@ -10,9 +16,21 @@ pub enum StakingInstruction {
     /// 4. [w] - staking token escrow pda
     /// 5. [w] - rewards token escrow pda
     /// 6. [] - system program
-    /// 7. [] - token program
+    /// 7. [] - staking token program
     /// 8. [] - rent account
-    Init,
+    /// 9. [] - rewards token program
+    /// 10. [] - (optional) penalty treasury, a staking token account receiving early unstake
+    ///     penalties. Without it penalties are paid to the remaining stakers
+    ///
+    /// Each token program is SPL Token or Token-2022 and must own its mint.
+    /// `pool_id` tells apart independent pools for the same mint pair. `reward_rate` emits
+    /// nothing by itself: it is the rate of the first period funded by `NotifyRewardAmount`
+    /// without a duration
+    Init {
+        reward_rate: u64,
+        precision: u64,
+        pool_id: u64,
+    },
 
     ///
     /// 0. [s] - user account who want to stake
@@ -23,7 +41,15 @@ pub enum StakingInstruction {
     /// 5. [] - staking token mint account
     /// 6. [] - token program
     /// 7. [] - system program
-    Stake { amount: u64 },
+    /// 8. [w] - receipt mint pda, only for pools with a receipt mint
+    /// 9. [w] - user receipt token account, only for pools with a receipt mint
+    ///
+    /// Only the amount the escrow receives after transfer fees is credited. A non-zero
+    /// `lock_duration` locks it as a new position boosted by the pool lock multiplier, leaving
+    /// positions already locked as they are. The position may expire later, up to the maximum
+    /// lock duration, when the pool has no room left for its expiry. Pools with a receipt mint
+    /// mint the credited amount as receipt tokens and do not support locks
+    Stake { amount: u64, lock_duration: u64 },
 
     ///
     /// 0. [s] - user account who want to unstake
@@ -34,7 +60,19 @@ pub enum StakingInstruction {
     /// 5. [] - staking token escrow pda owner
     /// 6. [] - staking token mint account
     /// 7. [] - token program
-    Unstake { amount: u64 },
+    /// 8. [w] - receipt mint pda, only for pools with a receipt mint
+    /// 9. [w] - user receipt token account, only for pools with a receipt mint
+    /// 10. [w] - penalty destination: the pool penalty treasury or, without one, the rewards token
+    ///     escrow pda of the stream paying the staking token, following the receipt accounts when
+    ///     the pool has them. Only needed while a penalty is due
+    ///
+    /// With `close` set the whole balance must be unstaked and all rewards claimed beforehand, for
+    /// example by `GetRewards` earlier in the same transaction. The user state pda is then closed
+    /// to the user.
+    /// Rejected while the pool has an unbonding period. Within the penalty decay period after the
+    /// last stake part of `amount` is withheld as an early unstake penalty. Pools with a receipt
+    /// mint burn `amount` receipt tokens
+    Unstake { amount: u64, close: bool },
 
     ///
     /// 0. [s] - user account who want to claim rewards
@@ -45,5 +83,1153 @@ pub enum StakingInstruction {
     /// 5. [] - staking token escrow pda owner
     /// 6. [] - rewards token mint account
     /// 7. [] - token program
+    /// 8. [w] - fee recipient token account of the rewards token mint, only while the pool
+    ///    charges a reward fee
+    /// 9. [] - user receipt token account, only for pools with a receipt mint. Follows account 7
+    ///    when the pool charges no fee
+    ///
+    /// Pools with a receipt mint sync the user's stake to the receipt token balance first.
+    /// Pays the stream of the passed rewards token mint. Further streams are paid by appending
+    /// for each of them:
+    /// 0. [w] - user rewards token account
+    /// 1. [w] - rewards token escrow pda
+    /// 2. [] - rewards token mint account
+    /// 3. [] - token program of the rewards token mint
+    /// 4. [w] - fee recipient token account of the rewards token mint, only while the pool
+    ///    charges a reward fee
     GetRewards,
+
+    /// Changes the rate of a running reward period, which must stay funded by the escrow balance
+    /// not owed to stakers. Outside a reward period it sets the rate of the next period funded by
+    /// `NotifyRewardAmount` without a duration
+    ///
+    /// 0. [s] - admin
+    /// 1. [w] - staking pool pda
+    /// 2. [] - rewards token mint account of the stream
+    /// 3. [] - rewards token escrow pda of the stream, only needed while its period is running
+    SetRewardRate { reward_rate: u64 },
+
+    /// Funds a reward period paying `amount`, plus what is left of the running period, over
+    /// `duration` seconds. A zero `duration` keeps the current rate of the stream and lasts as
+    /// long as the rewards pay for it
+    ///
+    /// 0. [s] - admin
+    /// 1. [w] - admin rewards token account
+    /// 2. [w] - staking pool pda
+    /// 3. [w] - rewards token escrow pda
+    /// 4. [] - rewards token mint account of the stream
+    /// 5. [] - token program
+    NotifyRewardAmount { amount: u64, duration: u64 },
+
+    ///
+    /// 0. [s] - admin
+    /// 1. [w] - admin rewards token account
+    /// 2. [w] - staking pool pda
+    /// 3. [w] - rewards token escrow pda
+    /// 4. [] - rewards token mint account of the stream
+    /// 5. [] - token program
+    WithdrawExcessRewards { amount: u64 },
+
+    /// Proposing the default pubkey cancels a pending transfer
+    ///
+    /// 0. [s] - admin
+    /// 1. [w] - staking pool pda
+    ProposeAdmin { new_admin: Pubkey },
+
+    ///
+    /// 0. [s] - pending admin
+    /// 1. [w] - staking pool pda
+    AcceptAdmin,
+
+    /// Returns the whole staked balance without updating rewards. Accrued rewards are forfeited.
+    /// Fails while the stake is locked. Pools with an unbonding period queue the balance as in
+    /// `RequestUnstake` instead of transferring it. The early unstake penalty is withheld as in
+    /// `Unstake`. Pools with a receipt mint burn the same amount of receipt tokens
+    ///
+    /// 0. [s] - user account who want to withdraw
+    /// 1. [w] - user staking token account
+    /// 2. [w] - user state pda
+    /// 3. [w] - staking pool pda
+    /// 4. [w] - staking token escrow pda
+    /// 5. [] - staking token escrow pda owner
+    /// 6. [] - staking token mint account
+    /// 7. [] - token program
+    /// 8. [w] - receipt mint pda, only for pools with a receipt mint
+    /// 9. [w] - user receipt token account, only for pools with a receipt mint
+    /// 10. [w] - penalty destination as in `Unstake`, following the receipt accounts when the
+    ///     pool has them. Only needed while a penalty is due or the pool has an unbonding period
+    /// 11. [w] - withdrawal queue pda, following the penalty destination. Only for pools with an
+    ///     unbonding period
+    /// 12. [] - system program, only for pools with an unbonding period
+    EmergencyWithdraw,
+
+    /// Pauses every operation whose flag is set. Unstake is never paused
+    ///
+    /// 0. [s] - admin
+    /// 1. [w] - staking pool pda
+    Pause {
+        staking: bool,
+        claiming: bool,
+        accrual: bool,
+    },
+
+    /// Resumes every operation whose flag is set
+    ///
+    /// 0. [s] - admin
+    /// 1. [w] - staking pool pda
+    Unpause {
+        staking: bool,
+        claiming: bool,
+        accrual: bool,
+    },
+
+    /// Requires zero balance and rewards. Anyone can close any user state, so empty ones cannot
+    /// hold up `ClosePool`. Rent is refunded to the user
+    ///
+    /// 0. [w] - user account who owns the user state
+    /// 1. [w] - user state pda
+    /// 2. [w] - staking pool pda
+    CloseUserState,
+
+    /// Requires no staked tokens, no rewards owed, every user state closed and no receipt tokens
+    /// left. Tokens left in the escrows are sent to the admin token accounts, then the escrows and
+    /// the pool are closed to the admin
+    ///
+    /// 0. [s, w] - admin
+    /// 1. [w] - admin staking token account
+    /// 2. [w] - admin rewards token account
+    /// 3. [w] - staking pool pda
+    /// 4. [w] - staking token escrow pda
+    /// 5. [w] - rewards token escrow pda
+    /// 6. [] - staking token mint account
+    /// 7. [] - rewards token mint account
+    /// 8. [] - staking token program
+    /// 9. [] - rewards token program
+    /// 10. [] - receipt mint pda, only for pools with a receipt mint
+    ///
+    /// The rewards token mint must be the one the pool was created with. Every added reward
+    /// stream then follows in stream order with:
+    /// 0. [w] - admin rewards token account
+    /// 1. [w] - rewards token escrow pda
+    /// 2. [] - rewards token mint account
+    /// 3. [] - token program of the rewards token mint
+    ClosePool,
+
+    /// Adds a reward stream paying another token. It is funded with `NotifyRewardAmount`
+    ///
+    /// 0. [s, w] - admin
+    /// 1. [w] - staking pool pda
+    /// 2. [] - rewards token mint account
+    /// 3. [w] - rewards token escrow pda
+    /// 4. [] - system program
+    /// 5. [] - token program
+    /// 6. [] - rent account
+    AddRewardStream,
+
+    /// Stakes the rewards of the stream paying the staking token. Requires neither staking nor
+    /// claiming to be paused
+    ///
+    /// 0. [s] - user account who want to compound
+    /// 1. [w] - user state pda
+    /// 2. [w] - staking pool pda
+    /// 3. [w] - rewards token escrow pda of the staking token stream
+    /// 4. [w] - staking token escrow pda
+    /// 5. [] - staking token mint account
+    /// 6. [] - token program
+    /// 7. [w] - fee recipient staking token account, only while the pool charges a reward fee
+    Compound,
+
+    /// Sets the lock multiplier curve for new locks. The multiplier grows linearly from 1x to
+    /// `max_lock_multiplier` basis points at `max_lock_duration` seconds. A zero
+    /// `max_lock_duration` disables locking
+    ///
+    /// 0. [s] - admin
+    /// 1. [w] - staking pool pda
+    SetLockMultiplier {
+        max_lock_duration: u64,
+        max_lock_multiplier: u64,
+    },
+
+    /// Stops rewards on `amount` and queues it until the pool unbonding period has passed. The
+    /// early unstake penalty is withheld as in `Unstake` and only the rest is queued
+    ///
+    /// 0. [s, w] - user account who want to unstake
+    /// 1. [w] - user state pda
+    /// 2. [w] - staking pool pda
+    /// 3. [w] - withdrawal queue pda
+    /// 4. [] - system program
+    /// 5. [w] - staking token escrow pda
+    /// 6. [] - staking token mint account
+    /// 7. [] - token program
+    /// 8. [w] - penalty destination as in `Unstake`. Only needed while a penalty is due
+    RequestUnstake { amount: u64 },
+
+    /// Returns every queued amount whose cooldown has passed. The emptied queue is closed to the
+    /// user
+    ///
+    /// 0. [s, w] - user account who requested the unstake
+    /// 1. [w] - user staking token account
+    /// 2. [w] - withdrawal queue pda
+    /// 3. [w] - staking pool pda
+    /// 4. [w] - staking token escrow pda
+    /// 5. [] - staking token mint account
+    /// 6. [] - token program
+    CompleteUnstake,
+
+    /// Sets the cooldown in seconds of unstake requests made from now on. Zero allows `Unstake`
+    ///
+    /// 0. [s] - admin
+    /// 1. [w] - staking pool pda
+    SetUnbondingPeriod { unbonding_period: u64 },
+
+    /// Sets the early unstake penalty, decaying linearly from `penalty_bps` basis points right
+    /// after a stake to zero `decay_period` seconds later. Zero disables it
+    ///
+    /// 0. [s] - admin
+    /// 1. [w] - staking pool pda
+    SetUnstakePenalty { penalty_bps: u64, decay_period: u64 },
+
+    /// Sets the fee taken from claimed and compounded rewards, up to `MAX_FEE_BPS` basis points,
+    /// and the owner of the token accounts receiving it
+    ///
+    /// 0. [s] - admin
+    /// 1. [w] - staking pool pda
+    /// 2. [] - fee recipient
+    SetRewardFee { fee_bps: u64 },
+
+    /// Creates the receipt mint of an empty pool, owned by the staking token program with the
+    /// staking token decimals. From then on stakes are tokenized 1:1 and rewards follow the
+    /// receipt tokens. Locks, unbonding and `Compound` are not supported by such pools. A pool
+    /// re-created under a closed pool id reuses the emptied receipt mint of the closed one
+    ///
+    /// 0. [s, w] - admin
+    /// 1. [w] - staking pool pda
+    /// 2. [w] - receipt mint pda
+    /// 3. [] - staking token mint account
+    /// 4. [] - token program
+    /// 5. [] - system program
+    CreateReceiptMint,
+
+    /// Moves the holder's stake to their current receipt token balance, settling rewards earned
+    /// so far. Receipts sent away since the holder last settled earn nothing for them, and
+    /// anyone can sync any holder. Each user state is bound to the first receipt token account
+    /// synced to it
+    ///
+    /// 0. [s, w] - payer of the user state rent if it does not exist yet
+    /// 1. [] - holder receipt token account
+    /// 2. [w] - user state pda of the holder
+    /// 3. [w] - staking pool pda
+    /// 4. [] - system program
+    SyncReceiptBalance,
+
+    /// Settles the rewards of a user and releases their locked positions that have expired,
+    /// freeing the pool lock expiries they held. Anyone can release any user
+    ///
+    /// 0. [] - user owning the user state
+    /// 1. [w] - user state pda
+    /// 2. [w] - staking pool pda
+    ReleaseExpiredLocks,
+}
+
+impl StakingInstruction {
+    pub(crate) fn build(&self, program_id: &Pubkey, accounts: Vec<AccountMeta>) -> Instruction {
+        Instruction::new_with_bytes(*program_id, &self.try_to_vec().unwrap(), accounts)
+    }
+}
+
+/// Creates an `Init` instruction for pool `pool_id` of the given mints, each owned by the given
+/// token program. Early unstake penalties go to `penalty_treasury` if given. `reward_rate` only
+/// applies once `NotifyRewardAmount` funds a reward period without a duration.
+#[allow(clippy::too_many_arguments)]
+pub fn init(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    staking_token_program: &Pubkey,
+    rewards_token_program: &Pubkey,
+    pool_id: u64,
+    reward_rate: u64,
+    precision: u64,
+    penalty_treasury: Option<&Pubkey>,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (staking_token_escrow, _) = PdaHelper::find_staking_token_pda(&staking_pool, program_id);
+    let (rewards_token_escrow, _) =
+        PdaHelper::find_rewards_token_pda(&staking_pool, rewards_token_mint, program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*authority, true),
+        AccountMeta::new(staking_pool, false),
+        AccountMeta::new_readonly(*staking_token_mint, false),
+        AccountMeta::new_readonly(*rewards_token_mint, false),
+        AccountMeta::new(staking_token_escrow, false),
+        AccountMeta::new(rewards_token_escrow, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*staking_token_program, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*rewards_token_program, false),
+    ];
+    if let Some(penalty_treasury) = penalty_treasury {
+        accounts.push(AccountMeta::new_readonly(*penalty_treasury, false));
+    }
+    StakingInstruction::Init {
+        reward_rate,
+        precision,
+        pool_id,
+    }
+    .build(program_id, accounts)
+}
+
+/// Creates a `Stake` instruction moving `amount` from `user_staking_token_account` into the pool,
+/// locked for `lock_duration` seconds unless it is zero. Pools with a receipt mint require
+/// `user_receipt_token_account`.
+#[allow(clippy::too_many_arguments)]
+pub fn stake(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    user_staking_token_account: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    staking_token_program: &Pubkey,
+    pool_id: u64,
+    amount: u64,
+    lock_duration: u64,
+    user_receipt_token_account: Option<&Pubkey>,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (staking_token_escrow, _) = PdaHelper::find_staking_token_pda(&staking_pool, program_id);
+    let (user_state, _) = PdaHelper::find_user_state_pda(&staking_pool, user, program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*user, true),
+        AccountMeta::new(*user_staking_token_account, false),
+        AccountMeta::new(staking_token_escrow, false),
+        AccountMeta::new(user_state, false),
+        AccountMeta::new(staking_pool, false),
+        AccountMeta::new_readonly(*staking_token_mint, false),
+        AccountMeta::new_readonly(*staking_token_program, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    push_receipt_accounts(
+        &mut accounts,
+        &staking_pool,
+        user_receipt_token_account,
+        program_id,
+    );
+    StakingInstruction::Stake {
+        amount,
+        lock_duration,
+    }
+    .build(program_id, accounts)
+}
+
+/// Appends the receipt mint pda and `user_receipt_token_account` for pools with a receipt mint.
+fn push_receipt_accounts(
+    accounts: &mut Vec<AccountMeta>,
+    staking_pool: &Pubkey,
+    user_receipt_token_account: Option<&Pubkey>,
+    program_id: &Pubkey,
+) {
+    if let Some(user_receipt_token_account) = user_receipt_token_account {
+        let (receipt_mint, _) = PdaHelper::find_receipt_mint_pda(staking_pool, program_id);
+        accounts.push(AccountMeta::new(receipt_mint, false));
+        accounts.push(AccountMeta::new(*user_receipt_token_account, false));
+    }
+}
+
+/// Creates an `Unstake` instruction returning `amount` to `user_staking_token_account` and
+/// optionally closing the emptied user state. Pools with a receipt mint require
+/// `user_receipt_token_account`.
+#[allow(clippy::too_many_arguments)]
+pub fn unstake(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    user_staking_token_account: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    staking_token_program: &Pubkey,
+    pool_id: u64,
+    amount: u64,
+    close: bool,
+    penalty_treasury: Option<&Pubkey>,
+    user_receipt_token_account: Option<&Pubkey>,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (staking_token_escrow, _) = PdaHelper::find_staking_token_pda(&staking_pool, program_id);
+    let (user_state, _) = PdaHelper::find_user_state_pda(&staking_pool, user, program_id);
+    let penalty_destination = penalty_treasury.copied().unwrap_or_else(|| {
+        PdaHelper::find_rewards_token_pda(&staking_pool, staking_token_mint, program_id).0
+    });
+    let mut accounts = vec![
+        AccountMeta::new(*user, true),
+        AccountMeta::new(*user_staking_token_account, false),
+        AccountMeta::new(user_state, false),
+        AccountMeta::new(staking_pool, false),
+        AccountMeta::new(staking_token_escrow, false),
+        AccountMeta::new_readonly(staking_token_escrow, false),
+        AccountMeta::new_readonly(*staking_token_mint, false),
+        AccountMeta::new_readonly(*staking_token_program, false),
+    ];
+    push_receipt_accounts(
+        &mut accounts,
+        &staking_pool,
+        user_receipt_token_account,
+        program_id,
+    );
+    accounts.push(AccountMeta::new(penalty_destination, false));
+    StakingInstruction::Unstake { amount, close }.build(program_id, accounts)
+}
+
+/// Creates an `EmergencyWithdraw` instruction returning the whole stake and forfeiting rewards.
+/// Pools with a receipt mint require `user_receipt_token_account`. `penalty_treasury` is the pool
+/// penalty treasury, if it has one. The withdrawal queue of `user` is always passed, so the stake
+/// can be queued in pools with an unbonding period.
+#[allow(clippy::too_many_arguments)]
+pub fn emergency_withdraw(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    user_staking_token_account: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    staking_token_program: &Pubkey,
+    pool_id: u64,
+    user_receipt_token_account: Option<&Pubkey>,
+    penalty_treasury: Option<&Pubkey>,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (staking_token_escrow, _) = PdaHelper::find_staking_token_pda(&staking_pool, program_id);
+    let (user_state, _) = PdaHelper::find_user_state_pda(&staking_pool, user, program_id);
+    let penalty_destination = penalty_treasury.copied().unwrap_or_else(|| {
+        PdaHelper::find_rewards_token_pda(&staking_pool, staking_token_mint, program_id).0
+    });
+    let mut accounts = vec![
+        AccountMeta::new(*user, true),
+        AccountMeta::new(*user_staking_token_account, false),
+        AccountMeta::new(user_state, false),
+        AccountMeta::new(staking_pool, false),
+        AccountMeta::new(staking_token_escrow, false),
+        AccountMeta::new_readonly(staking_token_escrow, false),
+        AccountMeta::new_readonly(*staking_token_mint, false),
+        AccountMeta::new_readonly(*staking_token_program, false),
+    ];
+    push_receipt_accounts(
+        &mut accounts,
+        &staking_pool,
+        user_receipt_token_account,
+        program_id,
+    );
+    accounts.push(AccountMeta::new(penalty_destination, false));
+    let (withdrawal_queue, _) =
+        PdaHelper::find_withdrawal_queue_pda(&staking_pool, user, program_id);
+    accounts.push(AccountMeta::new(withdrawal_queue, false));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    StakingInstruction::EmergencyWithdraw.build(program_id, accounts)
+}
+
+/// Creates a `GetRewards` instruction paying the `(user rewards token account, rewards token
+/// mint, token program)` of `claim` and then of each of `other_claims`. While the pool charges a
+/// reward fee `fee_token_accounts` holds the fee recipient token account of each claim, otherwise
+/// it is empty. Pools with a receipt mint require `user_receipt_token_account`.
+#[allow(clippy::too_many_arguments)]
+pub fn get_rewards(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+    claim: &(Pubkey, Pubkey, Pubkey),
+    other_claims: &[(Pubkey, Pubkey, Pubkey)],
+    fee_token_accounts: &[Pubkey],
+    user_receipt_token_account: Option<&Pubkey>,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (user_state, _) = PdaHelper::find_user_state_pda(&staking_pool, user, program_id);
+    let (user_rewards_token_account, stream_mint, token_program) = claim;
+    let (rewards_token_escrow, _) =
+        PdaHelper::find_rewards_token_pda(&staking_pool, stream_mint, program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*user, true),
+        AccountMeta::new(*user_rewards_token_account, false),
+        AccountMeta::new(user_state, false),
+        AccountMeta::new(staking_pool, false),
+        AccountMeta::new(rewards_token_escrow, false),
+        AccountMeta::new_readonly(rewards_token_escrow, false),
+        AccountMeta::new_readonly(*stream_mint, false),
+        AccountMeta::new_readonly(*token_program, false),
+    ];
+    let mut fee_token_accounts = fee_token_accounts.iter();
+    if let Some(fee_token_account) = fee_token_accounts.next() {
+        accounts.push(AccountMeta::new(*fee_token_account, false));
+    }
+    if let Some(user_receipt_token_account) = user_receipt_token_account {
+        accounts.push(AccountMeta::new_readonly(
+            *user_receipt_token_account,
+            false,
+        ));
+    }
+    for (user_rewards_token_account, stream_mint, token_program) in other_claims {
+        let (rewards_token_escrow, _) =
+            PdaHelper::find_rewards_token_pda(&staking_pool, stream_mint, program_id);
+        accounts.push(AccountMeta::new(*user_rewards_token_account, false));
+        accounts.push(AccountMeta::new(rewards_token_escrow, false));
+        accounts.push(AccountMeta::new_readonly(*stream_mint, false));
+        accounts.push(AccountMeta::new_readonly(*token_program, false));
+        if let Some(fee_token_account) = fee_token_accounts.next() {
+            accounts.push(AccountMeta::new(*fee_token_account, false));
+        }
+    }
+    StakingInstruction::GetRewards.build(program_id, accounts)
+}
+
+/// Creates a `SetRewardRate` instruction for the stream paying `stream_mint`.
+pub fn set_reward_rate(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+    stream_mint: &Pubkey,
+    reward_rate: u64,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (rewards_token_escrow, _) =
+        PdaHelper::find_rewards_token_pda(&staking_pool, stream_mint, program_id);
+    StakingInstruction::SetRewardRate { reward_rate }.build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(staking_pool, false),
+            AccountMeta::new_readonly(*stream_mint, false),
+            AccountMeta::new_readonly(rewards_token_escrow, false),
+        ],
+    )
+}
+
+/// Creates a `NotifyRewardAmount` instruction funding the stream paying `stream_mint` with
+/// `amount` over `duration` seconds.
+#[allow(clippy::too_many_arguments)]
+pub fn notify_reward_amount(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    admin_rewards_token_account: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+    stream_mint: &Pubkey,
+    token_program: &Pubkey,
+    amount: u64,
+    duration: u64,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (rewards_token_escrow, _) =
+        PdaHelper::find_rewards_token_pda(&staking_pool, stream_mint, program_id);
+    StakingInstruction::NotifyRewardAmount { amount, duration }.build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*admin_rewards_token_account, false),
+            AccountMeta::new(staking_pool, false),
+            AccountMeta::new(rewards_token_escrow, false),
+            AccountMeta::new_readonly(*stream_mint, false),
+            AccountMeta::new_readonly(*token_program, false),
+        ],
+    )
+}
+
+/// Creates a `WithdrawExcessRewards` instruction returning tokens of the stream paying
+/// `stream_mint` that are not owed to stakers.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_excess_rewards(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    admin_rewards_token_account: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+    stream_mint: &Pubkey,
+    token_program: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (rewards_token_escrow, _) =
+        PdaHelper::find_rewards_token_pda(&staking_pool, stream_mint, program_id);
+    StakingInstruction::WithdrawExcessRewards { amount }.build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*admin_rewards_token_account, false),
+            AccountMeta::new(staking_pool, false),
+            AccountMeta::new(rewards_token_escrow, false),
+            AccountMeta::new_readonly(*stream_mint, false),
+            AccountMeta::new_readonly(*token_program, false),
+        ],
+    )
+}
+
+/// Creates a `ProposeAdmin` instruction. Pass `Pubkey::default()` to cancel a pending transfer.
+pub fn propose_admin(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+    new_admin: &Pubkey,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    StakingInstruction::ProposeAdmin {
+        new_admin: *new_admin,
+    }
+    .build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(staking_pool, false),
+        ],
+    )
+}
+
+/// Creates an `AcceptAdmin` instruction signed by the pending admin.
+pub fn accept_admin(
+    program_id: &Pubkey,
+    pending_admin: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    StakingInstruction::AcceptAdmin.build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(*pending_admin, true),
+            AccountMeta::new(staking_pool, false),
+        ],
+    )
+}
+
+/// Creates a `Pause` instruction for the operations whose flag is set.
+#[allow(clippy::too_many_arguments)]
+pub fn pause(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+    staking: bool,
+    claiming: bool,
+    accrual: bool,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    StakingInstruction::Pause {
+        staking,
+        claiming,
+        accrual,
+    }
+    .build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(staking_pool, false),
+        ],
+    )
+}
+
+/// Creates an `Unpause` instruction for the operations whose flag is set.
+#[allow(clippy::too_many_arguments)]
+pub fn unpause(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+    staking: bool,
+    claiming: bool,
+    accrual: bool,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    StakingInstruction::Unpause {
+        staking,
+        claiming,
+        accrual,
+    }
+    .build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(staking_pool, false),
+        ],
+    )
+}
+
+/// Creates a `CloseUserState` instruction refunding the user state rent to `user`.
+pub fn close_user_state(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (user_state, _) = PdaHelper::find_user_state_pda(&staking_pool, user, program_id);
+    StakingInstruction::CloseUserState.build(
+        program_id,
+        vec![
+            AccountMeta::new(*user, false),
+            AccountMeta::new(user_state, false),
+            AccountMeta::new(staking_pool, false),
+        ],
+    )
+}
+
+/// Creates a `ClosePool` instruction sweeping the escrows to the admin token accounts.
+/// `other_streams` holds an admin token account, the mint and its token program for every reward
+/// stream after the first, in stream order. Set `has_receipt_mint` for pools with a receipt mint.
+#[allow(clippy::too_many_arguments)]
+pub fn close_pool(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    admin_staking_token_account: &Pubkey,
+    admin_rewards_token_account: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    staking_token_program: &Pubkey,
+    rewards_token_program: &Pubkey,
+    pool_id: u64,
+    other_streams: &[(Pubkey, Pubkey, Pubkey)],
+    has_receipt_mint: bool,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (staking_token_escrow, _) = PdaHelper::find_staking_token_pda(&staking_pool, program_id);
+    let (rewards_token_escrow, _) =
+        PdaHelper::find_rewards_token_pda(&staking_pool, rewards_token_mint, program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(*admin_staking_token_account, false),
+        AccountMeta::new(*admin_rewards_token_account, false),
+        AccountMeta::new(staking_pool, false),
+        AccountMeta::new(staking_token_escrow, false),
+        AccountMeta::new(rewards_token_escrow, false),
+        AccountMeta::new_readonly(*staking_token_mint, false),
+        AccountMeta::new_readonly(*rewards_token_mint, false),
+        AccountMeta::new_readonly(*staking_token_program, false),
+        AccountMeta::new_readonly(*rewards_token_program, false),
+    ];
+    if has_receipt_mint {
+        let (receipt_mint, _) = PdaHelper::find_receipt_mint_pda(&staking_pool, program_id);
+        accounts.push(AccountMeta::new_readonly(receipt_mint, false));
+    }
+    for (admin_rewards_token_account, stream_mint, token_program) in other_streams {
+        let (rewards_token_escrow, _) =
+            PdaHelper::find_rewards_token_pda(&staking_pool, stream_mint, program_id);
+        accounts.push(AccountMeta::new(*admin_rewards_token_account, false));
+        accounts.push(AccountMeta::new(rewards_token_escrow, false));
+        accounts.push(AccountMeta::new_readonly(*stream_mint, false));
+        accounts.push(AccountMeta::new_readonly(*token_program, false));
+    }
+    StakingInstruction::ClosePool.build(program_id, accounts)
+}
+
+/// Creates a `Compound` instruction staking the rewards `user` earned in the staking token. Pass
+/// the fee recipient staking token account while the pool charges a reward fee.
+pub fn compound(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    staking_token_program: &Pubkey,
+    pool_id: u64,
+    fee_token_account: Option<&Pubkey>,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (user_state, _) = PdaHelper::find_user_state_pda(&staking_pool, user, program_id);
+    let (rewards_token_escrow, _) =
+        PdaHelper::find_rewards_token_pda(&staking_pool, staking_token_mint, program_id);
+    let (staking_token_escrow, _) = PdaHelper::find_staking_token_pda(&staking_pool, program_id);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new(user_state, false),
+        AccountMeta::new(staking_pool, false),
+        AccountMeta::new(rewards_token_escrow, false),
+        AccountMeta::new(staking_token_escrow, false),
+        AccountMeta::new_readonly(*staking_token_mint, false),
+        AccountMeta::new_readonly(*staking_token_program, false),
+    ];
+    if let Some(fee_token_account) = fee_token_account {
+        accounts.push(AccountMeta::new(*fee_token_account, false));
+    }
+    StakingInstruction::Compound.build(program_id, accounts)
+}
+
+/// Creates a `SetLockMultiplier` instruction for locks made from now on.
+pub fn set_lock_multiplier(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+    max_lock_duration: u64,
+    max_lock_multiplier: u64,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    StakingInstruction::SetLockMultiplier {
+        max_lock_duration,
+        max_lock_multiplier,
+    }
+    .build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(staking_pool, false),
+        ],
+    )
+}
+
+/// Creates a `RequestUnstake` instruction queueing `amount` for the pool unbonding period.
+/// `penalty_treasury` is the pool penalty treasury, if it has one.
+#[allow(clippy::too_many_arguments)]
+pub fn request_unstake(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    staking_token_program: &Pubkey,
+    pool_id: u64,
+    amount: u64,
+    penalty_treasury: Option<&Pubkey>,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (user_state, _) = PdaHelper::find_user_state_pda(&staking_pool, user, program_id);
+    let (withdrawal_queue, _) =
+        PdaHelper::find_withdrawal_queue_pda(&staking_pool, user, program_id);
+    let (staking_token_escrow, _) = PdaHelper::find_staking_token_pda(&staking_pool, program_id);
+    let penalty_destination = penalty_treasury.copied().unwrap_or_else(|| {
+        PdaHelper::find_rewards_token_pda(&staking_pool, staking_token_mint, program_id).0
+    });
+    StakingInstruction::RequestUnstake { amount }.build(
+        program_id,
+        vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(user_state, false),
+            AccountMeta::new(staking_pool, false),
+            AccountMeta::new(withdrawal_queue, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(staking_token_escrow, false),
+            AccountMeta::new_readonly(*staking_token_mint, false),
+            AccountMeta::new_readonly(*staking_token_program, false),
+            AccountMeta::new(penalty_destination, false),
+        ],
+    )
+}
+
+/// Creates a `CompleteUnstake` instruction paying matured requests to
+/// `user_staking_token_account`.
+pub fn complete_unstake(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    user_staking_token_account: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    staking_token_program: &Pubkey,
+    pool_id: u64,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (withdrawal_queue, _) =
+        PdaHelper::find_withdrawal_queue_pda(&staking_pool, user, program_id);
+    let (staking_token_escrow, _) = PdaHelper::find_staking_token_pda(&staking_pool, program_id);
+    StakingInstruction::CompleteUnstake.build(
+        program_id,
+        vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(*user_staking_token_account, false),
+            AccountMeta::new(withdrawal_queue, false),
+            AccountMeta::new(staking_pool, false),
+            AccountMeta::new(staking_token_escrow, false),
+            AccountMeta::new_readonly(*staking_token_mint, false),
+            AccountMeta::new_readonly(*staking_token_program, false),
+        ],
+    )
+}
+
+/// Creates a `SetUnbondingPeriod` instruction.
+pub fn set_unbonding_period(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+    unbonding_period: u64,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    StakingInstruction::SetUnbondingPeriod { unbonding_period }.build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(staking_pool, false),
+        ],
+    )
+}
+
+/// Creates a `SetUnstakePenalty` instruction.
+pub fn set_unstake_penalty(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+    penalty_bps: u64,
+    decay_period: u64,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    StakingInstruction::SetUnstakePenalty {
+        penalty_bps,
+        decay_period,
+    }
+    .build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(staking_pool, false),
+        ],
+    )
+}
+
+/// Creates a `SetRewardFee` instruction sending `fee_bps` of claimed rewards to token accounts
+/// owned by `fee_recipient`.
+pub fn set_reward_fee(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+    fee_recipient: &Pubkey,
+    fee_bps: u64,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    StakingInstruction::SetRewardFee { fee_bps }.build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(staking_pool, false),
+            AccountMeta::new_readonly(*fee_recipient, false),
+        ],
+    )
+}
+
+/// Creates a `CreateReceiptMint` instruction for pool `pool_id`.
+pub fn create_receipt_mint(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    staking_token_program: &Pubkey,
+    pool_id: u64,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (receipt_mint, _) = PdaHelper::find_receipt_mint_pda(&staking_pool, program_id);
+    StakingInstruction::CreateReceiptMint.build(
+        program_id,
+        vec![
+            AccountMeta::new(*admin, true),
+            AccountMeta::new(staking_pool, false),
+            AccountMeta::new(receipt_mint, false),
+            AccountMeta::new_readonly(*staking_token_mint, false),
+            AccountMeta::new_readonly(*staking_token_program, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates a `SyncReceiptBalance` instruction for the holder of `receipt_token_account`, paid by
+/// `payer`.
+pub fn sync_receipt_balance(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    holder: &Pubkey,
+    receipt_token_account: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (user_state, _) = PdaHelper::find_user_state_pda(&staking_pool, holder, program_id);
+    StakingInstruction::SyncReceiptBalance.build(
+        program_id,
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*receipt_token_account, false),
+            AccountMeta::new(user_state, false),
+            AccountMeta::new(staking_pool, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates a `ReleaseExpiredLocks` instruction for the positions of `user`.
+pub fn release_expired_locks(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (user_state, _) = PdaHelper::find_user_state_pda(&staking_pool, user, program_id);
+    StakingInstruction::ReleaseExpiredLocks.build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(*user, false),
+            AccountMeta::new(user_state, false),
+            AccountMeta::new(staking_pool, false),
+        ],
+    )
+}
+
+/// Creates an `AddRewardStream` instruction paying `stream_mint` as an additional reward.
+pub fn add_reward_stream(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    staking_token_mint: &Pubkey,
+    rewards_token_mint: &Pubkey,
+    pool_id: u64,
+    stream_mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        staking_token_mint,
+        rewards_token_mint,
+        pool_id,
+        program_id,
+    );
+    let (rewards_token_escrow, _) =
+        PdaHelper::find_rewards_token_pda(&staking_pool, stream_mint, program_id);
+    StakingInstruction::AddRewardStream.build(
+        program_id,
+        vec![
+            AccountMeta::new(*admin, true),
+            AccountMeta::new(staking_pool, false),
+            AccountMeta::new_readonly(*stream_mint, false),
+            AccountMeta::new(rewards_token_escrow, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
 }
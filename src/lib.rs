@@ -1,8 +1,13 @@
+pub mod cpi;
+#[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
+pub mod error;
 pub mod instruction;
+pub mod math;
 pub mod pda_helper;
 pub mod processor;
 pub mod state;
+pub mod validator;
 
 // Program Id: FSdEubh9fHvWXZ9TvMQBsMSdUS7ZmmZyDvp6p6S1tjcf
 // Owner: BPFLoaderUpgradeab1e11111111111111111111111
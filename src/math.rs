@@ -0,0 +1,162 @@
+use crate::error::StakingError;
+use crate::state::{LOCK_MULTIPLIER_BASE, MAX_UNSTAKE_PENALTY_BPS};
+
+/// Basis points in a whole.
+const BPS_BASE: u128 = 10_000;
+
+/// Reward per staked token accumulated after `elapsed` seconds of emission at `reward_rate`,
+/// scaled by `precision`.
+pub fn reward_per_token(
+    reward_per_token_stored: u128,
+    reward_rate: u64,
+    elapsed: u64,
+    precision: u64,
+    total_supply: u64,
+) -> Result<u128, StakingError> {
+    if total_supply == 0 {
+        return Ok(reward_per_token_stored);
+    }
+    let accrued = (reward_rate as u128)
+        .checked_mul(elapsed as u128)
+        .and_then(|rewards| rewards.checked_mul(precision as u128))
+        .ok_or(StakingError::ArithmeticOverflow)?
+        / total_supply as u128;
+    reward_per_token_stored
+        .checked_add(accrued)
+        .ok_or(StakingError::ArithmeticOverflow)
+}
+
+/// Rewards of a staker with `balance` accrued since `reward_per_token_paid`, scaled by precision.
+pub fn accrued(
+    balance: u64,
+    reward_per_token: u128,
+    reward_per_token_paid: u128,
+) -> Result<u128, StakingError> {
+    reward_per_token
+        .checked_sub(reward_per_token_paid)
+        .and_then(|delta| delta.checked_mul(balance as u128))
+        .ok_or(StakingError::ArithmeticOverflow)
+}
+
+/// Rewards of a staker with `balance` who last settled at `reward_per_token_paid`.
+pub fn earned(
+    balance: u64,
+    reward_per_token: u128,
+    reward_per_token_paid: u128,
+    precision: u64,
+    rewards: u64,
+) -> Result<u64, StakingError> {
+    earned_from_accrued(
+        accrued(balance, reward_per_token, reward_per_token_paid)?,
+        precision,
+        rewards,
+    )
+}
+
+/// Rewards of a staker who accrued `accrued`, scaled by precision, since settling `rewards`.
+pub fn earned_from_accrued(
+    accrued: u128,
+    precision: u64,
+    rewards: u64,
+) -> Result<u64, StakingError> {
+    if precision == 0 {
+        return Err(StakingError::InvalidPrecision);
+    }
+    u64::try_from(accrued / precision as u128)
+        .ok()
+        .and_then(|pending| pending.checked_add(rewards))
+        .ok_or(StakingError::ArithmeticOverflow)
+}
+
+/// Upper bound of the rewards stakers can still claim: settled but unclaimed rewards plus the
+/// scaled accruals not settled yet, rounded up.
+pub fn rewards_owed(
+    rewards_unclaimed: u64,
+    rewards_accrued: u128,
+    precision: u64,
+) -> Result<u64, StakingError> {
+    if precision == 0 {
+        return Err(StakingError::InvalidPrecision);
+    }
+    let accrued = rewards_accrued.div_ceil(precision as u128);
+    u64::try_from(accrued)
+        .ok()
+        .and_then(|accrued| accrued.checked_add(rewards_unclaimed))
+        .ok_or(StakingError::ArithmeticOverflow)
+}
+
+/// Balance rewards accrue against for `amount` locked for `lock_duration` seconds. The multiplier
+/// grows linearly from 1x without a lock to `max_lock_multiplier` basis points at
+/// `max_lock_duration`; longer locks are capped.
+pub fn locked_effective_balance(
+    amount: u64,
+    lock_duration: u64,
+    max_lock_duration: u64,
+    max_lock_multiplier: u64,
+) -> Result<u64, StakingError> {
+    if max_lock_duration == 0 {
+        return Ok(amount);
+    }
+    let boost = (max_lock_multiplier.saturating_sub(LOCK_MULTIPLIER_BASE) as u128)
+        .checked_mul(lock_duration.min(max_lock_duration) as u128)
+        .ok_or(StakingError::ArithmeticOverflow)?
+        / max_lock_duration as u128;
+    let effective = (amount as u128)
+        .checked_mul(LOCK_MULTIPLIER_BASE as u128 + boost)
+        .ok_or(StakingError::ArithmeticOverflow)?
+        / LOCK_MULTIPLIER_BASE as u128;
+    u64::try_from(effective).map_err(|_| StakingError::ArithmeticOverflow)
+}
+
+/// Part of `amount` withheld when unstaking `elapsed` seconds after the last stake. The penalty
+/// decays linearly from `penalty_bps` basis points to zero over `decay_period` seconds.
+pub fn unstake_penalty(
+    amount: u64,
+    penalty_bps: u64,
+    decay_period: u64,
+    elapsed: u64,
+) -> Result<u64, StakingError> {
+    if elapsed >= decay_period {
+        return Ok(0);
+    }
+    let penalty = (amount as u128)
+        .checked_mul(penalty_bps as u128)
+        .and_then(|penalty| penalty.checked_mul((decay_period - elapsed) as u128))
+        .ok_or(StakingError::ArithmeticOverflow)?
+        / (MAX_UNSTAKE_PENALTY_BPS as u128 * decay_period as u128);
+    u64::try_from(penalty).map_err(|_| StakingError::ArithmeticOverflow)
+}
+
+/// Stake timestamp of `balance` staked at `last_stake` after `received` more arrives at
+/// `current_timestamp`, weighted by amount so a small deposit barely moves it.
+pub fn weighted_stake_timestamp(
+    last_stake: i64,
+    balance: u64,
+    current_timestamp: i64,
+    received: u64,
+) -> Result<i64, StakingError> {
+    let total = (balance as i128)
+        .checked_add(received as i128)
+        .ok_or(StakingError::ArithmeticOverflow)?;
+    if total == 0 {
+        return Ok(current_timestamp);
+    }
+    let weighted = (last_stake as i128)
+        .checked_mul(balance as i128)
+        .and_then(|weighted| {
+            (current_timestamp as i128)
+                .checked_mul(received as i128)
+                .and_then(|received| weighted.checked_add(received))
+        })
+        .ok_or(StakingError::ArithmeticOverflow)?;
+    i64::try_from(weighted / total).map_err(|_| StakingError::ArithmeticOverflow)
+}
+
+/// Fee of `fee_bps` basis points taken from `rewards`, rounded down.
+pub fn reward_fee(rewards: u64, fee_bps: u64) -> Result<u64, StakingError> {
+    let fee = (rewards as u128)
+        .checked_mul(fee_bps as u128)
+        .ok_or(StakingError::ArithmeticOverflow)?
+        / BPS_BASE;
+    u64::try_from(fee).map_err(|_| StakingError::ArithmeticOverflow)
+}
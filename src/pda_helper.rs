@@ -1,55 +1,72 @@
-use solana_program::{pubkey::Pubkey, sysvar::slot_history::AccountInfo};
+use solana_program::pubkey::Pubkey;
 
 pub struct PdaHelper;
 
 impl PdaHelper {
+    /// `pool_id` allows several independent pools for the same mint pair.
     pub fn find_staking_pool_pda(
-        staking_token_mint_ai: &AccountInfo,
-        rewards_token_mint_ai: &AccountInfo,
+        staking_token_mint: &Pubkey,
+        rewards_token_mint: &Pubkey,
+        pool_id: u64,
         program_id: &Pubkey,
     ) -> (Pubkey, u8) {
         Pubkey::find_program_address(
             &[
-                &staking_token_mint_ai.key.to_bytes(),
-                &rewards_token_mint_ai.key.to_bytes(),
+                &staking_token_mint.to_bytes(),
+                &rewards_token_mint.to_bytes(),
+                &pool_id.to_le_bytes(),
                 b"staking-pool",
             ],
             program_id,
         )
     }
 
-    pub fn find_staking_token_pda(
-        staking_token_mint_ai: &AccountInfo,
+    pub fn find_staking_token_pda(staking_pool: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[&staking_pool.to_bytes(), b"staking-token"], program_id)
+    }
+
+    pub fn find_rewards_token_pda(
+        staking_pool: &Pubkey,
+        rewards_token_mint: &Pubkey,
         program_id: &Pubkey,
     ) -> (Pubkey, u8) {
         Pubkey::find_program_address(
-            &[&staking_token_mint_ai.key.to_bytes(), b"staking-token"],
+            &[
+                &staking_pool.to_bytes(),
+                &rewards_token_mint.to_bytes(),
+                b"rewards-token",
+            ],
             program_id,
         )
     }
 
-    pub fn find_rewards_token_pda(
-        rewards_token_mint_ai: &AccountInfo,
+    pub fn find_user_state_pda(
+        staking_pool: &Pubkey,
+        user: &Pubkey,
         program_id: &Pubkey,
     ) -> (Pubkey, u8) {
         Pubkey::find_program_address(
-            &[&rewards_token_mint_ai.key.to_bytes(), b"rewards-token"],
+            &[&staking_pool.to_bytes(), &user.to_bytes(), b"user-state"],
             program_id,
         )
     }
 
-    pub fn find_user_state_pda(
-        staking_pool_pda: &AccountInfo,
-        user_account: &AccountInfo,
+    pub fn find_withdrawal_queue_pda(
+        staking_pool: &Pubkey,
+        user: &Pubkey,
         program_id: &Pubkey,
     ) -> (Pubkey, u8) {
         Pubkey::find_program_address(
             &[
-                &staking_pool_pda.key.to_bytes(),
-                &user_account.key.to_bytes(),
-                b"user-state",
+                &staking_pool.to_bytes(),
+                &user.to_bytes(),
+                b"withdrawal-queue",
             ],
             program_id,
         )
     }
+
+    pub fn find_receipt_mint_pda(staking_pool: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[&staking_pool.to_bytes(), b"receipt-mint"], program_id)
+    }
 }
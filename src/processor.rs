@@ -1,7 +1,16 @@
+use crate::error::StakingError;
 use crate::instruction::StakingInstruction;
+use crate::math;
 use crate::pda_helper::PdaHelper;
-use crate::state::StakingPoolState;
-use crate::state::UserState;
+use crate::state::{
+    LockExpiry, LockedPosition, UnbondingRequest, UserState, WithdrawalQueue, MAX_LOCKED_POSITIONS,
+    MAX_LOCK_EXPIRIES, MAX_UNBONDING_REQUESTS,
+};
+use crate::state::{
+    RewardStream, StakingPoolState, LOCK_MULTIPLIER_BASE, MAX_FEE_BPS, MAX_REWARD_STREAMS,
+    MAX_UNSTAKE_PENALTY_BPS,
+};
+use crate::validator::Validator;
 use borsh::BorshDeserialize;
 use solana_program::clock::UnixTimestamp;
 use solana_program::program::invoke;
@@ -10,30 +19,149 @@ use solana_program::sysvar::clock::Clock;
 use solana_program::sysvar::Sysvar;
 use solana_program::{
     account_info::next_account_info, account_info::AccountInfo, entrypoint::ProgramResult, msg,
-    program_error::ProgramError, program_pack::Pack, pubkey::Pubkey, rent::Rent,
+    program_error::ProgramError, pubkey::Pubkey, rent::Rent,
+};
+use spl_token_2022::{
+    extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+    state::{Account, Mint},
 };
-use spl_token::state::Account;
 
 pub struct Processor;
 
 impl Processor {
-    const REWARD_RATE: u64 = 100;
-
     pub fn process(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         instruction_data: &[u8],
     ) -> ProgramResult {
         let instructions = StakingInstruction::try_from_slice(instruction_data)?;
+        let current_timestamp = Clock::get()?.unix_timestamp;
         match instructions {
-            StakingInstruction::Init => Self::initialize(program_id, accounts),
-            StakingInstruction::Stake { amount } => Self::stake(program_id, accounts, amount),
-            StakingInstruction::Unstake { amount } => Self::unstake(program_id, accounts, amount),
-            StakingInstruction::GetRewards => Self::get_rewards(program_id, accounts),
+            StakingInstruction::Init {
+                reward_rate,
+                precision,
+                pool_id,
+            } => Self::initialize(
+                program_id,
+                accounts,
+                reward_rate,
+                precision,
+                pool_id,
+                current_timestamp,
+            ),
+            StakingInstruction::Stake {
+                amount,
+                lock_duration,
+            } => Self::stake(
+                program_id,
+                accounts,
+                amount,
+                lock_duration,
+                current_timestamp,
+            ),
+            StakingInstruction::Unstake { amount, close } => {
+                Self::unstake(program_id, accounts, amount, close, current_timestamp)
+            }
+            StakingInstruction::CloseUserState => Self::close_user_state(program_id, accounts),
+            StakingInstruction::ClosePool => Self::close_pool(program_id, accounts),
+            StakingInstruction::AddRewardStream => {
+                Self::add_reward_stream(program_id, accounts, current_timestamp)
+            }
+            StakingInstruction::GetRewards => {
+                Self::get_rewards(program_id, accounts, current_timestamp)
+            }
+            StakingInstruction::Compound => Self::compound(program_id, accounts, current_timestamp),
+            StakingInstruction::SetRewardRate { reward_rate } => {
+                Self::set_reward_rate(program_id, accounts, reward_rate, current_timestamp)
+            }
+            StakingInstruction::NotifyRewardAmount { amount, duration } => {
+                Self::notify_reward_amount(
+                    program_id,
+                    accounts,
+                    amount,
+                    duration,
+                    current_timestamp,
+                )
+            }
+            StakingInstruction::WithdrawExcessRewards { amount } => {
+                Self::withdraw_excess_rewards(program_id, accounts, amount, current_timestamp)
+            }
+            StakingInstruction::ProposeAdmin { new_admin } => {
+                Self::propose_admin(program_id, accounts, new_admin)
+            }
+            StakingInstruction::AcceptAdmin => Self::accept_admin(program_id, accounts),
+            StakingInstruction::RequestUnstake { amount } => {
+                Self::request_unstake(program_id, accounts, amount, current_timestamp)
+            }
+            StakingInstruction::CompleteUnstake => {
+                Self::complete_unstake(program_id, accounts, current_timestamp)
+            }
+            StakingInstruction::SetUnbondingPeriod { unbonding_period } => {
+                Self::set_unbonding_period(program_id, accounts, unbonding_period)
+            }
+            StakingInstruction::SetUnstakePenalty {
+                penalty_bps,
+                decay_period,
+            } => Self::set_unstake_penalty(program_id, accounts, penalty_bps, decay_period),
+            StakingInstruction::SetRewardFee { fee_bps } => {
+                Self::set_reward_fee(program_id, accounts, fee_bps)
+            }
+            StakingInstruction::CreateReceiptMint => {
+                Self::create_receipt_mint(program_id, accounts)
+            }
+            StakingInstruction::SyncReceiptBalance => {
+                Self::sync_receipt_balance(program_id, accounts, current_timestamp)
+            }
+            StakingInstruction::ReleaseExpiredLocks => {
+                Self::release_expired_locks(program_id, accounts, current_timestamp)
+            }
+            StakingInstruction::SetLockMultiplier {
+                max_lock_duration,
+                max_lock_multiplier,
+            } => Self::set_lock_multiplier(
+                program_id,
+                accounts,
+                max_lock_duration,
+                max_lock_multiplier,
+            ),
+            StakingInstruction::EmergencyWithdraw => {
+                Self::emergency_withdraw(program_id, accounts, current_timestamp)
+            }
+            StakingInstruction::Pause {
+                staking,
+                claiming,
+                accrual,
+            } => Self::set_paused(
+                program_id,
+                accounts,
+                staking.then_some(true),
+                claiming.then_some(true),
+                accrual.then_some(true),
+                current_timestamp,
+            ),
+            StakingInstruction::Unpause {
+                staking,
+                claiming,
+                accrual,
+            } => Self::set_paused(
+                program_id,
+                accounts,
+                staking.then_some(false),
+                claiming.then_some(false),
+                accrual.then_some(false),
+                current_timestamp,
+            ),
         }
     }
 
-    fn initialize(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    fn initialize(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        reward_rate: u64,
+        precision: u64,
+        pool_id: u64,
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
         let authority = next_account_info(accounts_iter)?;
         let staking_pool_pda_ai = next_account_info(accounts_iter)?;
@@ -44,174 +172,366 @@ impl Processor {
         let system_program = next_account_info(accounts_iter)?;
         let token_program = next_account_info(accounts_iter)?;
         let rent_account = next_account_info(accounts_iter)?;
-        let clock = Clock::get()?;
+        let rewards_token_program = next_account_info(accounts_iter)?;
+        let penalty_treasury = next_account_info(accounts_iter).ok();
+        Validator::check_signer(authority)?;
+        Validator::check_system_program(system_program)?;
+        Validator::check_token_program(token_program, staking_token_mint_account)?;
+        Validator::check_token_program(rewards_token_program, rewards_token_mint_account)?;
+        if let Some(penalty_treasury) = penalty_treasury {
+            Validator::check_penalty_treasury(penalty_treasury, staking_token_mint_account)?;
+        }
+        if precision == 0 {
+            msg!("Precision = 0");
+            return Err(StakingError::InvalidPrecision.into());
+        }
         let (staking_pool_pda, bump_seed) = PdaHelper::find_staking_pool_pda(
-            staking_token_mint_account,
-            rewards_token_mint_account,
+            staking_token_mint_account.key,
+            rewards_token_mint_account.key,
+            pool_id,
             program_id,
         );
         if *staking_pool_pda_ai.key != staking_pool_pda {
             msg!("Staking pool pda passed: {}", staking_pool_pda_ai.key);
             msg!("Staking pool pda computed: {}", staking_pool_pda);
-            return Err(ProgramError::InvalidAccountData);
+            return Err(StakingError::InvalidStakingPoolPda.into());
         }
         if !staking_pool_pda_ai.try_data_is_empty()? {
-            let mut staking_state =
-                StakingPoolState::try_from_slice(&staking_pool_pda_ai.try_borrow_mut_data()?)?;
-            staking_state.admin = *authority.key;
-            staking_state.staking_token_mint = *staking_token_mint_account.key;
-            staking_state.reward_token_mint = *rewards_token_mint_account.key;
-            staking_state.total_supply = 0;
-            staking_state.last_update_timestamp = clock.unix_timestamp;
-            staking_state.reward_per_token_stored = 0;
-            staking_state.pack(&mut staking_pool_pda_ai.try_borrow_mut_data()?);
-            msg!("Reset staking pool values");
-        } else {
-            msg!("Trying to create account");
-            let create_account_ix = solana_program::system_instruction::create_account(
-                &authority.key,
-                &staking_pool_pda,
-                Rent::get()?.minimum_balance(StakingPoolState::LEN),
-                StakingPoolState::LEN as u64,
-                program_id,
+            msg!(
+                "Staking pool {} is already initialized",
+                staking_pool_pda_ai.key
             );
-            invoke_signed(
-                &create_account_ix,
-                &[
-                    authority.clone(),
-                    staking_pool_pda_ai.clone(),
-                    system_program.clone(),
-                ],
-                &[&[
-                    staking_token_mint_account.key.as_ref(),
-                    rewards_token_mint_account.key.as_ref(),
-                    b"staking-pool",
-                    &[bump_seed],
-                ]],
-            )?;
-            msg!("Staking pool pda created: {}", staking_pool_pda_ai.key);
-            let mut staking_state =
-                StakingPoolState::try_from_slice(&staking_pool_pda_ai.try_borrow_mut_data()?)?;
-            staking_state.admin = *authority.key;
-            staking_state.staking_token_mint = *staking_token_mint_account.key;
-            staking_state.reward_token_mint = *rewards_token_mint_account.key;
-            staking_state.total_supply = 0;
-            staking_state.last_update_timestamp = clock.unix_timestamp;
-            staking_state.reward_per_token_stored = 0;
-            staking_state.pack(&mut staking_pool_pda_ai.try_borrow_mut_data()?);
-            msg!("Initialized staking with next values: ");
-            msg!("admin: {}", authority.key);
+            return Err(StakingError::AlreadyInitialized.into());
+        }
+        msg!("Trying to create account");
+        Self::create_pda_account(
+            authority,
+            staking_pool_pda_ai,
+            system_program,
+            StakingPoolState::LEN,
+            program_id,
+            &[
+                staking_token_mint_account.key.as_ref(),
+                rewards_token_mint_account.key.as_ref(),
+                &pool_id.to_le_bytes(),
+                b"staking-pool",
+                &[bump_seed],
+            ],
+        )?;
+        msg!("Staking pool pda created: {}", staking_pool_pda_ai.key);
+        let mut staking_state =
+            StakingPoolState::try_from_slice(&staking_pool_pda_ai.try_borrow_data()?)?;
+        staking_state.is_initialized = true;
+        staking_state.admin = *authority.key;
+        staking_state.staking_token_mint = *staking_token_mint_account.key;
+        staking_state.pool_id = pool_id;
+        staking_state.total_supply = 0;
+        staking_state.total_effective_supply = 0;
+        staking_state.last_update_timestamp = current_timestamp;
+        staking_state.precision = precision;
+        staking_state.max_lock_duration = 0;
+        staking_state.max_lock_multiplier = LOCK_MULTIPLIER_BASE;
+        staking_state.unbonding_period = 0;
+        staking_state.total_unbonding = 0;
+        staking_state.user_state_count = 0;
+        staking_state.lock_expiry_count = 0;
+        staking_state.lock_expiries = Default::default();
+        staking_state.unstake_penalty_bps = 0;
+        staking_state.penalty_decay_period = 0;
+        staking_state.penalty_treasury =
+            penalty_treasury.map_or_else(Pubkey::default, |treasury| *treasury.key);
+        staking_state.fee_bps = 0;
+        staking_state.fee_recipient = Pubkey::default();
+        staking_state.receipt_mint = Pubkey::default();
+        staking_state.pending_admin = Pubkey::default();
+        staking_state.staking_paused = false;
+        staking_state.claiming_paused = false;
+        staking_state.accrual_paused = false;
+        staking_state.reward_stream_count = 1;
+        staking_state.reward_streams = Default::default();
+        staking_state.reward_streams[0] = RewardStream {
+            mint: *rewards_token_mint_account.key,
+            reward_rate,
+            period_finish: current_timestamp,
+            ..RewardStream::default()
+        };
+        staking_state.pack(&mut staking_pool_pda_ai.try_borrow_mut_data()?)?;
+        msg!("Initialized staking with next values: ");
+        msg!("admin: {}", authority.key);
+        msg!(
+            "staking token mint pubkey: {}",
+            staking_token_mint_account.key
+        );
+        msg!(
+            "reward token mint pubkey: {}",
+            rewards_token_mint_account.key
+        );
+        msg!("pool id: {}", pool_id);
+        msg!("total staked: {}", 0);
+        msg!("reward rate: {}", reward_rate);
+        msg!("precision: {}", precision);
+        msg!("last reward timestamp: {}", current_timestamp);
+        let (staking_token_pda, staking_token_bump_seed) =
+            PdaHelper::find_staking_token_pda(staking_pool_pda_ai.key, program_id);
+        if *staking_token_escrow_pda.key != staking_token_pda {
             msg!(
-                "staking token mint pubkey: {}",
-                staking_token_mint_account.key
+                "Staking token escrow passed: {}",
+                staking_token_escrow_pda.key
             );
+            return Err(StakingError::InvalidStakingTokenEscrow.into());
+        }
+        Self::create_escrow(
+            authority,
+            staking_token_escrow_pda,
+            staking_token_mint_account,
+            system_program,
+            token_program,
+            rent_account,
+            &[
+                staking_pool_pda_ai.key.as_ref(),
+                b"staking-token",
+                &[staking_token_bump_seed],
+            ],
+        )?;
+        let (rewards_token_pda, rewards_token_bump_seed) = PdaHelper::find_rewards_token_pda(
+            staking_pool_pda_ai.key,
+            rewards_token_mint_account.key,
+            program_id,
+        );
+        if *rewards_token_escrow_pda.key != rewards_token_pda {
             msg!(
-                "reward token mint pubkey: {}",
-                rewards_token_mint_account.key
+                "Rewards token escrow passed: {}",
+                rewards_token_escrow_pda.key
             );
-            msg!("total staked: {}", 0);
-            msg!("last reward timestamp: {}", clock.unix_timestamp);
-            let (staking_token_pda, staking_token_bump_seed) =
-                PdaHelper::find_staking_token_pda(staking_token_mint_account, program_id);
-            invoke_signed(
-                &solana_program::system_instruction::create_account(
-                    &authority.key,
-                    &staking_token_pda,
-                    Rent::get()?.minimum_balance(spl_token::state::Account::LEN),
-                    spl_token::state::Account::LEN as u64,
-                    &token_program.key,
-                ),
-                &[
-                    authority.clone(),
-                    staking_token_escrow_pda.clone(),
-                    token_program.clone(),
-                ],
-                &[&[
-                    staking_token_mint_account.key.as_ref(),
-                    b"staking-token",
-                    &[staking_token_bump_seed],
-                ]],
-            )?;
-            let ix = spl_token::instruction::initialize_account(
-                &token_program.key,
-                &staking_token_escrow_pda.key,
-                &staking_token_mint_account.key,
-                &staking_token_pda,
-            )?;
-            invoke(
-                &ix,
-                &[
-                    staking_token_escrow_pda.clone(),
-                    staking_token_mint_account.clone(),
-                    staking_pool_pda_ai.clone(),
-                    rent_account.clone(),
-                    token_program.clone(),
-                ],
-            )?;
-            let (rewards_token_pda, rewards_token_bump_seed) =
-                PdaHelper::find_rewards_token_pda(rewards_token_mint_account, program_id);
-            invoke_signed(
+            return Err(StakingError::InvalidRewardsTokenEscrow.into());
+        }
+        Self::create_escrow(
+            authority,
+            rewards_token_escrow_pda,
+            rewards_token_mint_account,
+            system_program,
+            rewards_token_program,
+            rent_account,
+            &[
+                staking_pool_pda_ai.key.as_ref(),
+                rewards_token_mint_account.key.as_ref(),
+                b"rewards-token",
+                &[rewards_token_bump_seed],
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Creates the pda `pda` with `space` bytes owned by `owner`, paid by `payer`. Anyone can send
+    /// lamports to the address beforehand, which `create_account` refuses, so a funded address
+    /// is topped up to rent exemption and then allocated and assigned instead.
+    fn create_pda_account<'a>(
+        payer: &AccountInfo<'a>,
+        pda: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        space: usize,
+        owner: &Pubkey,
+        seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let rent = Rent::get()?.minimum_balance(space);
+        if pda.lamports() == 0 {
+            return invoke_signed(
                 &solana_program::system_instruction::create_account(
-                    &authority.key,
-                    &rewards_token_pda,
-                    Rent::get()?.minimum_balance(spl_token::state::Account::LEN),
-                    spl_token::state::Account::LEN as u64,
-                    &token_program.key,
+                    payer.key,
+                    pda.key,
+                    rent,
+                    space as u64,
+                    owner,
                 ),
-                &[
-                    authority.clone(),
-                    rewards_token_escrow_pda.clone(),
-                    token_program.clone(),
-                ],
-                &[&[
-                    rewards_token_mint_account.key.as_ref(),
-                    b"rewards-token",
-                    &[rewards_token_bump_seed],
-                ]],
-            )?;
-            let ix = spl_token::instruction::initialize_account(
-                &token_program.key,
-                &rewards_token_escrow_pda.key,
-                &rewards_token_mint_account.key,
-                &rewards_token_pda,
-            )?;
+                &[payer.clone(), pda.clone(), system_program.clone()],
+                &[seeds],
+            );
+        }
+        let top_up = rent.saturating_sub(pda.lamports());
+        if top_up != 0 {
             invoke(
-                &ix,
-                &[
-                    rewards_token_escrow_pda.clone(),
-                    rewards_token_mint_account.clone(),
-                    staking_pool_pda_ai.clone(),
-                    rent_account.clone(),
-                    token_program.clone(),
-                ],
+                &solana_program::system_instruction::transfer(payer.key, pda.key, top_up),
+                &[payer.clone(), pda.clone(), system_program.clone()],
             )?;
-        };
-        let (staking_token_pda, _) =
-            PdaHelper::find_staking_token_pda(staking_token_mint_account, program_id);
-        let (rewards_token_pda, _) =
-            PdaHelper::find_rewards_token_pda(rewards_token_mint_account, program_id);
-        let staking_token_account = Account::unpack(&staking_token_escrow_pda.try_borrow_data()?)?;
-        let rewards_token_account = Account::unpack(&rewards_token_escrow_pda.try_borrow_data()?)?;
-        if staking_token_account.owner != staking_token_pda {
+        }
+        invoke_signed(
+            &solana_program::system_instruction::allocate(pda.key, space as u64),
+            &[pda.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+        invoke_signed(
+            &solana_program::system_instruction::assign(pda.key, owner),
+            &[pda.clone(), system_program.clone()],
+            &[seeds],
+        )
+    }
+
+    /// Creates a token account for `mint` at `escrow_pda` that is its own owner, sized for the
+    /// account extensions the mint requires.
+    fn create_escrow<'a>(
+        payer: &AccountInfo<'a>,
+        escrow_pda: &AccountInfo<'a>,
+        mint: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        rent_account: &AccountInfo<'a>,
+        escrow_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let mint_extensions =
+            StateWithExtensions::<Mint>::unpack(&mint.try_borrow_data()?)?.get_extension_types()?;
+        let space = ExtensionType::try_calculate_account_len::<Account>(
+            &ExtensionType::get_required_init_account_extensions(&mint_extensions),
+        )?;
+        Self::create_pda_account(
+            payer,
+            escrow_pda,
+            system_program,
+            space,
+            token_program.key,
+            escrow_seeds,
+        )?;
+        let ix = spl_token_2022::instruction::initialize_account(
+            token_program.key,
+            escrow_pda.key,
+            mint.key,
+            escrow_pda.key,
+        )?;
+        invoke(
+            &ix,
+            &[
+                escrow_pda.clone(),
+                mint.clone(),
+                escrow_pda.clone(),
+                rent_account.clone(),
+                token_program.clone(),
+            ],
+        )?;
+        msg!("Escrow {} created for mint {}", escrow_pda.key, mint.key);
+        Ok(())
+    }
+
+    /// Transfers with `transfer_checked`, which Token-2022 mints with extensions require.
+    fn transfer_tokens<'a>(
+        token_program: &AccountInfo<'a>,
+        source: &AccountInfo<'a>,
+        mint: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        authority: &AccountInfo<'a>,
+        amount: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let decimals = StateWithExtensions::<Mint>::unpack(&mint.try_borrow_data()?)?
+            .base
+            .decimals;
+        let transfer_ix = spl_token_2022::instruction::transfer_checked(
+            token_program.key,
+            source.key,
+            mint.key,
+            destination.key,
+            authority.key,
+            &[],
+            amount,
+            decimals,
+        )?;
+        invoke_signed(
+            &transfer_ix,
+            &[
+                source.clone(),
+                mint.clone(),
+                destination.clone(),
+                authority.clone(),
+                token_program.clone(),
+            ],
+            signer_seeds,
+        )
+    }
+
+    fn token_balance(token_account: &AccountInfo) -> Result<u64, ProgramError> {
+        let data = token_account.try_borrow_data()?;
+        Ok(StateWithExtensions::<Account>::unpack(&data)?.base.amount)
+    }
+
+    fn add_reward_stream(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let admin = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let rewards_token_mint_account = next_account_info(accounts_iter)?;
+        let rewards_token_escrow_pda = next_account_info(accounts_iter)?;
+        let system_program = next_account_info(accounts_iter)?;
+        let token_program = next_account_info(accounts_iter)?;
+        let rent_account = next_account_info(accounts_iter)?;
+        Validator::check_system_program(system_program)?;
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_admin(&staking_pool_state, admin)?;
+        Validator::check_token_program(token_program, rewards_token_mint_account)?;
+        if staking_pool_state
+            .find_reward_stream(rewards_token_mint_account.key)
+            .is_some()
+        {
             msg!(
-                "Stake token account must have pda as owner. Current owner {}, pda {}",
-                staking_token_account.owner,
-                staking_token_pda
+                "Rewards token mint {} is already paid",
+                rewards_token_mint_account.key
             );
-            return Err(ProgramError::InvalidAccountData);
+            return Err(StakingError::RewardStreamExists.into());
+        }
+        let index = staking_pool_state.reward_stream_count as usize;
+        if index == MAX_REWARD_STREAMS {
+            msg!("Reward streams: {}", index);
+            return Err(StakingError::TooManyRewardStreams.into());
         }
-        if rewards_token_account.owner != rewards_token_pda {
+        let (rewards_token_pda, bump_seed) = PdaHelper::find_rewards_token_pda(
+            staking_pool_pda.key,
+            rewards_token_mint_account.key,
+            program_id,
+        );
+        if *rewards_token_escrow_pda.key != rewards_token_pda {
             msg!(
-                "Rewards token account must have pda as owner. Current owner {}, pda {}",
-                rewards_token_account.owner,
-                rewards_token_pda
+                "Rewards token escrow passed: {}",
+                rewards_token_escrow_pda.key
             );
-            return Err(ProgramError::InvalidAccountData);
+            return Err(StakingError::InvalidRewardsTokenEscrow.into());
         }
+        Self::create_escrow(
+            admin,
+            rewards_token_escrow_pda,
+            rewards_token_mint_account,
+            system_program,
+            token_program,
+            rent_account,
+            &[
+                staking_pool_pda.key.as_ref(),
+                rewards_token_mint_account.key.as_ref(),
+                b"rewards-token",
+                &[bump_seed],
+            ],
+        )?;
+        Self::update_reward_per_token(&mut staking_pool_state, current_timestamp)?;
+        staking_pool_state.reward_streams[index] = RewardStream {
+            mint: *rewards_token_mint_account.key,
+            period_finish: current_timestamp,
+            ..RewardStream::default()
+        };
+        staking_pool_state.reward_stream_count += 1;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!(
+            "ADD REWARD STREAM: {} Escrow: {}",
+            rewards_token_mint_account.key,
+            rewards_token_escrow_pda.key
+        );
         Ok(())
     }
 
-    fn stake(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    fn stake(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        lock_duration: u64,
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
         let user_authority = next_account_info(accounts_iter)?;
         let staking_token_account = next_account_info(accounts_iter)?;
@@ -221,84 +541,422 @@ impl Processor {
         let staking_token_mint_account = next_account_info(accounts_iter)?;
         let token_program = next_account_info(accounts_iter)?;
         let system_program = next_account_info(accounts_iter)?;
+        Validator::check_signer(user_authority)?;
+        Validator::check_token_program(token_program, staking_token_mint_account)?;
+        Validator::check_system_program(system_program)?;
         if amount == 0 {
             msg!("Amount = 0");
-            return Err(ProgramError::InvalidInstructionData);
+            return Err(StakingError::ZeroAmount.into());
         }
-        let (staking_token_escrow_pda_owner, _) =
-            PdaHelper::find_staking_token_pda(staking_token_mint_account, program_id);
-        let staking_token_escrow_account =
-            Account::unpack(&staking_token_escrow_pda.try_borrow_data()?)?;
-        if staking_token_escrow_account.owner != staking_token_escrow_pda_owner {
+        let staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        if staking_pool_state.staking_paused {
+            msg!("Staking is paused");
+            return Err(StakingError::StakingPaused.into());
+        }
+        let receipt_accounts = Self::next_receipt_accounts(
+            program_id,
+            &staking_pool_state,
+            staking_pool_pda,
+            user_authority,
+            accounts_iter,
+        )?;
+        if receipt_accounts.is_some() && lock_duration > 0 {
+            msg!("Receipt tokens cannot be locked");
+            return Err(StakingError::ReceiptMintUnsupported.into());
+        }
+        if lock_duration > staking_pool_state.max_lock_duration {
             msg!(
-                "Stake token account must have pda as owner. Current owner {}, pda {}",
-                staking_token_escrow_account.owner,
-                staking_token_escrow_pda_owner
+                "Lock duration {} exceeds the maximum {}",
+                lock_duration,
+                staking_pool_state.max_lock_duration
             );
-            return Err(ProgramError::InvalidAccountData);
+            return Err(StakingError::LockTooLong.into());
         }
-        let transafer_ix = spl_token::instruction::transfer(
-            &token_program.key,
-            &staking_token_account.key,
-            &staking_token_escrow_pda.key,
-            &user_authority.key,
-            &[],
-            amount,
+        Validator::check_staking_token_mint(&staking_pool_state, staking_token_mint_account)?;
+        Validator::check_staking_token_escrow(
+            program_id,
+            staking_pool_pda,
+            staking_token_mint_account,
+            staking_token_escrow_pda,
         )?;
-        invoke(
-            &transafer_ix,
-            &[
-                staking_token_account.clone(),
-                staking_token_escrow_pda.clone(),
-                user_authority.clone(),
-                token_program.clone(),
-            ],
+        let bump_seed = Validator::check_user_state_pda(
+            program_id,
+            staking_pool_pda,
+            user_authority,
+            user_state_pda_ai,
+        )?;
+        // Transfer fee mints deliver less than `amount`, so only what the escrow received is staked
+        let escrow_balance_before = Self::token_balance(staking_token_escrow_pda)?;
+        Self::transfer_tokens(
+            token_program,
+            staking_token_account,
+            staking_token_mint_account,
+            staking_token_escrow_pda,
+            user_authority,
+            amount,
+            &[],
         )?;
+        let received = Self::token_balance(staking_token_escrow_pda)?
+            .checked_sub(escrow_balance_before)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        if received == 0 {
+            msg!("Escrow received nothing of {}", amount);
+            return Err(StakingError::ZeroAmount.into());
+        }
         msg!(
             "Tokens transfered from staker {} to pda {}",
             staking_token_account.key,
             staking_token_escrow_pda.key
         );
-        let (user_state_pda, bump_seed) =
-            PdaHelper::find_user_state_pda(staking_pool_pda, user_authority, program_id);
-        msg!("Staker pda: {}", user_state_pda_ai.key);
-        msg!("Staker pda computed: {}", user_state_pda);
         if user_state_pda_ai.try_data_is_empty()? {
-            let create_acc_ix = solana_program::system_instruction::create_account(
-                &user_authority.key,
-                &user_state_pda,
-                Rent::get()?.minimum_balance(UserState::LEN),
-                UserState::LEN as u64,
-                &program_id,
-            );
-            invoke_signed(
-                &create_acc_ix,
-                &[
-                    user_authority.clone(),
-                    user_state_pda_ai.clone(),
-                    system_program.clone(),
-                ],
-                &[&[
-                    &staking_pool_pda.key.to_bytes(),
-                    &user_authority.key.to_bytes(),
-                    b"user-state",
-                    &[bump_seed],
-                ]],
+            Self::create_user_state(
+                program_id,
+                user_authority,
+                user_state_pda_ai,
+                staking_pool_pda,
+                user_authority.key,
+                system_program,
+                bump_seed,
+            )?;
+        }
+        Self::update_rewards(
+            staking_pool_pda,
+            user_state_pda_ai,
+            receipt_accounts.map(|(_, receipt_token_account, _)| receipt_token_account),
+            current_timestamp,
+        )?;
+        let mut user_state = UserState::unpack(&user_state_pda_ai.try_borrow_data()?)?;
+        let mut staking_pool_state =
+            StakingPoolState::unpack(&staking_pool_pda.try_borrow_data()?)?;
+        // The early unstake penalty decays from the latest stake
+        user_state.last_stake_timestamp = current_timestamp;
+        user_state.balance = user_state
+            .balance
+            .checked_add(received)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        let mut lock_until = 0;
+        let effective_received = if lock_duration > 0 {
+            // A new position, so stake that is already locked keeps its own expiry and boost
+            let effective_balance = math::locked_effective_balance(
+                received,
+                lock_duration,
+                staking_pool_state.max_lock_duration,
+                staking_pool_state.max_lock_multiplier,
+            )?;
+            lock_until = i64::try_from(lock_duration)
+                .ok()
+                .and_then(|duration| current_timestamp.checked_add(duration))
+                .ok_or(StakingError::ArithmeticOverflow)?;
+            if effective_balance > received {
+                let latest_lock_until = i64::try_from(staking_pool_state.max_lock_duration)
+                    .ok()
+                    .and_then(|duration| current_timestamp.checked_add(duration))
+                    .ok_or(StakingError::ArithmeticOverflow)?
+                    .max(lock_until);
+                lock_until = Self::schedule_lock_expiry(
+                    &mut staking_pool_state,
+                    lock_until,
+                    latest_lock_until,
+                    effective_balance - received,
+                )?;
+            }
+            Self::add_locked_position(
+                &mut user_state,
+                LockedPosition {
+                    amount: received,
+                    effective_balance,
+                    lock_until,
+                },
+            )?;
+            effective_balance
+        } else {
+            received
+        };
+        user_state.effective_balance = user_state
+            .effective_balance
+            .checked_add(effective_received)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        user_state.pack(&mut user_state_pda_ai.try_borrow_mut_data()?)?;
+        staking_pool_state.total_supply = staking_pool_state
+            .total_supply
+            .checked_add(received)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        staking_pool_state.total_effective_supply = staking_pool_state
+            .total_effective_supply
+            .checked_add(effective_received)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        if let Some((receipt_mint, receipt_token_account, receipt_bump)) = receipt_accounts {
+            Self::mint_receipts(
+                token_program,
+                receipt_mint,
+                receipt_token_account,
+                staking_pool_pda,
+                receipt_bump,
+                received,
             )?;
         }
-        Self::update_rewards(staking_pool_pda, user_state_pda_ai)?;
-        let mut user_state = UserState::try_from_slice(&user_state_pda_ai.try_borrow_data()?)?;
-        user_state.balance += amount;
-        user_state.pack(&mut user_state_pda_ai.try_borrow_mut_data()?);
+        msg!(
+            "STAKE From: {} Amount: {} Received: {} Locked until: {}",
+            user_authority.key,
+            amount,
+            received,
+            lock_until
+        );
+        Ok(())
+    }
+
+    /// Adds `position` to the user, merged into the one expiring at the same time if any.
+    fn add_locked_position(user_state: &mut UserState, position: LockedPosition) -> ProgramResult {
+        let count = user_state.position_count as usize;
+        if let Some(existing) = user_state.positions[..count]
+            .iter_mut()
+            .find(|existing| existing.lock_until == position.lock_until)
+        {
+            existing.amount = existing
+                .amount
+                .checked_add(position.amount)
+                .ok_or(StakingError::ArithmeticOverflow)?;
+            existing.effective_balance = existing
+                .effective_balance
+                .checked_add(position.effective_balance)
+                .ok_or(StakingError::ArithmeticOverflow)?;
+            return Ok(());
+        }
+        if count == MAX_LOCKED_POSITIONS {
+            msg!("User already holds {} locked positions", count);
+            return Err(StakingError::TooManyLockedPositions.into());
+        }
+        user_state.positions[count] = position;
+        user_state.position_count += 1;
+        Ok(())
+    }
+
+    /// Creates the user state pda of `user`, paid by `payer`, and counts it on the pool.
+    fn create_user_state<'a>(
+        program_id: &Pubkey,
+        payer: &AccountInfo<'a>,
+        user_state_pda: &AccountInfo<'a>,
+        staking_pool_pda: &AccountInfo<'a>,
+        user: &Pubkey,
+        system_program: &AccountInfo<'a>,
+        bump_seed: u8,
+    ) -> ProgramResult {
+        Self::create_pda_account(
+            payer,
+            user_state_pda,
+            system_program,
+            UserState::LEN,
+            program_id,
+            &[
+                &staking_pool_pda.key.to_bytes(),
+                &user.to_bytes(),
+                b"user-state",
+                &[bump_seed],
+            ],
+        )?;
         let mut staking_pool_state =
-            StakingPoolState::unpack(&mut staking_pool_pda.try_borrow_mut_data()?);
-        staking_pool_state.total_supply += amount;
-        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?);
-        msg!("STAKE From: {} Amount: {}", user_authority.key, amount);
+            StakingPoolState::unpack(&staking_pool_pda.try_borrow_data()?)?;
+        staking_pool_state.user_state_count = staking_pool_state
+            .user_state_count
+            .checked_add(1)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)
+    }
+
+    /// Reads the trailing receipt mint and receipt token account of `user_authority` in pools with
+    /// a receipt mint and returns them with the receipt mint bump seed. Other pools take none.
+    #[allow(clippy::type_complexity)]
+    fn next_receipt_accounts<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        program_id: &Pubkey,
+        staking_pool_state: &StakingPoolState,
+        staking_pool_pda: &AccountInfo,
+        user_authority: &AccountInfo,
+        accounts_iter: &mut I,
+    ) -> Result<Option<(&'a AccountInfo<'b>, &'a AccountInfo<'b>, u8)>, ProgramError> {
+        if staking_pool_state.receipt_mint == Pubkey::default() {
+            return Ok(None);
+        }
+        let receipt_mint = next_account_info(accounts_iter)?;
+        let receipt_token_account = next_account_info(accounts_iter)?;
+        let bump_seed = Validator::check_receipt_mint(
+            program_id,
+            staking_pool_state,
+            staking_pool_pda,
+            receipt_mint,
+        )?;
+        Validator::check_holder_receipt_token_account(
+            staking_pool_state,
+            receipt_token_account,
+            user_authority.key,
+        )?;
+        Ok(Some((receipt_mint, receipt_token_account, bump_seed)))
+    }
+
+    /// Moves the stake of a receipt holder to the balance of their bound receipt token account.
+    /// Pools with a receipt mint have no locks, so the effective balance equals the balance.
+    /// Expects the holder's rewards to be settled already.
+    fn sync_receipts(
+        staking_pool_state: &mut StakingPoolState,
+        user_state: &mut UserState,
+        receipt_token_account: &AccountInfo,
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let receipt_balance = Self::bound_receipt_balance(user_state, receipt_token_account)?;
+        if receipt_balance > user_state.balance {
+            // Received receipts count as a fresh stake for the early unstake penalty, weighted by
+            // amount so that anyone sending dust cannot restart the holder's decay
+            user_state.last_stake_timestamp = math::weighted_stake_timestamp(
+                user_state.last_stake_timestamp,
+                user_state.balance,
+                current_timestamp,
+                receipt_balance - user_state.balance,
+            )?;
+        }
+        staking_pool_state.total_supply = staking_pool_state
+            .total_supply
+            .checked_sub(user_state.balance)
+            .and_then(|supply| supply.checked_add(receipt_balance))
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        staking_pool_state.total_effective_supply = staking_pool_state
+            .total_effective_supply
+            .checked_sub(user_state.effective_balance)
+            .and_then(|supply| supply.checked_add(receipt_balance))
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        user_state.balance = receipt_balance;
+        user_state.effective_balance = receipt_balance;
+        Ok(())
+    }
+
+    /// Drops the receipts a holder sent away since their last settlement from their stake
+    /// before it is settled. What the sent receipts accrued since then is no longer owed to
+    /// anyone and becomes excess, so the holder only earns on the lower of both balances.
+    fn forfeit_sent_receipts(
+        staking_pool_state: &mut StakingPoolState,
+        user_state: &mut UserState,
+        receipt_token_account: &AccountInfo,
+    ) -> ProgramResult {
+        let receipt_balance = Self::bound_receipt_balance(user_state, receipt_token_account)?;
+        let sent = user_state.balance.saturating_sub(receipt_balance);
+        if sent == 0 {
+            return Ok(());
+        }
+        for (stream, user_stream) in staking_pool_state
+            .active_reward_streams_mut()
+            .iter_mut()
+            .zip(user_state.reward_streams.iter())
+        {
+            let forfeited = math::accrued(
+                sent,
+                stream.reward_per_token_stored,
+                user_stream.reward_per_token_paid,
+            )?;
+            stream.rewards_accrued = stream
+                .rewards_accrued
+                .checked_sub(forfeited)
+                .ok_or(StakingError::ArithmeticOverflow)?;
+        }
+        staking_pool_state.total_supply = staking_pool_state
+            .total_supply
+            .checked_sub(sent)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        staking_pool_state.total_effective_supply = staking_pool_state
+            .total_effective_supply
+            .checked_sub(sent)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        user_state.balance = receipt_balance;
+        user_state.effective_balance = receipt_balance;
         Ok(())
     }
 
-    fn unstake(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    /// Balance of the receipt token account bound to the user state, binding it on first use.
+    fn bound_receipt_balance(
+        user_state: &mut UserState,
+        receipt_token_account: &AccountInfo,
+    ) -> Result<u64, ProgramError> {
+        if user_state.receipt_token_account == Pubkey::default() {
+            user_state.receipt_token_account = *receipt_token_account.key;
+        } else if user_state.receipt_token_account != *receipt_token_account.key {
+            msg!(
+                "Receipt token account passed: {}, bound: {}",
+                receipt_token_account.key,
+                user_state.receipt_token_account
+            );
+            return Err(StakingError::InvalidReceiptAccount.into());
+        }
+        Self::token_balance(receipt_token_account)
+    }
+
+    /// Mints receipt tokens, signed by the receipt mint pda as its own mint authority.
+    fn mint_receipts<'a>(
+        token_program: &AccountInfo<'a>,
+        receipt_mint: &AccountInfo<'a>,
+        receipt_token_account: &AccountInfo<'a>,
+        staking_pool_pda: &AccountInfo<'a>,
+        bump_seed: u8,
+        amount: u64,
+    ) -> ProgramResult {
+        let decimals = StateWithExtensions::<Mint>::unpack(&receipt_mint.try_borrow_data()?)?
+            .base
+            .decimals;
+        let mint_ix = spl_token_2022::instruction::mint_to_checked(
+            token_program.key,
+            receipt_mint.key,
+            receipt_token_account.key,
+            receipt_mint.key,
+            &[],
+            amount,
+            decimals,
+        )?;
+        invoke_signed(
+            &mint_ix,
+            &[
+                receipt_mint.clone(),
+                receipt_token_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[staking_pool_pda.key.as_ref(), b"receipt-mint", &[bump_seed]]],
+        )
+    }
+
+    /// Burns receipt tokens of `holder`, who signed the instruction.
+    fn burn_receipts<'a>(
+        token_program: &AccountInfo<'a>,
+        receipt_mint: &AccountInfo<'a>,
+        receipt_token_account: &AccountInfo<'a>,
+        holder: &AccountInfo<'a>,
+        amount: u64,
+    ) -> ProgramResult {
+        let decimals = StateWithExtensions::<Mint>::unpack(&receipt_mint.try_borrow_data()?)?
+            .base
+            .decimals;
+        let burn_ix = spl_token_2022::instruction::burn_checked(
+            token_program.key,
+            receipt_token_account.key,
+            receipt_mint.key,
+            holder.key,
+            &[],
+            amount,
+            decimals,
+        )?;
+        invoke(
+            &burn_ix,
+            &[
+                receipt_token_account.clone(),
+                receipt_mint.clone(),
+                holder.clone(),
+                token_program.clone(),
+            ],
+        )
+    }
+
+    fn unstake(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        close: bool,
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
         let user_authority = next_account_info(accounts_iter)?;
         let staking_token_account = next_account_info(accounts_iter)?; // user_staking_token_ai
@@ -308,69 +966,521 @@ impl Processor {
         let staking_token_escrow_pda_owner_ai = next_account_info(accounts_iter)?;
         let staking_token_mint_account = next_account_info(accounts_iter)?;
         let token_program = next_account_info(accounts_iter)?;
-        if !user_authority.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
+        Validator::check_signer(user_authority)?;
+        Validator::check_token_program(token_program, staking_token_mint_account)?;
         if amount == 0 {
             msg!("Amount = 0");
-            return Err(ProgramError::InvalidInstructionData);
+            return Err(StakingError::ZeroAmount.into());
+        }
+        let staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        if staking_pool_state.unbonding_period != 0 {
+            msg!("Unbonding period: {}", staking_pool_state.unbonding_period);
+            return Err(StakingError::UnbondingRequired.into());
+        }
+        let receipt_accounts = Self::next_receipt_accounts(
+            program_id,
+            &staking_pool_state,
+            staking_pool_pda,
+            user_authority,
+            accounts_iter,
+        )?;
+        let penalty_destination = next_account_info(accounts_iter).ok();
+        Validator::check_user_state_pda(
+            program_id,
+            staking_pool_pda,
+            user_authority,
+            user_state_pda,
+        )?;
+        Validator::check_staking_token_mint(&staking_pool_state, staking_token_mint_account)?;
+        let bump = Validator::check_staking_token_escrow(
+            program_id,
+            staking_pool_pda,
+            staking_token_mint_account,
+            staking_token_escrow_pda,
+        )?;
+        if staking_token_escrow_pda_owner_ai.key != staking_token_escrow_pda.key {
+            msg!(
+                "Passed escrow staking owner: {}",
+                staking_token_escrow_pda_owner_ai.key
+            );
+            return Err(StakingError::InvalidStakingTokenEscrow.into());
         }
-        Self::update_rewards(staking_pool_pda, user_state_pda)?;
-        let users_state = UserState::unpack(&mut user_state_pda.try_borrow_mut_data()?);
-        if amount > users_state.balance {
+        Self::update_rewards(
+            staking_pool_pda,
+            user_state_pda,
+            receipt_accounts.map(|(_, receipt_token_account, _)| receipt_token_account),
+            current_timestamp,
+        )?;
+        let mut staking_pool_state =
+            StakingPoolState::unpack(&staking_pool_pda.try_borrow_data()?)?;
+        let mut user_state = UserState::unpack(&user_state_pda.try_borrow_data()?)?;
+        if amount > user_state.balance {
             msg!(
                 "Cannot unstake more than staked. Staked: {}, trying to withdraw: {}",
-                users_state.balance,
+                user_state.balance,
                 amount
             );
-            return Err(ProgramError::InvalidInstructionData);
+            return Err(StakingError::UnstakeExceedsBalance.into());
         }
-        let (staking_token_escrow_pda_owner, bump) =
-            PdaHelper::find_staking_token_pda(staking_token_mint_account, program_id);
-        let staking_token_escrow_account =
-            Account::unpack_from_slice(&mut staking_token_escrow_pda.try_borrow_mut_data()?)?;
-        if staking_token_escrow_account.owner != staking_token_escrow_pda_owner {
+        if amount > user_state.unlocked_balance() {
             msg!(
-                "Passed escrow staking owner: {}",
-                staking_token_escrow_pda_owner_ai.key
+                "Only {} is unlocked, {} is still locked",
+                user_state.unlocked_balance(),
+                user_state.locked_balance()
             );
+            return Err(StakingError::StakeLocked.into());
+        }
+        // Rewards settled above would be lost with the user state, so they must be claimed first
+        if close && (amount != user_state.balance || user_state.unclaimed_rewards() != 0) {
             msg!(
-                "Computed escrow staking owner: {}",
-                staking_token_escrow_pda_owner
+                "Closing requires unstaking the whole balance {} and claiming rewards {} first",
+                user_state.balance,
+                user_state.unclaimed_rewards()
             );
-            return Err(ProgramError::InvalidAccountData);
+            return Err(StakingError::UserStateNotEmpty.into());
         }
-        let transfer_ix = spl_token::instruction::transfer(
-            token_program.key,
-            staking_token_escrow_pda.key,
-            staking_token_account.key,
-            &staking_token_escrow_pda_owner,
-            &[],
+        let penalty = Self::early_unstake_penalty(
+            &staking_pool_state,
+            &user_state,
             amount,
+            current_timestamp,
         )?;
-        invoke_signed(
-            &transfer_ix,
-            &[
-                staking_token_escrow_pda.clone(),
-                staking_token_account.clone(),
-                staking_token_escrow_pda_owner_ai.clone(),
-                token_program.clone(),
-            ],
-            &[&[
-                &staking_token_mint_account.key.to_bytes(),
-                b"staking-token",
-                &[bump],
-            ]],
+        if let Some((receipt_mint, receipt_token_account, _)) = receipt_accounts {
+            Self::burn_receipts(
+                token_program,
+                receipt_mint,
+                receipt_token_account,
+                user_authority,
+                amount,
+            )?;
+        }
+        Self::transfer_tokens(
+            token_program,
+            staking_token_escrow_pda,
+            staking_token_mint_account,
+            staking_token_account,
+            staking_token_escrow_pda_owner_ai,
+            amount - penalty,
+            &[&[staking_pool_pda.key.as_ref(), b"staking-token", &[bump]]],
+        )?;
+        // Only unlocked tokens leave, and those count 1x towards the effective balance
+        user_state.balance = user_state
+            .balance
+            .checked_sub(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        user_state.effective_balance = user_state
+            .effective_balance
+            .checked_sub(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        user_state.pack(&mut user_state_pda.try_borrow_mut_data()?)?;
+        staking_pool_state.total_supply = staking_pool_state
+            .total_supply
+            .checked_sub(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        staking_pool_state.total_effective_supply = staking_pool_state
+            .total_effective_supply
+            .checked_sub(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        if penalty != 0 {
+            let penalty_destination =
+                penalty_destination.ok_or(ProgramError::NotEnoughAccountKeys)?;
+            Self::withhold_penalty(
+                program_id,
+                &mut staking_pool_state,
+                staking_pool_pda,
+                staking_token_escrow_pda,
+                staking_token_mint_account,
+                penalty_destination,
+                token_program,
+                bump,
+                penalty,
+            )?;
+        }
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!(
+            "UNSTAKE Transfer: {} Penalty: {} From: {} To: {}",
+            amount - penalty,
+            penalty,
+            staking_token_escrow_pda.key,
+            staking_token_account.key
+        );
+        if close {
+            Self::close_user_state_account(user_state_pda, staking_pool_pda, user_authority)?;
+        }
+        Ok(())
+    }
+
+    /// Part of `amount` withheld for unstaking within the penalty decay period after the last
+    /// stake. Waived when it would go to the remaining stakers but nobody else is staked.
+    fn early_unstake_penalty(
+        staking_pool_state: &StakingPoolState,
+        user_state: &UserState,
+        amount: u64,
+        current_timestamp: UnixTimestamp,
+    ) -> Result<u64, ProgramError> {
+        if staking_pool_state.penalty_treasury == Pubkey::default()
+            && staking_pool_state.total_effective_supply <= amount
+        {
+            return Ok(0);
+        }
+        let elapsed = current_timestamp
+            .saturating_sub(user_state.last_stake_timestamp)
+            .max(0) as u64;
+        Ok(math::unstake_penalty(
+            amount,
+            staking_pool_state.unstake_penalty_bps,
+            staking_pool_state.penalty_decay_period,
+            elapsed,
+        )?)
+    }
+
+    /// Moves an early unstake penalty out of the staking escrow, either to the pool treasury or
+    /// into the reward stream paying the staking token, shared by the remaining stakers.
+    #[allow(clippy::too_many_arguments)]
+    fn withhold_penalty<'a>(
+        program_id: &Pubkey,
+        staking_pool_state: &mut StakingPoolState,
+        staking_pool_pda: &AccountInfo<'a>,
+        staking_token_escrow_pda: &AccountInfo<'a>,
+        staking_token_mint_account: &AccountInfo<'a>,
+        penalty_destination: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        bump: u8,
+        penalty: u64,
+    ) -> ProgramResult {
+        let signer_seeds: &[&[&[u8]]] =
+            &[&[staking_pool_pda.key.as_ref(), b"staking-token", &[bump]]];
+        if staking_pool_state.penalty_treasury != Pubkey::default() {
+            if *penalty_destination.key != staking_pool_state.penalty_treasury {
+                msg!(
+                    "Penalty destination passed: {}, treasury: {}",
+                    penalty_destination.key,
+                    staking_pool_state.penalty_treasury
+                );
+                return Err(StakingError::InvalidPenaltyDestination.into());
+            }
+            return Self::transfer_tokens(
+                token_program,
+                staking_token_escrow_pda,
+                staking_token_mint_account,
+                penalty_destination,
+                staking_token_escrow_pda,
+                penalty,
+                signer_seeds,
+            );
+        }
+        let index = staking_pool_state
+            .find_reward_stream(staking_token_mint_account.key)
+            .ok_or(StakingError::PenaltyDestinationMissing)?;
+        Validator::check_rewards_token_escrow(
+            program_id,
+            staking_pool_pda,
+            staking_token_mint_account.key,
+            penalty_destination,
+        )?;
+        let balance_before = Self::token_balance(penalty_destination)?;
+        Self::transfer_tokens(
+            token_program,
+            staking_token_escrow_pda,
+            staking_token_mint_account,
+            penalty_destination,
+            staking_token_escrow_pda,
+            penalty,
+            signer_seeds,
+        )?;
+        let received = Self::token_balance(penalty_destination)?
+            .checked_sub(balance_before)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        // Paid out at once, as if the stream emitted the penalty over a single second. Reward
+        // math must not block the exit, so a penalty that cannot be shared stays as excess
+        if let Err(error) = Self::distribute_rewards(staking_pool_state, index, received) {
+            msg!("Penalty left as excess rewards: {}", error);
+        }
+        Ok(())
+    }
+
+    /// Books `amount` as rewards of the stream at `index` for the current stakers at once. The
+    /// stream is left as it was if that fails.
+    fn distribute_rewards(
+        staking_pool_state: &mut StakingPoolState,
+        index: usize,
+        amount: u64,
+    ) -> ProgramResult {
+        let mut stream = staking_pool_state.reward_streams[index];
+        let reward_per_token = math::reward_per_token(
+            stream.reward_per_token_stored,
+            amount,
+            1,
+            staking_pool_state.precision,
+            staking_pool_state.total_effective_supply,
+        )?;
+        let accrued = math::accrued(
+            staking_pool_state.total_effective_supply,
+            reward_per_token,
+            stream.reward_per_token_stored,
+        )?;
+        stream.rewards_accrued = stream
+            .rewards_accrued
+            .checked_add(accrued)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        stream.reward_per_token_stored = reward_per_token;
+        staking_pool_state.reward_streams[index] = stream;
+        Ok(())
+    }
+
+    fn request_unstake(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let user_authority = next_account_info(accounts_iter)?;
+        let user_state_pda = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let withdrawal_queue_pda = next_account_info(accounts_iter)?;
+        let system_program = next_account_info(accounts_iter)?;
+        let staking_token_escrow_pda = next_account_info(accounts_iter)?;
+        let staking_token_mint_account = next_account_info(accounts_iter)?;
+        let token_program = next_account_info(accounts_iter)?;
+        let penalty_destination = next_account_info(accounts_iter).ok();
+        Validator::check_signer(user_authority)?;
+        Validator::check_system_program(system_program)?;
+        Validator::check_token_program(token_program, staking_token_mint_account)?;
+        if amount == 0 {
+            msg!("Amount = 0");
+            return Err(StakingError::ZeroAmount.into());
+        }
+        let staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        if staking_pool_state.receipt_mint != Pubkey::default() {
+            msg!("Unstake receipt tokens with Unstake");
+            return Err(StakingError::ReceiptMintUnsupported.into());
+        }
+        Validator::check_user_state_pda(
+            program_id,
+            staking_pool_pda,
+            user_authority,
+            user_state_pda,
+        )?;
+        let bump_seed = Validator::check_withdrawal_queue_pda(
+            program_id,
+            staking_pool_pda,
+            user_authority,
+            withdrawal_queue_pda,
+        )?;
+        Validator::check_staking_token_mint(&staking_pool_state, staking_token_mint_account)?;
+        let bump = Validator::check_staking_token_escrow(
+            program_id,
+            staking_pool_pda,
+            staking_token_mint_account,
+            staking_token_escrow_pda,
         )?;
-        let mut user_state = UserState::unpack(&mut user_state_pda.try_borrow_mut_data()?);
-        user_state.balance -= amount;
-        user_state.pack(&mut user_state_pda.try_borrow_mut_data()?);
+        Self::update_rewards(staking_pool_pda, user_state_pda, None, current_timestamp)?;
         let mut staking_pool_state =
-            StakingPoolState::unpack(&mut staking_pool_pda.try_borrow_mut_data()?);
-        staking_pool_state.total_supply -= amount;
-        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?);
+            StakingPoolState::unpack(&staking_pool_pda.try_borrow_data()?)?;
+        let mut user_state = UserState::unpack(&user_state_pda.try_borrow_data()?)?;
+        if amount > user_state.balance {
+            msg!(
+                "Cannot unstake more than staked. Staked: {}, trying to withdraw: {}",
+                user_state.balance,
+                amount
+            );
+            return Err(StakingError::UnstakeExceedsBalance.into());
+        }
+        if amount > user_state.unlocked_balance() {
+            msg!(
+                "Only {} is unlocked, {} is still locked",
+                user_state.unlocked_balance(),
+                user_state.locked_balance()
+            );
+            return Err(StakingError::StakeLocked.into());
+        }
+        // Withheld now, so only the rest of `amount` is queued
+        let penalty = Self::early_unstake_penalty(
+            &staking_pool_state,
+            &user_state,
+            amount,
+            current_timestamp,
+        )?;
+        let unlock_timestamp = Self::queue_unbonding(
+            program_id,
+            &mut staking_pool_state,
+            user_authority,
+            withdrawal_queue_pda,
+            staking_pool_pda,
+            system_program,
+            bump_seed,
+            amount - penalty,
+            current_timestamp,
+        )?;
+        // Queued tokens stay in the escrow but no longer earn rewards
+        user_state.balance = user_state
+            .balance
+            .checked_sub(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        user_state.effective_balance = user_state
+            .effective_balance
+            .checked_sub(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        user_state.pack(&mut user_state_pda.try_borrow_mut_data()?)?;
+        staking_pool_state.total_supply = staking_pool_state
+            .total_supply
+            .checked_sub(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        staking_pool_state.total_effective_supply = staking_pool_state
+            .total_effective_supply
+            .checked_sub(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        if penalty != 0 {
+            let penalty_destination =
+                penalty_destination.ok_or(ProgramError::NotEnoughAccountKeys)?;
+            Self::withhold_penalty(
+                program_id,
+                &mut staking_pool_state,
+                staking_pool_pda,
+                staking_token_escrow_pda,
+                staking_token_mint_account,
+                penalty_destination,
+                token_program,
+                bump,
+                penalty,
+            )?;
+        }
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!(
+            "REQUEST UNSTAKE From: {} Amount: {} Penalty: {} Unlock: {}",
+            user_authority.key,
+            amount - penalty,
+            penalty,
+            unlock_timestamp
+        );
+        Ok(())
+    }
+
+    /// Appends a request for `amount` unlocking after the pool unbonding period to the withdrawal
+    /// queue of `user_authority`, who pays for creating it if needed, and returns when it unlocks.
+    #[allow(clippy::too_many_arguments)]
+    fn queue_unbonding<'a>(
+        program_id: &Pubkey,
+        staking_pool_state: &mut StakingPoolState,
+        user_authority: &AccountInfo<'a>,
+        withdrawal_queue_pda: &AccountInfo<'a>,
+        staking_pool_pda: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        bump_seed: u8,
+        amount: u64,
+        current_timestamp: UnixTimestamp,
+    ) -> Result<UnixTimestamp, ProgramError> {
+        if withdrawal_queue_pda.try_data_is_empty()? {
+            Self::create_pda_account(
+                user_authority,
+                withdrawal_queue_pda,
+                system_program,
+                WithdrawalQueue::LEN,
+                program_id,
+                &[
+                    &staking_pool_pda.key.to_bytes(),
+                    &user_authority.key.to_bytes(),
+                    b"withdrawal-queue",
+                    &[bump_seed],
+                ],
+            )?;
+        }
+        let mut withdrawal_queue =
+            WithdrawalQueue::unpack(&withdrawal_queue_pda.try_borrow_data()?)?;
+        let index = withdrawal_queue.request_count as usize;
+        if index == MAX_UNBONDING_REQUESTS {
+            msg!("Unstake requests: {}", index);
+            return Err(StakingError::WithdrawalQueueFull.into());
+        }
+        let unlock_timestamp = i64::try_from(staking_pool_state.unbonding_period)
+            .ok()
+            .and_then(|period| current_timestamp.checked_add(period))
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        withdrawal_queue.requests[index] = UnbondingRequest {
+            amount,
+            unlock_timestamp,
+        };
+        withdrawal_queue.request_count += 1;
+        withdrawal_queue.pack(&mut withdrawal_queue_pda.try_borrow_mut_data()?)?;
+        staking_pool_state.total_unbonding = staking_pool_state
+            .total_unbonding
+            .checked_add(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        Ok(unlock_timestamp)
+    }
+
+    fn complete_unstake(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let user_authority = next_account_info(accounts_iter)?;
+        let staking_token_account = next_account_info(accounts_iter)?;
+        let withdrawal_queue_pda = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let staking_token_escrow_pda = next_account_info(accounts_iter)?;
+        let staking_token_mint_account = next_account_info(accounts_iter)?;
+        let token_program = next_account_info(accounts_iter)?;
+        Validator::check_signer(user_authority)?;
+        Validator::check_token_program(token_program, staking_token_mint_account)?;
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_withdrawal_queue_pda(
+            program_id,
+            staking_pool_pda,
+            user_authority,
+            withdrawal_queue_pda,
+        )?;
+        Validator::check_staking_token_mint(&staking_pool_state, staking_token_mint_account)?;
+        let bump = Validator::check_staking_token_escrow(
+            program_id,
+            staking_pool_pda,
+            staking_token_mint_account,
+            staking_token_escrow_pda,
+        )?;
+        if withdrawal_queue_pda.try_data_is_empty()? {
+            msg!("No unstake requests");
+            return Err(StakingError::UnbondingNotFinished.into());
+        }
+        let mut withdrawal_queue =
+            WithdrawalQueue::unpack(&withdrawal_queue_pda.try_borrow_data()?)?;
+        let (matured, pending): (Vec<UnbondingRequest>, Vec<UnbondingRequest>) = withdrawal_queue
+            .active_requests()
+            .iter()
+            .partition(|request| request.unlock_timestamp <= current_timestamp);
+        let amount = matured
+            .iter()
+            .try_fold(0u64, |sum, request| sum.checked_add(request.amount))
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        if amount == 0 {
+            msg!("No unstake request is unlocked at {}", current_timestamp);
+            return Err(StakingError::UnbondingNotFinished.into());
+        }
+        Self::transfer_tokens(
+            token_program,
+            staking_token_escrow_pda,
+            staking_token_mint_account,
+            staking_token_account,
+            staking_token_escrow_pda,
+            amount,
+            &[&[staking_pool_pda.key.as_ref(), b"staking-token", &[bump]]],
+        )?;
+        staking_pool_state.total_unbonding = staking_pool_state
+            .total_unbonding
+            .checked_sub(amount)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        if pending.is_empty() {
+            Self::close_program_account(withdrawal_queue_pda, user_authority)?;
+        } else {
+            withdrawal_queue.requests = Default::default();
+            withdrawal_queue.requests[..pending.len()].copy_from_slice(&pending);
+            withdrawal_queue.request_count = pending.len() as u8;
+            withdrawal_queue.pack(&mut withdrawal_queue_pda.try_borrow_mut_data()?)?;
+        }
         msg!(
-            "UNSTAKE Transfer: {} From: {} To: {}",
+            "COMPLETE UNSTAKE Transfer: {} From: {} To: {}",
             amount,
             staking_token_escrow_pda.key,
             staking_token_account.key
@@ -378,97 +1488,1600 @@ impl Processor {
         Ok(())
     }
 
-    fn get_rewards(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    fn close_user_state(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
         let user_authority = next_account_info(accounts_iter)?;
-        let rewards_token_account = next_account_info(accounts_iter)?;
         let user_state_pda = next_account_info(accounts_iter)?;
         let staking_pool_pda = next_account_info(accounts_iter)?;
+        // Anyone can close an emptied user state, so stale ones cannot hold up closing the pool
+        Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_user_state_pda(
+            program_id,
+            staking_pool_pda,
+            user_authority,
+            user_state_pda,
+        )?;
+        Self::close_user_state_account(user_state_pda, staking_pool_pda, user_authority)
+    }
+
+    /// Refunds the rent of an emptied user state to the user. Nothing accrues on a zero balance,
+    /// so the stored rewards are all the user could still claim.
+    fn close_user_state_account(
+        user_state_pda: &AccountInfo,
+        staking_pool_pda: &AccountInfo,
+        user_authority: &AccountInfo,
+    ) -> ProgramResult {
+        let user_state = UserState::unpack(&user_state_pda.try_borrow_data()?)?;
+        let rewards = user_state.unclaimed_rewards();
+        if user_state.balance != 0 || rewards != 0 {
+            msg!(
+                "User state still holds balance {} and rewards {}",
+                user_state.balance,
+                rewards
+            );
+            return Err(StakingError::UserStateNotEmpty.into());
+        }
+        let mut staking_pool_state =
+            StakingPoolState::unpack(&staking_pool_pda.try_borrow_data()?)?;
+        staking_pool_state.user_state_count = staking_pool_state
+            .user_state_count
+            .checked_sub(1)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        let lamports = Self::close_program_account(user_state_pda, user_authority)?;
+        msg!(
+            "CLOSE USER STATE: {} Refund: {}",
+            user_state_pda.key,
+            lamports
+        );
+        Ok(())
+    }
+
+    /// Moves all lamports of a program owned account to `destination` and returns them.
+    fn close_program_account(
+        account: &AccountInfo,
+        destination: &AccountInfo,
+    ) -> Result<u64, ProgramError> {
+        let lamports = account.lamports();
+        **destination.try_borrow_mut_lamports()? = destination
+            .lamports()
+            .checked_add(lamports)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        **account.try_borrow_mut_lamports()? = 0;
+        // Handing the account back to the system program lets the same address be recreated
+        account.realloc(0, false)?;
+        account.assign(&solana_program::system_program::id());
+        Ok(lamports)
+    }
+
+    fn close_pool(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let admin = next_account_info(accounts_iter)?;
+        let admin_staking_token_account = next_account_info(accounts_iter)?;
+        let admin_rewards_token_account = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let staking_token_escrow_pda = next_account_info(accounts_iter)?;
         let rewards_token_escrow_pda = next_account_info(accounts_iter)?;
-        let rewards_token_escrow_pda_owner_ai = next_account_info(accounts_iter)?;
+        let staking_token_mint_account = next_account_info(accounts_iter)?;
         let rewards_token_mint_account = next_account_info(accounts_iter)?;
-        let token_program = next_account_info(accounts_iter)?;
-        if !user_authority.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-        Self::update_rewards(staking_pool_pda, user_state_pda)?;
-        let user_rewards = Self::get_user_rewards(user_state_pda);
-        if user_rewards > 0 {
-            let (rewards_token_escrow_pda_owner, bump_seed) =
-                PdaHelper::find_rewards_token_pda(rewards_token_mint_account, program_id);
-            let transfer_ix = spl_token::instruction::transfer(
-                token_program.key,
-                rewards_token_escrow_pda.key,
-                rewards_token_account.key,
-                &rewards_token_escrow_pda_owner,
-                &[],
-                user_rewards,
+        let staking_token_program = next_account_info(accounts_iter)?;
+        let rewards_token_program = next_account_info(accounts_iter)?;
+        Validator::check_token_program(staking_token_program, staking_token_mint_account)?;
+        let staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_admin(&staking_pool_state, admin)?;
+        let receipt_mint = if staking_pool_state.receipt_mint != Pubkey::default() {
+            Some(next_account_info(accounts_iter)?)
+        } else {
+            None
+        };
+        Validator::check_staking_token_mint(&staking_pool_state, staking_token_mint_account)?;
+        if Validator::check_rewards_token_mint(&staking_pool_state, rewards_token_mint_account)?
+            != 0
+        {
+            msg!("Rewards token mint must be the one the pool was created with");
+            return Err(StakingError::InvalidRewardsTokenMint.into());
+        }
+        let staking_token_bump_seed = Validator::check_staking_token_escrow(
+            program_id,
+            staking_pool_pda,
+            staking_token_mint_account,
+            staking_token_escrow_pda,
+        )?;
+        // Nothing accrues without stakers, so the stored accounting is final
+        if staking_pool_state.total_supply != 0 || staking_pool_state.total_unbonding != 0 {
+            msg!(
+                "Total supply: {} Unbonding: {}",
+                staking_pool_state.total_supply,
+                staking_pool_state.total_unbonding
+            );
+            return Err(StakingError::PoolNotEmpty.into());
+        }
+        for index in 0..staking_pool_state.reward_stream_count as usize {
+            let rewards_owed = Self::rewards_owed(&staking_pool_state, index)?;
+            if rewards_owed != 0 {
+                msg!("Rewards owed: {} Stream: {}", rewards_owed, index);
+                return Err(StakingError::RewardsOutstanding.into());
+            }
+        }
+        // User states outliving the pool would carry stale reward checkpoints into a pool
+        // re-created under the same id
+        if staking_pool_state.user_state_count != 0 {
+            msg!("User states: {}", staking_pool_state.user_state_count);
+            return Err(StakingError::PoolNotEmpty.into());
+        }
+        // Receipts transferred to holders who never synced are still redeemable
+        if let Some(receipt_mint) = receipt_mint {
+            Validator::check_receipt_mint(
+                program_id,
+                &staking_pool_state,
+                staking_pool_pda,
+                receipt_mint,
+            )?;
+            let supply = StateWithExtensions::<Mint>::unpack(&receipt_mint.try_borrow_data()?)?
+                .base
+                .supply;
+            if supply != 0 {
+                msg!("Receipt supply: {}", supply);
+                return Err(StakingError::PoolNotEmpty.into());
+            }
+        }
+        Self::close_escrow(
+            staking_token_program,
+            staking_token_escrow_pda,
+            staking_token_mint_account,
+            admin_staking_token_account,
+            admin,
+            &[
+                staking_pool_pda.key.as_ref(),
+                b"staking-token",
+                &[staking_token_bump_seed],
+            ],
+        )?;
+        // Escrows of added streams follow as (admin token account, escrow, mint, token program)
+        // groups in stream order
+        let mut rewards_escrows = vec![(
+            admin_rewards_token_account,
+            rewards_token_escrow_pda,
+            rewards_token_mint_account,
+            rewards_token_program,
+        )];
+        for _ in 1..staking_pool_state.reward_stream_count {
+            rewards_escrows.push((
+                next_account_info(accounts_iter)?,
+                next_account_info(accounts_iter)?,
+                next_account_info(accounts_iter)?,
+                next_account_info(accounts_iter)?,
+            ));
+        }
+        for (
+            stream,
+            (
+                admin_rewards_token_account,
+                rewards_token_escrow_pda,
+                rewards_token_mint_account,
+                rewards_token_program,
+            ),
+        ) in staking_pool_state
+            .active_reward_streams()
+            .iter()
+            .zip(rewards_escrows)
+        {
+            if *rewards_token_mint_account.key != stream.mint {
+                msg!(
+                    "Rewards token mint passed: {}",
+                    rewards_token_mint_account.key
+                );
+                return Err(StakingError::InvalidRewardsTokenMint.into());
+            }
+            Validator::check_token_program(rewards_token_program, rewards_token_mint_account)?;
+            let rewards_token_bump_seed = Validator::check_rewards_token_escrow(
+                program_id,
+                staking_pool_pda,
+                &stream.mint,
+                rewards_token_escrow_pda,
             )?;
-            invoke_signed(
-                &transfer_ix,
+            Self::close_escrow(
+                rewards_token_program,
+                rewards_token_escrow_pda,
+                rewards_token_mint_account,
+                admin_rewards_token_account,
+                admin,
                 &[
-                    rewards_token_escrow_pda.clone(),
-                    rewards_token_account.clone(),
-                    rewards_token_escrow_pda_owner_ai.clone(),
-                    token_program.clone(),
-                ],
-                &[&[
-                    &rewards_token_mint_account.key.to_bytes(),
+                    staking_pool_pda.key.as_ref(),
+                    stream.mint.as_ref(),
                     b"rewards-token",
-                    &[bump_seed],
-                ]],
+                    &[rewards_token_bump_seed],
+                ],
             )?;
-            let mut user_state = UserState::unpack(&mut user_state_pda.try_borrow_mut_data()?);
-            user_state.rewards = 0;
-            user_state.pack(&mut user_state_pda.try_borrow_mut_data()?);
         }
+        let lamports = Self::close_program_account(staking_pool_pda, admin)?;
+        msg!("CLOSE POOL: {} Refund: {}", staking_pool_pda.key, lamports);
         Ok(())
     }
 
-    fn update_rewards(
-        staking_pool_pda: &AccountInfo,
-        user_state_pda: &AccountInfo,
+    /// Sends the remaining escrow balance to `destination` and closes the escrow to `admin`.
+    fn close_escrow<'a>(
+        token_program: &AccountInfo<'a>,
+        escrow_pda: &AccountInfo<'a>,
+        mint: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        admin: &AccountInfo<'a>,
+        escrow_seeds: &[&[u8]],
     ) -> ProgramResult {
-        let last_update_timestamp = Clock::get().unwrap().unix_timestamp;
-        let mut staking_pool_state =
-            StakingPoolState::unpack(&mut staking_pool_pda.try_borrow_mut_data().unwrap());
-        let mut user_state = UserState::unpack(&mut user_state_pda.try_borrow_mut_data().unwrap());
-        let rewards_per_token_stored = Self::reward_per_token(&staking_pool_state);
-        staking_pool_state.reward_per_token_stored = rewards_per_token_stored;
-        staking_pool_state.last_update_timestamp = last_update_timestamp;
-        user_state.rewards = Self::earned(&staking_pool_state, &user_state);
-        user_state.reward_per_token_paid = rewards_per_token_stored;
-        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?);
-        user_state.pack(&mut user_state_pda.try_borrow_mut_data()?);
-        Ok(())
-    }
-
-    fn get_user_rewards(user_state_pda: &AccountInfo) -> u64 {
-        let user_state = UserState::unpack(&mut user_state_pda.try_borrow_mut_data().unwrap());
-        return user_state.rewards;
-    }
-
-    fn reward_per_token(staking_pool_state: &StakingPoolState) -> u64 {
-        let total_supply = staking_pool_state.total_supply;
-        let reward_per_token_stored = staking_pool_state.reward_per_token_stored;
-        if total_supply == 0 {
-            return reward_per_token_stored;
-        }
-        let last_update_timestamp = staking_pool_state.last_update_timestamp;
-        let current_timestamp = Clock::get().unwrap().unix_timestamp + 100; // For testing purposes only
-        reward_per_token_stored
-            + (Self::REWARD_RATE
-                * ((current_timestamp - last_update_timestamp) as u64)
-                * 10_000_000_000)
-                / total_supply
-    }
-
-    fn earned(staking_pool_state: &StakingPoolState, user_state: &UserState) -> u64 {
-        let stake_amount = user_state.balance;
-        let user_reward_per_token_paid = user_state.reward_per_token_paid;
-        let user_rewards = user_state.rewards;
-        let reward_per_token = Self::reward_per_token(&staking_pool_state);
-        (stake_amount * (reward_per_token - user_reward_per_token_paid) / 10_000_000_000)
-            + user_rewards
+        let balance = Self::token_balance(escrow_pda)?;
+        if balance > 0 {
+            Self::transfer_tokens(
+                token_program,
+                escrow_pda,
+                mint,
+                destination,
+                escrow_pda,
+                balance,
+                &[escrow_seeds],
+            )?;
+        }
+        let close_ix = spl_token_2022::instruction::close_account(
+            token_program.key,
+            escrow_pda.key,
+            admin.key,
+            escrow_pda.key,
+            &[],
+        )?;
+        invoke_signed(
+            &close_ix,
+            &[escrow_pda.clone(), admin.clone(), token_program.clone()],
+            &[escrow_seeds],
+        )?;
+        msg!("Escrow {} closed, swept {}", escrow_pda.key, balance);
+        Ok(())
+    }
+
+    fn emergency_withdraw(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let user_authority = next_account_info(accounts_iter)?;
+        let staking_token_account = next_account_info(accounts_iter)?;
+        let user_state_pda = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let staking_token_escrow_pda = next_account_info(accounts_iter)?;
+        let staking_token_escrow_pda_owner_ai = next_account_info(accounts_iter)?;
+        let staking_token_mint_account = next_account_info(accounts_iter)?;
+        let token_program = next_account_info(accounts_iter)?;
+        Validator::check_signer(user_authority)?;
+        Validator::check_token_program(token_program, staking_token_mint_account)?;
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        let receipt_accounts = Self::next_receipt_accounts(
+            program_id,
+            &staking_pool_state,
+            staking_pool_pda,
+            user_authority,
+            accounts_iter,
+        )?;
+        let penalty_destination = next_account_info(accounts_iter).ok();
+        // The cooldown applies to every exit, rewards being forfeited or not
+        let unbonding_accounts = if staking_pool_state.unbonding_period != 0 {
+            let withdrawal_queue_pda = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            Validator::check_system_program(system_program)?;
+            let bump_seed = Validator::check_withdrawal_queue_pda(
+                program_id,
+                staking_pool_pda,
+                user_authority,
+                withdrawal_queue_pda,
+            )?;
+            Some((withdrawal_queue_pda, system_program, bump_seed))
+        } else {
+            None
+        };
+        Validator::check_user_state_pda(
+            program_id,
+            staking_pool_pda,
+            user_authority,
+            user_state_pda,
+        )?;
+        Validator::check_staking_token_mint(&staking_pool_state, staking_token_mint_account)?;
+        let bump = Validator::check_staking_token_escrow(
+            program_id,
+            staking_pool_pda,
+            staking_token_mint_account,
+            staking_token_escrow_pda,
+        )?;
+        if staking_token_escrow_pda_owner_ai.key != staking_token_escrow_pda.key {
+            msg!(
+                "Passed escrow staking owner: {}",
+                staking_token_escrow_pda_owner_ai.key
+            );
+            return Err(StakingError::InvalidStakingTokenEscrow.into());
+        }
+        let mut user_state = UserState::unpack(&user_state_pda.try_borrow_data()?)?;
+        if let Some(position) = user_state
+            .active_positions()
+            .iter()
+            .find(|position| current_timestamp < position.lock_until)
+        {
+            msg!(
+                "{} is locked until {}",
+                position.amount,
+                position.lock_until
+            );
+            return Err(StakingError::StakeLocked.into());
+        }
+        // The pool checkpoint keeps remaining stakers from accruing on the withdrawn supply, but
+        // the principal must stay recoverable even if the reward math fails
+        if let Err(error) =
+            Self::update_reward_per_token(&mut staking_pool_state, current_timestamp)
+        {
+            msg!("Skipping reward checkpoint: {}", error);
+        }
+        // Forfeited rewards are no longer owed and become withdrawable excess
+        if let Err(error) = Self::settle_user_rewards(&mut staking_pool_state, &mut user_state) {
+            msg!("Skipping reward settlement: {}", error);
+        }
+        Self::release_expired_positions(
+            &mut staking_pool_state,
+            &mut user_state,
+            current_timestamp,
+        )?;
+        if let Some((_, receipt_token_account, _)) = receipt_accounts {
+            Self::sync_receipts(
+                &mut staking_pool_state,
+                &mut user_state,
+                receipt_token_account,
+                current_timestamp,
+            )?;
+        }
+        let amount = user_state.balance;
+        if amount == 0 {
+            msg!("Nothing staked");
+            return Err(StakingError::ZeroAmount.into());
+        }
+        // Forfeiting rewards does not waive the early unstake penalty
+        let penalty = Self::early_unstake_penalty(
+            &staking_pool_state,
+            &user_state,
+            amount,
+            current_timestamp,
+        )?;
+        if let Some((receipt_mint, receipt_token_account, _)) = receipt_accounts {
+            Self::burn_receipts(
+                token_program,
+                receipt_mint,
+                receipt_token_account,
+                user_authority,
+                amount,
+            )?;
+        }
+        if let Some((withdrawal_queue_pda, system_program, bump_seed)) = unbonding_accounts {
+            let unlock_timestamp = Self::queue_unbonding(
+                program_id,
+                &mut staking_pool_state,
+                user_authority,
+                withdrawal_queue_pda,
+                staking_pool_pda,
+                system_program,
+                bump_seed,
+                amount - penalty,
+                current_timestamp,
+            )?;
+            msg!("Queued until {}", unlock_timestamp);
+        } else {
+            Self::transfer_tokens(
+                token_program,
+                staking_token_escrow_pda,
+                staking_token_mint_account,
+                staking_token_account,
+                staking_token_escrow_pda_owner_ai,
+                amount - penalty,
+                &[&[staking_pool_pda.key.as_ref(), b"staking-token", &[bump]]],
+            )?;
+        }
+        for (stream, user_stream) in staking_pool_state
+            .active_reward_streams_mut()
+            .iter_mut()
+            .zip(user_state.reward_streams.iter_mut())
+        {
+            stream.rewards_unclaimed = stream.rewards_unclaimed.saturating_sub(user_stream.rewards);
+            user_stream.rewards = 0;
+            user_stream.reward_per_token_paid = stream.reward_per_token_stored;
+        }
+        staking_pool_state.total_supply = staking_pool_state.total_supply.saturating_sub(amount);
+        staking_pool_state.total_effective_supply = staking_pool_state
+            .total_effective_supply
+            .saturating_sub(user_state.effective_balance);
+        if penalty != 0 {
+            let penalty_destination =
+                penalty_destination.ok_or(ProgramError::NotEnoughAccountKeys)?;
+            Self::withhold_penalty(
+                program_id,
+                &mut staking_pool_state,
+                staking_pool_pda,
+                staking_token_escrow_pda,
+                staking_token_mint_account,
+                penalty_destination,
+                token_program,
+                bump,
+                penalty,
+            )?;
+        }
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        user_state.balance = 0;
+        user_state.effective_balance = 0;
+        user_state.pack(&mut user_state_pda.try_borrow_mut_data()?)?;
+        msg!(
+            "EMERGENCY WITHDRAW Transfer: {} Penalty: {} From: {} To: {}",
+            amount - penalty,
+            penalty,
+            staking_token_escrow_pda.key,
+            staking_token_account.key
+        );
+        Ok(())
+    }
+
+    fn get_rewards(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let user_authority = next_account_info(accounts_iter)?;
+        let rewards_token_account = next_account_info(accounts_iter)?;
+        let user_state_pda = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let rewards_token_escrow_pda = next_account_info(accounts_iter)?;
+        let rewards_token_escrow_pda_owner_ai = next_account_info(accounts_iter)?;
+        let rewards_token_mint_account = next_account_info(accounts_iter)?;
+        let token_program = next_account_info(accounts_iter)?;
+        Validator::check_signer(user_authority)?;
+        let staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_user_state_pda(
+            program_id,
+            staking_pool_pda,
+            user_authority,
+            user_state_pda,
+        )?;
+        if staking_pool_state.claiming_paused {
+            msg!("Claiming rewards is paused");
+            return Err(StakingError::ClaimingPaused.into());
+        }
+        if rewards_token_escrow_pda_owner_ai.key != rewards_token_escrow_pda.key {
+            msg!(
+                "Passed escrow rewards owner: {}",
+                rewards_token_escrow_pda_owner_ai.key
+            );
+            return Err(StakingError::InvalidRewardsTokenEscrow.into());
+        }
+        // Every claim carries a fee token account while the pool charges a fee
+        let charges_fee = staking_pool_state.fee_bps != 0;
+        let fee_token_account = if charges_fee {
+            Some(next_account_info(accounts_iter)?)
+        } else {
+            None
+        };
+        let receipt_token_account = if staking_pool_state.receipt_mint != Pubkey::default() {
+            let receipt_token_account = next_account_info(accounts_iter)?;
+            Validator::check_holder_receipt_token_account(
+                &staking_pool_state,
+                receipt_token_account,
+                user_authority.key,
+            )?;
+            Some(receipt_token_account)
+        } else {
+            None
+        };
+        // Further streams are paid from trailing (user token account, escrow, mint, token program)
+        // groups
+        let mut claims = vec![(
+            rewards_token_account,
+            rewards_token_escrow_pda,
+            rewards_token_mint_account,
+            token_program,
+            fee_token_account,
+        )];
+        while let Ok(rewards_token_account) = next_account_info(accounts_iter) {
+            claims.push((
+                rewards_token_account,
+                next_account_info(accounts_iter)?,
+                next_account_info(accounts_iter)?,
+                next_account_info(accounts_iter)?,
+                if charges_fee {
+                    Some(next_account_info(accounts_iter)?)
+                } else {
+                    None
+                },
+            ));
+        }
+        // Receipts transferred away stop earning for the sender
+        Self::update_rewards(
+            staking_pool_pda,
+            user_state_pda,
+            receipt_token_account,
+            current_timestamp,
+        )?;
+        let mut staking_pool_state =
+            StakingPoolState::unpack(&staking_pool_pda.try_borrow_data()?)?;
+        let mut user_state = UserState::unpack(&user_state_pda.try_borrow_data()?)?;
+        for (
+            rewards_token_account,
+            rewards_token_escrow_pda,
+            rewards_token_mint_account,
+            token_program,
+            fee_token_account,
+        ) in claims
+        {
+            Validator::check_token_program(token_program, rewards_token_mint_account)?;
+            let index = Validator::check_rewards_token_mint(
+                &staking_pool_state,
+                rewards_token_mint_account,
+            )?;
+            let bump_seed = Validator::check_rewards_token_escrow(
+                program_id,
+                staking_pool_pda,
+                rewards_token_mint_account.key,
+                rewards_token_escrow_pda,
+            )?;
+            let user_rewards = user_state.reward_streams[index].rewards;
+            if user_rewards == 0 {
+                continue;
+            }
+            let signer_seeds: &[&[&[u8]]] = &[&[
+                staking_pool_pda.key.as_ref(),
+                rewards_token_mint_account.key.as_ref(),
+                b"rewards-token",
+                &[bump_seed],
+            ]];
+            let fee = Self::pay_reward_fee(
+                &staking_pool_state,
+                token_program,
+                rewards_token_escrow_pda,
+                rewards_token_mint_account,
+                fee_token_account,
+                user_rewards,
+                signer_seeds,
+            )?;
+            Self::transfer_tokens(
+                token_program,
+                rewards_token_escrow_pda,
+                rewards_token_mint_account,
+                rewards_token_account,
+                rewards_token_escrow_pda,
+                user_rewards - fee,
+                signer_seeds,
+            )?;
+            user_state.reward_streams[index].rewards = 0;
+            let stream = &mut staking_pool_state.reward_streams[index];
+            stream.rewards_unclaimed = stream
+                .rewards_unclaimed
+                .checked_sub(user_rewards)
+                .ok_or(StakingError::ArithmeticOverflow)?;
+            msg!(
+                "GET REWARDS Transfer: {} Fee: {} Mint: {} To: {}",
+                user_rewards - fee,
+                fee,
+                rewards_token_mint_account.key,
+                rewards_token_account.key
+            );
+        }
+        user_state.pack(&mut user_state_pda.try_borrow_mut_data()?)?;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        Ok(())
+    }
+
+    fn compound(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let user_authority = next_account_info(accounts_iter)?;
+        let user_state_pda = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let rewards_token_escrow_pda = next_account_info(accounts_iter)?;
+        let staking_token_escrow_pda = next_account_info(accounts_iter)?;
+        let staking_token_mint_account = next_account_info(accounts_iter)?;
+        let token_program = next_account_info(accounts_iter)?;
+        let fee_token_account = next_account_info(accounts_iter).ok();
+        Validator::check_signer(user_authority)?;
+        Validator::check_token_program(token_program, staking_token_mint_account)?;
+        let staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_user_state_pda(
+            program_id,
+            staking_pool_pda,
+            user_authority,
+            user_state_pda,
+        )?;
+        if staking_pool_state.staking_paused {
+            msg!("Staking is paused");
+            return Err(StakingError::StakingPaused.into());
+        }
+        if staking_pool_state.claiming_paused {
+            msg!("Claiming rewards is paused");
+            return Err(StakingError::ClaimingPaused.into());
+        }
+        if staking_pool_state.receipt_mint != Pubkey::default() {
+            msg!("Compounding would stake without minting receipt tokens");
+            return Err(StakingError::ReceiptMintUnsupported.into());
+        }
+        Validator::check_staking_token_mint(&staking_pool_state, staking_token_mint_account)?;
+        let index = staking_pool_state
+            .find_reward_stream(staking_token_mint_account.key)
+            .ok_or(StakingError::CompoundMintMismatch)?;
+        let rewards_bump_seed = Validator::check_rewards_token_escrow(
+            program_id,
+            staking_pool_pda,
+            staking_token_mint_account.key,
+            rewards_token_escrow_pda,
+        )?;
+        Validator::check_staking_token_escrow(
+            program_id,
+            staking_pool_pda,
+            staking_token_mint_account,
+            staking_token_escrow_pda,
+        )?;
+        Self::update_rewards(staking_pool_pda, user_state_pda, None, current_timestamp)?;
+        let mut staking_pool_state =
+            StakingPoolState::unpack(&staking_pool_pda.try_borrow_data()?)?;
+        let mut user_state = UserState::unpack(&user_state_pda.try_borrow_data()?)?;
+        let user_rewards = user_state.reward_streams[index].rewards;
+        if user_rewards == 0 {
+            msg!("No rewards to compound");
+            return Err(StakingError::ZeroAmount.into());
+        }
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            staking_pool_pda.key.as_ref(),
+            staking_token_mint_account.key.as_ref(),
+            b"rewards-token",
+            &[rewards_bump_seed],
+        ]];
+        let fee = Self::pay_reward_fee(
+            &staking_pool_state,
+            token_program,
+            rewards_token_escrow_pda,
+            staking_token_mint_account,
+            fee_token_account,
+            user_rewards,
+            signer_seeds,
+        )?;
+        // Transfer fees apply between the escrows as well, so only the received delta is staked
+        let escrow_balance_before = Self::token_balance(staking_token_escrow_pda)?;
+        Self::transfer_tokens(
+            token_program,
+            rewards_token_escrow_pda,
+            staking_token_mint_account,
+            staking_token_escrow_pda,
+            rewards_token_escrow_pda,
+            user_rewards - fee,
+            signer_seeds,
+        )?;
+        let received = Self::token_balance(staking_token_escrow_pda)?
+            .checked_sub(escrow_balance_before)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        user_state.reward_streams[index].rewards = 0;
+        user_state.balance = user_state
+            .balance
+            .checked_add(received)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        user_state.effective_balance = user_state
+            .effective_balance
+            .checked_add(received)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        let stream = &mut staking_pool_state.reward_streams[index];
+        stream.rewards_unclaimed = stream
+            .rewards_unclaimed
+            .checked_sub(user_rewards)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        staking_pool_state.total_supply = staking_pool_state
+            .total_supply
+            .checked_add(received)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        staking_pool_state.total_effective_supply = staking_pool_state
+            .total_effective_supply
+            .checked_add(received)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        user_state.pack(&mut user_state_pda.try_borrow_mut_data()?)?;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!(
+            "COMPOUND From: {} Rewards: {} Fee: {} Staked: {}",
+            user_authority.key,
+            user_rewards,
+            fee,
+            received
+        );
+        Ok(())
+    }
+
+    /// Sends the pool reward fee on `rewards` from the rewards escrow to `fee_token_account` and
+    /// returns it. The account is only required while the fee is not zero.
+    #[allow(clippy::too_many_arguments)]
+    fn pay_reward_fee<'a>(
+        staking_pool_state: &StakingPoolState,
+        token_program: &AccountInfo<'a>,
+        rewards_token_escrow_pda: &AccountInfo<'a>,
+        rewards_token_mint_account: &AccountInfo<'a>,
+        fee_token_account: Option<&AccountInfo<'a>>,
+        rewards: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<u64, ProgramError> {
+        let fee = math::reward_fee(rewards, staking_pool_state.fee_bps)?;
+        if fee == 0 {
+            return Ok(0);
+        }
+        let fee_token_account = fee_token_account.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        Validator::check_fee_token_account(
+            staking_pool_state,
+            fee_token_account,
+            rewards_token_mint_account,
+        )?;
+        Self::transfer_tokens(
+            token_program,
+            rewards_token_escrow_pda,
+            rewards_token_mint_account,
+            fee_token_account,
+            rewards_token_escrow_pda,
+            fee,
+            signer_seeds,
+        )?;
+        Ok(fee)
+    }
+
+    /// Settles the user's rewards and releases their expired positions. In pools with a receipt
+    /// mint the stake is then moved to the holder's receipt balance; receipts sent away since the
+    /// last settlement are dropped first, so they earn nothing for the sender.
+    fn update_rewards(
+        staking_pool_pda: &AccountInfo,
+        user_state_pda: &AccountInfo,
+        receipt_token_account: Option<&AccountInfo>,
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let mut staking_pool_state =
+            StakingPoolState::unpack(&staking_pool_pda.try_borrow_data()?)?;
+        let mut user_state = UserState::unpack(&user_state_pda.try_borrow_data()?)?;
+        Self::update_reward_per_token(&mut staking_pool_state, current_timestamp)?;
+        if let Some(receipt_token_account) = receipt_token_account {
+            Self::forfeit_sent_receipts(
+                &mut staking_pool_state,
+                &mut user_state,
+                receipt_token_account,
+            )?;
+        }
+        Self::settle_user_rewards(&mut staking_pool_state, &mut user_state)?;
+        Self::release_expired_positions(
+            &mut staking_pool_state,
+            &mut user_state,
+            current_timestamp,
+        )?;
+        if let Some(receipt_token_account) = receipt_token_account {
+            Self::sync_receipts(
+                &mut staking_pool_state,
+                &mut user_state,
+                receipt_token_account,
+                current_timestamp,
+            )?;
+        }
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        user_state.pack(&mut user_state_pda.try_borrow_mut_data()?)?;
+        Ok(())
+    }
+
+    fn set_reward_rate(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        reward_rate: u64,
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let admin = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let rewards_token_mint_account = next_account_info(accounts_iter)?;
+        let rewards_token_escrow_pda = next_account_info(accounts_iter).ok();
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_admin(&staking_pool_state, admin)?;
+        let index =
+            Validator::check_rewards_token_mint(&staking_pool_state, rewards_token_mint_account)?;
+        // Checkpoint rewards accrued at the old rate before switching to the new one
+        Self::update_reward_per_token(&mut staking_pool_state, current_timestamp)?;
+        let period_finish = staking_pool_state.reward_streams[index].period_finish;
+        if period_finish > current_timestamp {
+            // The rest of the running period must stay funded at the new rate
+            let rewards_token_escrow_pda =
+                rewards_token_escrow_pda.ok_or(ProgramError::NotEnoughAccountKeys)?;
+            Validator::check_rewards_token_escrow(
+                program_id,
+                staking_pool_pda,
+                rewards_token_mint_account.key,
+                rewards_token_escrow_pda,
+            )?;
+            let remaining = (period_finish - current_timestamp) as u64;
+            let rewards_balance = Self::token_balance(rewards_token_escrow_pda)?
+                .saturating_sub(Self::rewards_owed(&staking_pool_state, index)?);
+            if reward_rate > rewards_balance / remaining {
+                msg!(
+                    "Reward rate {} is too high for rewards escrow balance {}",
+                    reward_rate,
+                    rewards_balance
+                );
+                return Err(StakingError::InsufficientRewardEscrow.into());
+            }
+        }
+        staking_pool_state.reward_streams[index].reward_rate = reward_rate;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!(
+            "SET REWARD RATE: {} Mint: {}",
+            reward_rate,
+            rewards_token_mint_account.key
+        );
+        Ok(())
+    }
+
+    fn notify_reward_amount(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        duration: u64,
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let admin = next_account_info(accounts_iter)?;
+        let admin_rewards_token_account = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let rewards_token_escrow_pda = next_account_info(accounts_iter)?;
+        let rewards_token_mint_account = next_account_info(accounts_iter)?;
+        let token_program = next_account_info(accounts_iter)?;
+        Validator::check_token_program(token_program, rewards_token_mint_account)?;
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_admin(&staking_pool_state, admin)?;
+        if amount == 0 {
+            msg!("Amount = 0");
+            return Err(StakingError::ZeroAmount.into());
+        }
+        let index =
+            Validator::check_rewards_token_mint(&staking_pool_state, rewards_token_mint_account)?;
+        Validator::check_rewards_token_escrow(
+            program_id,
+            staking_pool_pda,
+            rewards_token_mint_account.key,
+            rewards_token_escrow_pda,
+        )?;
+        Self::transfer_tokens(
+            token_program,
+            admin_rewards_token_account,
+            rewards_token_mint_account,
+            rewards_token_escrow_pda,
+            admin,
+            amount,
+            &[],
+        )?;
+        Self::update_reward_per_token(&mut staking_pool_state, current_timestamp)?;
+        // Undistributed rewards of an unfinished period are rolled over into the new one
+        let leftover =
+            Self::rewards_remaining(&staking_pool_state.reward_streams[index], current_timestamp)?;
+        let rewards = amount
+            .checked_add(leftover)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        let (reward_rate, duration) = match rewards.checked_div(duration) {
+            Some(reward_rate) => (reward_rate, duration),
+            None => {
+                // The period lasts as long as the configured rate can pay for
+                let reward_rate = staking_pool_state.reward_streams[index].reward_rate;
+                match rewards.checked_div(reward_rate) {
+                    Some(duration) if duration > 0 => (reward_rate, duration),
+                    _ => {
+                        msg!(
+                            "Duration = 0 and reward rate {} pays no second",
+                            reward_rate
+                        );
+                        return Err(StakingError::ZeroDuration.into());
+                    }
+                }
+            }
+        };
+        let rewards_balance = Self::token_balance(rewards_token_escrow_pda)?
+            .saturating_sub(Self::rewards_owed(&staking_pool_state, index)?);
+        if reward_rate > rewards_balance / duration {
+            msg!(
+                "Reward rate {} is too high for rewards escrow balance {}",
+                reward_rate,
+                rewards_balance
+            );
+            return Err(StakingError::InsufficientRewardEscrow.into());
+        }
+        let stream = &mut staking_pool_state.reward_streams[index];
+        stream.reward_rate = reward_rate;
+        stream.period_finish = i64::try_from(duration)
+            .ok()
+            .and_then(|duration| current_timestamp.checked_add(duration))
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        let period_finish = stream.period_finish;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!(
+            "NOTIFY REWARD AMOUNT: {} Reward rate: {} Period finish: {}",
+            amount,
+            reward_rate,
+            period_finish
+        );
+        Ok(())
+    }
+
+    fn withdraw_excess_rewards(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let admin = next_account_info(accounts_iter)?;
+        let admin_rewards_token_account = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let rewards_token_escrow_pda = next_account_info(accounts_iter)?;
+        let rewards_token_mint_account = next_account_info(accounts_iter)?;
+        let token_program = next_account_info(accounts_iter)?;
+        Validator::check_token_program(token_program, rewards_token_mint_account)?;
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_admin(&staking_pool_state, admin)?;
+        if amount == 0 {
+            msg!("Amount = 0");
+            return Err(StakingError::ZeroAmount.into());
+        }
+        let index =
+            Validator::check_rewards_token_mint(&staking_pool_state, rewards_token_mint_account)?;
+        let bump_seed = Validator::check_rewards_token_escrow(
+            program_id,
+            staking_pool_pda,
+            rewards_token_mint_account.key,
+            rewards_token_escrow_pda,
+        )?;
+        Self::update_reward_per_token(&mut staking_pool_state, current_timestamp)?;
+        // Rewards still to be emitted in the current period are committed to stakers as well
+        let remaining =
+            Self::rewards_remaining(&staking_pool_state.reward_streams[index], current_timestamp)?;
+        let rewards_balance = Self::token_balance(rewards_token_escrow_pda)?;
+        let excess = rewards_balance
+            .saturating_sub(Self::rewards_owed(&staking_pool_state, index)?)
+            .saturating_sub(remaining);
+        if amount > excess {
+            msg!(
+                "Requested {} but only {} reward tokens are not owed to stakers",
+                amount,
+                excess
+            );
+            return Err(StakingError::InsufficientExcessRewards.into());
+        }
+        Self::transfer_tokens(
+            token_program,
+            rewards_token_escrow_pda,
+            rewards_token_mint_account,
+            admin_rewards_token_account,
+            rewards_token_escrow_pda,
+            amount,
+            &[&[
+                staking_pool_pda.key.as_ref(),
+                rewards_token_mint_account.key.as_ref(),
+                b"rewards-token",
+                &[bump_seed],
+            ]],
+        )?;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!("WITHDRAW EXCESS REWARDS: {}", amount);
+        Ok(())
+    }
+
+    fn set_lock_multiplier(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        max_lock_duration: u64,
+        max_lock_multiplier: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let admin = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_admin(&staking_pool_state, admin)?;
+        if max_lock_multiplier < LOCK_MULTIPLIER_BASE {
+            msg!("Lock multiplier: {}", max_lock_multiplier);
+            return Err(StakingError::InvalidLockMultiplier.into());
+        }
+        // Existing locks keep the boost they were created with
+        staking_pool_state.max_lock_duration = max_lock_duration;
+        staking_pool_state.max_lock_multiplier = max_lock_multiplier;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!(
+            "SET LOCK MULTIPLIER: {} Max lock duration: {}",
+            max_lock_multiplier,
+            max_lock_duration
+        );
+        Ok(())
+    }
+
+    fn set_unstake_penalty(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        penalty_bps: u64,
+        decay_period: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let admin = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_admin(&staking_pool_state, admin)?;
+        if penalty_bps > MAX_UNSTAKE_PENALTY_BPS {
+            msg!("Penalty: {} bps", penalty_bps);
+            return Err(StakingError::InvalidUnstakePenalty.into());
+        }
+        if penalty_bps != 0
+            && staking_pool_state.penalty_treasury == Pubkey::default()
+            && staking_pool_state
+                .find_reward_stream(&staking_pool_state.staking_token_mint)
+                .is_none()
+        {
+            msg!("Add a reward stream paying the staking token first");
+            return Err(StakingError::PenaltyDestinationMissing.into());
+        }
+        staking_pool_state.unstake_penalty_bps = penalty_bps;
+        staking_pool_state.penalty_decay_period = decay_period;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!(
+            "SET UNSTAKE PENALTY: {} bps Decay period: {}",
+            penalty_bps,
+            decay_period
+        );
+        Ok(())
+    }
+
+    fn create_receipt_mint(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let admin = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let receipt_mint = next_account_info(accounts_iter)?;
+        let staking_token_mint_account = next_account_info(accounts_iter)?;
+        let token_program = next_account_info(accounts_iter)?;
+        let system_program = next_account_info(accounts_iter)?;
+        Validator::check_system_program(system_program)?;
+        Validator::check_token_program(token_program, staking_token_mint_account)?;
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_admin(&staking_pool_state, admin)?;
+        Validator::check_staking_token_mint(&staking_pool_state, staking_token_mint_account)?;
+        if staking_pool_state.receipt_mint != Pubkey::default() {
+            msg!("Receipt mint: {}", staking_pool_state.receipt_mint);
+            return Err(StakingError::AlreadyInitialized.into());
+        }
+        // Every staked token must be backed by a receipt token
+        if staking_pool_state.total_supply != 0 || staking_pool_state.total_unbonding != 0 {
+            msg!(
+                "Total supply: {} Unbonding: {}",
+                staking_pool_state.total_supply,
+                staking_pool_state.total_unbonding
+            );
+            return Err(StakingError::PoolNotEmpty.into());
+        }
+        if staking_pool_state.unbonding_period != 0 {
+            msg!("Unbonding period: {}", staking_pool_state.unbonding_period);
+            return Err(StakingError::ReceiptMintUnsupported.into());
+        }
+        let (receipt_mint_pda, bump_seed) =
+            PdaHelper::find_receipt_mint_pda(staking_pool_pda.key, program_id);
+        if *receipt_mint.key != receipt_mint_pda {
+            msg!("Receipt mint passed: {}", receipt_mint.key);
+            return Err(StakingError::InvalidReceiptMint.into());
+        }
+        // Mints cannot be closed, so a pool re-created under a closed pool id takes over the
+        // emptied receipt mint of its predecessor
+        if receipt_mint.try_data_is_empty()? {
+            let mint_len = ExtensionType::try_calculate_account_len::<Mint>(&[])?;
+            Self::create_pda_account(
+                admin,
+                receipt_mint,
+                system_program,
+                mint_len,
+                token_program.key,
+                &[staking_pool_pda.key.as_ref(), b"receipt-mint", &[bump_seed]],
+            )?;
+            let decimals = StateWithExtensions::<Mint>::unpack(
+                &staking_token_mint_account.try_borrow_data()?,
+            )?
+            .base
+            .decimals;
+            let init_mint_ix = spl_token_2022::instruction::initialize_mint2(
+                token_program.key,
+                receipt_mint.key,
+                receipt_mint.key,
+                None,
+                decimals,
+            )?;
+            invoke(
+                &init_mint_ix,
+                &[receipt_mint.clone(), token_program.clone()],
+            )?;
+        } else {
+            if receipt_mint.owner != token_program.key {
+                msg!("Receipt mint owner: {}", receipt_mint.owner);
+                return Err(StakingError::InvalidReceiptMint.into());
+            }
+            let supply = StateWithExtensions::<Mint>::unpack(&receipt_mint.try_borrow_data()?)?
+                .base
+                .supply;
+            if supply != 0 {
+                msg!("Receipt supply: {}", supply);
+                return Err(StakingError::InvalidReceiptMint.into());
+            }
+        }
+        staking_pool_state.receipt_mint = *receipt_mint.key;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!("CREATE RECEIPT MINT: {}", receipt_mint.key);
+        Ok(())
+    }
+
+    fn sync_receipt_balance(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let payer = next_account_info(accounts_iter)?;
+        let receipt_token_account = next_account_info(accounts_iter)?;
+        let user_state_pda = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let system_program = next_account_info(accounts_iter)?;
+        Validator::check_signer(payer)?;
+        Validator::check_system_program(system_program)?;
+        let staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        if staking_pool_state.receipt_mint == Pubkey::default() {
+            msg!("Staking pool {} has no receipt mint", staking_pool_pda.key);
+            return Err(StakingError::InvalidReceiptMint.into());
+        }
+        let holder =
+            Validator::check_receipt_token_account(&staking_pool_state, receipt_token_account)?;
+        let bump_seed = Validator::check_holder_state_pda(
+            program_id,
+            staking_pool_pda,
+            &holder,
+            user_state_pda,
+        )?;
+        if user_state_pda.try_data_is_empty()? {
+            Self::create_user_state(
+                program_id,
+                payer,
+                user_state_pda,
+                staking_pool_pda,
+                &holder,
+                system_program,
+                bump_seed,
+            )?;
+        }
+        Self::update_rewards(
+            staking_pool_pda,
+            user_state_pda,
+            Some(receipt_token_account),
+            current_timestamp,
+        )?;
+        let user_state = UserState::unpack(&user_state_pda.try_borrow_data()?)?;
+        msg!(
+            "SYNC RECEIPT BALANCE Holder: {} Balance: {}",
+            holder,
+            user_state.balance
+        );
+        Ok(())
+    }
+
+    fn release_expired_locks(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let user = next_account_info(accounts_iter)?;
+        let user_state_pda = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        if staking_pool_state.receipt_mint != Pubkey::default() {
+            // Settling a receipt holder without syncing their receipts would pay a stale balance
+            msg!("Receipt tokens cannot be locked");
+            return Err(StakingError::ReceiptMintUnsupported.into());
+        }
+        Validator::check_user_state_pda(program_id, staking_pool_pda, user, user_state_pda)?;
+        Self::update_rewards(staking_pool_pda, user_state_pda, None, current_timestamp)?;
+        msg!("RELEASE EXPIRED LOCKS User: {}", user.key);
+        Ok(())
+    }
+
+    fn set_reward_fee(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        fee_bps: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let admin = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let fee_recipient = next_account_info(accounts_iter)?;
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_admin(&staking_pool_state, admin)?;
+        if fee_bps > MAX_FEE_BPS {
+            msg!("Fee: {} bps, maximum: {} bps", fee_bps, MAX_FEE_BPS);
+            return Err(StakingError::RewardFeeTooHigh.into());
+        }
+        staking_pool_state.fee_bps = fee_bps;
+        staking_pool_state.fee_recipient = *fee_recipient.key;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!(
+            "SET REWARD FEE: {} bps Recipient: {}",
+            fee_bps,
+            fee_recipient.key
+        );
+        Ok(())
+    }
+
+    fn set_unbonding_period(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        unbonding_period: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let admin = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_admin(&staking_pool_state, admin)?;
+        if unbonding_period != 0 && staking_pool_state.receipt_mint != Pubkey::default() {
+            msg!("Receipt tokens are unstaked without a cooldown");
+            return Err(StakingError::ReceiptMintUnsupported.into());
+        }
+        // Queued requests keep the unlock time they were created with
+        staking_pool_state.unbonding_period = unbonding_period;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!("SET UNBONDING PERIOD: {}", unbonding_period);
+        Ok(())
+    }
+
+    fn propose_admin(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        new_admin: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let admin = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_admin(&staking_pool_state, admin)?;
+        staking_pool_state.pending_admin = new_admin;
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!("PROPOSE ADMIN: {}", new_admin);
+        Ok(())
+    }
+
+    fn accept_admin(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let pending_admin = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_signer(pending_admin)?;
+        if staking_pool_state.pending_admin == Pubkey::default()
+            || staking_pool_state.pending_admin != *pending_admin.key
+        {
+            msg!(
+                "Only the pending admin can accept. Pending admin: {}, signer: {}",
+                staking_pool_state.pending_admin,
+                pending_admin.key
+            );
+            return Err(StakingError::Unauthorized.into());
+        }
+        staking_pool_state.admin = *pending_admin.key;
+        staking_pool_state.pending_admin = Pubkey::default();
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!("ACCEPT ADMIN: {}", pending_admin.key);
+        Ok(())
+    }
+
+    /// Updates the pause flags that are `Some` and leaves the others untouched.
+    fn set_paused(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        staking: Option<bool>,
+        claiming: Option<bool>,
+        accrual: Option<bool>,
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let admin = next_account_info(accounts_iter)?;
+        let staking_pool_pda = next_account_info(accounts_iter)?;
+        let mut staking_pool_state = Validator::check_staking_pool(program_id, staking_pool_pda)?;
+        Validator::check_admin(&staking_pool_state, admin)?;
+        // Rewards up to now accrue under the previous accrual flag
+        Self::update_reward_per_token(&mut staking_pool_state, current_timestamp)?;
+        if let Some(paused) = staking {
+            staking_pool_state.staking_paused = paused;
+        }
+        if let Some(paused) = claiming {
+            staking_pool_state.claiming_paused = paused;
+        }
+        if let Some(paused) = accrual {
+            staking_pool_state.accrual_paused = paused;
+        }
+        staking_pool_state.pack(&mut staking_pool_pda.try_borrow_mut_data()?)?;
+        msg!(
+            "PAUSED: staking {} claiming {} accrual {}",
+            staking_pool_state.staking_paused,
+            staking_pool_state.claiming_paused,
+            staking_pool_state.accrual_paused
+        );
+        Ok(())
+    }
+
+    /// Checkpoints `reward_per_token_stored` of every stream and books what all stakers accrued
+    /// since the last update into its `rewards_accrued`. Lock expiries passed in between are
+    /// checkpointed first, and their boost leaves the total effective supply right there. The
+    /// pool is only changed if every checkpoint succeeds.
+    fn update_reward_per_token(
+        staking_pool_state: &mut StakingPoolState,
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let mut updated = staking_pool_state.clone();
+        for index in 0..updated.lock_expiry_count as usize {
+            let lock_until = updated.lock_expiries[index].lock_until;
+            if lock_until <= updated.last_update_timestamp || lock_until > current_timestamp {
+                continue;
+            }
+            Self::checkpoint_reward_streams(&mut updated, lock_until)?;
+            let expiry = &mut updated.lock_expiries[index];
+            for (reward_per_token, stream) in expiry
+                .reward_per_token
+                .iter_mut()
+                .zip(updated.reward_streams.iter())
+            {
+                *reward_per_token = stream.reward_per_token_stored;
+            }
+            updated.total_effective_supply = updated
+                .total_effective_supply
+                .checked_sub(expiry.boost)
+                .ok_or(StakingError::ArithmeticOverflow)?;
+        }
+        Self::checkpoint_reward_streams(&mut updated, current_timestamp)?;
+        *staking_pool_state = updated;
+        Ok(())
+    }
+
+    fn checkpoint_reward_streams(
+        staking_pool_state: &mut StakingPoolState,
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        for index in 0..staking_pool_state.reward_stream_count as usize {
+            let reward_per_token =
+                Self::reward_per_token(staking_pool_state, index, current_timestamp)?;
+            let total_effective_supply = staking_pool_state.total_effective_supply;
+            let stream = &mut staking_pool_state.reward_streams[index];
+            let accrued = math::accrued(
+                total_effective_supply,
+                reward_per_token,
+                stream.reward_per_token_stored,
+            )?;
+            stream.rewards_accrued = stream
+                .rewards_accrued
+                .checked_add(accrued)
+                .ok_or(StakingError::ArithmeticOverflow)?;
+            stream.reward_per_token_stored = reward_per_token;
+        }
+        staking_pool_state.last_update_timestamp = current_timestamp;
+        Ok(())
+    }
+
+    /// Moves the user's accrual since the last settlement from each stream's `rewards_accrued`
+    /// into its rewards. Expects the pool to be checkpointed already. The boost of a position
+    /// the pool already dropped at expiry only earns up to the reward per token kept there.
+    /// Nothing is changed unless every stream settles.
+    fn settle_user_rewards(
+        staking_pool_state: &mut StakingPoolState,
+        user_state: &mut UserState,
+    ) -> ProgramResult {
+        let mut live_balance = user_state.effective_balance;
+        let mut expired_boosts = Vec::new();
+        for position in user_state.active_positions() {
+            if position.boost() == 0
+                || position.lock_until > staking_pool_state.last_update_timestamp
+            {
+                continue;
+            }
+            let index = Self::lock_expiry_index(staking_pool_state, position.lock_until)?;
+            live_balance = live_balance
+                .checked_sub(position.boost())
+                .ok_or(StakingError::ArithmeticOverflow)?;
+            expired_boosts.push((position.boost(), index));
+        }
+        let mut reward_streams = staking_pool_state.reward_streams;
+        let mut user_reward_streams = user_state.reward_streams;
+        for index in 0..staking_pool_state.reward_stream_count as usize {
+            let user_stream = &mut user_reward_streams[index];
+            let reward_per_token_stored =
+                staking_pool_state.reward_streams[index].reward_per_token_stored;
+            let mut accrued = math::accrued(
+                live_balance,
+                reward_per_token_stored,
+                user_stream.reward_per_token_paid,
+            )?;
+            for (boost, expiry_index) in &expired_boosts {
+                let boost_accrued = math::accrued(
+                    *boost,
+                    staking_pool_state.lock_expiries[*expiry_index].reward_per_token[index],
+                    user_stream.reward_per_token_paid,
+                )?;
+                accrued = accrued
+                    .checked_add(boost_accrued)
+                    .ok_or(StakingError::ArithmeticOverflow)?;
+            }
+            let earned = math::earned_from_accrued(
+                accrued,
+                staking_pool_state.precision,
+                user_stream.rewards,
+            )?;
+            let stream = &mut reward_streams[index];
+            stream.rewards_accrued = stream
+                .rewards_accrued
+                .checked_sub(accrued)
+                .ok_or(StakingError::ArithmeticOverflow)?;
+            stream.rewards_unclaimed = (earned - user_stream.rewards)
+                .checked_add(stream.rewards_unclaimed)
+                .ok_or(StakingError::ArithmeticOverflow)?;
+            user_stream.rewards = earned;
+            user_stream.reward_per_token_paid = reward_per_token_stored;
+        }
+        staking_pool_state.reward_streams = reward_streams;
+        user_state.reward_streams = user_reward_streams;
+        Ok(())
+    }
+
+    /// Releases the positions whose lock expired by `current_timestamp` and drops their boost
+    /// from the user and from the pool lock expiry holding it. Call it only after settling the
+    /// user's rewards.
+    fn release_expired_positions(
+        staking_pool_state: &mut StakingPoolState,
+        user_state: &mut UserState,
+        current_timestamp: UnixTimestamp,
+    ) -> ProgramResult {
+        let (expired, locked): (Vec<LockedPosition>, Vec<LockedPosition>) = user_state
+            .active_positions()
+            .iter()
+            .partition(|position| position.lock_until <= current_timestamp);
+        for position in expired.iter().filter(|position| position.boost() != 0) {
+            let index = Self::lock_expiry_index(staking_pool_state, position.lock_until)?;
+            if position.lock_until > staking_pool_state.last_update_timestamp {
+                // The pool has not dropped this boost at expiry yet
+                staking_pool_state.total_effective_supply = staking_pool_state
+                    .total_effective_supply
+                    .checked_sub(position.boost())
+                    .ok_or(StakingError::ArithmeticOverflow)?;
+            }
+            let expiry = &mut staking_pool_state.lock_expiries[index];
+            expiry.boost = expiry
+                .boost
+                .checked_sub(position.boost())
+                .ok_or(StakingError::ArithmeticOverflow)?;
+            user_state.effective_balance = user_state
+                .effective_balance
+                .checked_sub(position.boost())
+                .ok_or(StakingError::ArithmeticOverflow)?;
+        }
+        let pending: Vec<LockExpiry> = staking_pool_state
+            .active_lock_expiries()
+            .iter()
+            .filter(|expiry| expiry.boost != 0)
+            .copied()
+            .collect();
+        staking_pool_state.lock_expiries = Default::default();
+        staking_pool_state.lock_expiries[..pending.len()].copy_from_slice(&pending);
+        staking_pool_state.lock_expiry_count = pending.len() as u8;
+        user_state.positions = Default::default();
+        user_state.positions[..locked.len()].copy_from_slice(&locked);
+        user_state.position_count = locked.len() as u8;
+        Ok(())
+    }
+
+    /// Adds a boost of `boost` expiring at `lock_until` to the pool schedule and returns the
+    /// expiry it joined. When every slot is taken it joins the earliest pending expiry after
+    /// `lock_until` that is still no later than `latest_lock_until`.
+    fn schedule_lock_expiry(
+        staking_pool_state: &mut StakingPoolState,
+        lock_until: UnixTimestamp,
+        latest_lock_until: UnixTimestamp,
+        boost: u64,
+    ) -> Result<UnixTimestamp, ProgramError> {
+        let count = staking_pool_state.lock_expiry_count as usize;
+        let index = match staking_pool_state.find_lock_expiry(lock_until) {
+            Some(index) => index,
+            None if count < MAX_LOCK_EXPIRIES => {
+                let index = staking_pool_state
+                    .active_lock_expiries()
+                    .iter()
+                    .position(|expiry| expiry.lock_until > lock_until)
+                    .unwrap_or(count);
+                staking_pool_state
+                    .lock_expiries
+                    .copy_within(index..count, index + 1);
+                staking_pool_state.lock_expiries[index] = LockExpiry {
+                    lock_until,
+                    ..Default::default()
+                };
+                staking_pool_state.lock_expiry_count += 1;
+                index
+            }
+            None => staking_pool_state
+                .active_lock_expiries()
+                .iter()
+                .position(|expiry| {
+                    expiry.lock_until > lock_until && expiry.lock_until <= latest_lock_until
+                })
+                .ok_or(StakingError::LockScheduleFull)?,
+        };
+        let expiry = &mut staking_pool_state.lock_expiries[index];
+        expiry.boost = expiry
+            .boost
+            .checked_add(boost)
+            .ok_or(StakingError::ArithmeticOverflow)?;
+        Ok(expiry.lock_until)
+    }
+
+    fn lock_expiry_index(
+        staking_pool_state: &StakingPoolState,
+        lock_until: UnixTimestamp,
+    ) -> Result<usize, ProgramError> {
+        staking_pool_state
+            .find_lock_expiry(lock_until)
+            .ok_or_else(|| {
+                msg!("No lock expiry at {}", lock_until);
+                ProgramError::InvalidAccountData
+            })
+    }
+
+    fn rewards_owed(
+        staking_pool_state: &StakingPoolState,
+        index: usize,
+    ) -> Result<u64, ProgramError> {
+        let stream = &staking_pool_state.reward_streams[index];
+        Ok(math::rewards_owed(
+            stream.rewards_unclaimed,
+            stream.rewards_accrued,
+            staking_pool_state.precision,
+        )?)
+    }
+
+    fn reward_per_token(
+        staking_pool_state: &StakingPoolState,
+        index: usize,
+        current_timestamp: UnixTimestamp,
+    ) -> Result<u128, ProgramError> {
+        let stream = &staking_pool_state.reward_streams[index];
+        Ok(math::reward_per_token(
+            stream.reward_per_token_stored,
+            stream.reward_rate,
+            Self::reward_elapsed(staking_pool_state, stream, current_timestamp),
+            staking_pool_state.precision,
+            staking_pool_state.total_effective_supply,
+        )?)
+    }
+
+    /// Seconds since the last update during which `stream` emitted rewards.
+    fn reward_elapsed(
+        staking_pool_state: &StakingPoolState,
+        stream: &RewardStream,
+        current_timestamp: UnixTimestamp,
+    ) -> u64 {
+        if staking_pool_state.accrual_paused {
+            return 0;
+        }
+        // Rewards are only emitted until the end of the funded period
+        let last_time_reward_applicable = current_timestamp.min(stream.period_finish);
+        last_time_reward_applicable
+            .saturating_sub(staking_pool_state.last_update_timestamp)
+            .max(0) as u64
+    }
+
+    /// Seconds left in the funded period of `stream` times its rate.
+    fn rewards_remaining(
+        stream: &RewardStream,
+        current_timestamp: UnixTimestamp,
+    ) -> Result<u64, ProgramError> {
+        if current_timestamp >= stream.period_finish {
+            return Ok(0);
+        }
+        Ok(((stream.period_finish - current_timestamp) as u64)
+            .checked_mul(stream.reward_rate)
+            .ok_or(StakingError::ArithmeticOverflow)?)
     }
 }
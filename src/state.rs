@@ -1,46 +1,242 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::clock::UnixTimestamp;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program_error::ProgramError;
 use solana_program::pubkey::Pubkey;
 
-#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug)]
+/// Maximum number of reward tokens a pool can pay out at the same time.
+pub const MAX_REWARD_STREAMS: usize = 4;
+
+/// Maximum number of unstake requests a user can have cooling down at the same time.
+pub const MAX_UNBONDING_REQUESTS: usize = 8;
+
+/// Maximum number of locked positions a user can hold at the same time.
+pub const MAX_LOCKED_POSITIONS: usize = 8;
+
+/// Maximum number of distinct lock expiries a pool tracks at the same time.
+pub const MAX_LOCK_EXPIRIES: usize = 16;
+
+/// Lock multipliers are in basis points, so this is an unboosted stake.
+pub const LOCK_MULTIPLIER_BASE: u64 = 10_000;
+
+/// Early unstake penalties are in basis points of the unstaked amount.
+pub const MAX_UNSTAKE_PENALTY_BPS: u64 = 10_000;
+
+/// Upper bound of the fee taken from claimed rewards, in basis points.
+pub const MAX_FEE_BPS: u64 = 1_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct RewardStream {
+    pub mint: Pubkey,                  // 32 bytes
+    pub reward_per_token_stored: u128, // 16 bytes
+    pub reward_rate: u64,              // 8 bytes
+    pub period_finish: UnixTimestamp,  // 8 bytes
+    pub rewards_unclaimed: u64,        // 8 bytes
+    pub rewards_accrued: u128,         // 16 bytes
+}
+
+impl RewardStream {
+    pub const LEN: usize = 32 + 16 + 8 * 3 + 16;
+}
+
+/// Boost of every locked position expiring at `lock_until`. The pool drops it from the total
+/// effective supply at that moment and keeps the reward per token of each stream there until
+/// every position is released.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct LockExpiry {
+    pub lock_until: UnixTimestamp, // 8 bytes
+    pub boost: u64,                // 8 bytes
+    /// Indexed like `StakingPoolState::reward_streams`
+    pub reward_per_token: [u128; MAX_REWARD_STREAMS],
+}
+
+impl LockExpiry {
+    pub const LEN: usize = 8 * 2 + 16 * MAX_REWARD_STREAMS;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, PartialEq, Debug)]
 pub struct StakingPoolState {
+    pub is_initialized: bool,                 // 1 byte
     pub admin: Pubkey,                        // 32 bytes
     pub staking_token_mint: Pubkey,           // 32 bytes
-    pub reward_token_mint: Pubkey,            // 32 bytes
+    pub pool_id: u64,                         // 8 bytes
     pub total_supply: u64,                    // 8 bytes
-    pub reward_per_token_stored: u64,         // 8 bytes
+    pub total_effective_supply: u64,          // 8 bytes
     pub last_update_timestamp: UnixTimestamp, // 8 bytes
+    pub precision: u64,                       // 8 bytes
+    pub max_lock_duration: u64,               // 8 bytes
+    pub max_lock_multiplier: u64,             // 8 bytes
+    pub unbonding_period: u64,                // 8 bytes
+    pub total_unbonding: u64,                 // 8 bytes
+    pub user_state_count: u64,                // 8 bytes
+    pub unstake_penalty_bps: u64,             // 8 bytes
+    pub penalty_decay_period: u64,            // 8 bytes
+    pub penalty_treasury: Pubkey,             // 32 bytes
+    pub fee_bps: u64,                         // 8 bytes
+    pub fee_recipient: Pubkey,                // 32 bytes
+    pub receipt_mint: Pubkey,                 // 32 bytes
+    pub pending_admin: Pubkey,                // 32 bytes
+    pub staking_paused: bool,                 // 1 byte
+    pub claiming_paused: bool,                // 1 byte
+    pub accrual_paused: bool,                 // 1 byte
+    pub reward_stream_count: u8,              // 1 byte
+    pub reward_streams: [RewardStream; MAX_REWARD_STREAMS],
+    pub lock_expiry_count: u8, // 1 byte
+    /// Pending and passed lock expiries, earliest first
+    pub lock_expiries: [LockExpiry; MAX_LOCK_EXPIRIES],
 }
 
 impl StakingPoolState {
-    pub const LEN: usize = 32 * 3 + 8 * 3;
+    pub const LEN: usize = 1
+        + 32 * 6
+        + 8 * 13
+        + 4
+        + RewardStream::LEN * MAX_REWARD_STREAMS
+        + 1
+        + LockExpiry::LEN * MAX_LOCK_EXPIRIES;
 
-    pub fn unpack(data: &mut [u8]) -> Self {
-        StakingPoolState::try_from_slice(data).unwrap()
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        let staking_pool_state = StakingPoolState::try_from_slice(data)?;
+        if !staking_pool_state.is_initialized {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Ok(staking_pool_state)
     }
 
-    pub fn pack(&self, data: &mut [u8]) {
-        let encoded = self.try_to_vec().unwrap();
+    pub fn pack(&self, data: &mut [u8]) -> ProgramResult {
+        let encoded = self.try_to_vec()?;
         data[..encoded.len()].copy_from_slice(&encoded);
+        Ok(())
+    }
+
+    /// Streams added so far. The first one pays the rewards mint the pool was created with.
+    pub fn active_reward_streams(&self) -> &[RewardStream] {
+        &self.reward_streams[..self.reward_stream_count as usize]
+    }
+
+    pub fn active_reward_streams_mut(&mut self) -> &mut [RewardStream] {
+        &mut self.reward_streams[..self.reward_stream_count as usize]
+    }
+
+    pub fn active_lock_expiries(&self) -> &[LockExpiry] {
+        &self.lock_expiries[..self.lock_expiry_count as usize]
+    }
+
+    /// Index of the lock expiry at `lock_until`, if any.
+    pub fn find_lock_expiry(&self, lock_until: UnixTimestamp) -> Option<usize> {
+        self.active_lock_expiries()
+            .iter()
+            .position(|expiry| expiry.lock_until == lock_until)
+    }
+
+    /// Index of the stream paying `mint`, if any.
+    pub fn find_reward_stream(&self, mint: &Pubkey) -> Option<usize> {
+        self.active_reward_streams()
+            .iter()
+            .position(|stream| stream.mint == *mint)
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct UserRewardStream {
+    pub reward_per_token_paid: u128, // 16 bytes
+    pub rewards: u64,                // 8 bytes
+}
+
+/// Stake locked until `lock_until`, earning on its boosted `effective_balance` until then.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct LockedPosition {
+    pub amount: u64,               // 8 bytes
+    pub effective_balance: u64,    // 8 bytes
+    pub lock_until: UnixTimestamp, // 8 bytes
+}
+
+impl LockedPosition {
+    /// Part of the effective balance above the locked amount.
+    pub fn boost(&self) -> u64 {
+        self.effective_balance.saturating_sub(self.amount)
     }
 }
 
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug)]
 pub struct UserState {
-    pub balance: u64,               // 8 bytes
-    pub reward_per_token_paid: u64, // 8 bytes
-    pub rewards: u64,               // 8 bytes
+    pub balance: u64,                        // 8 bytes
+    pub effective_balance: u64,              // 8 bytes
+    pub last_stake_timestamp: UnixTimestamp, // 8 bytes
+    pub receipt_token_account: Pubkey,       // 32 bytes
+    /// Indexed like `StakingPoolState::reward_streams`
+    pub reward_streams: [UserRewardStream; MAX_REWARD_STREAMS],
+    pub position_count: u8, // 1 byte
+    pub positions: [LockedPosition; MAX_LOCKED_POSITIONS],
 }
 
 impl UserState {
-    pub const LEN: usize = 8 * 3;
+    pub const LEN: usize =
+        8 * 3 + 32 + (16 + 8) * MAX_REWARD_STREAMS + 1 + (8 * 3) * MAX_LOCKED_POSITIONS;
+
+    pub fn active_positions(&self) -> &[LockedPosition] {
+        &self.positions[..self.position_count as usize]
+    }
 
-    pub fn unpack(data: &mut [u8]) -> Self {
-        UserState::try_from_slice(data).unwrap()
+    /// Staked amount held by positions that were not released yet.
+    pub fn locked_balance(&self) -> u64 {
+        self.active_positions()
+            .iter()
+            .map(|position| position.amount)
+            .sum()
     }
 
-    pub fn pack(&self, data: &mut [u8]) {
-        let encoded = self.try_to_vec().unwrap();
+    /// Part of the balance that can be unstaked right away.
+    pub fn unlocked_balance(&self) -> u64 {
+        self.balance.saturating_sub(self.locked_balance())
+    }
+
+    /// Settled rewards not yet claimed, summed over all streams.
+    pub fn unclaimed_rewards(&self) -> u128 {
+        self.reward_streams
+            .iter()
+            .map(|stream| stream.rewards as u128)
+            .sum()
+    }
+
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        Ok(UserState::try_from_slice(data)?)
+    }
+
+    pub fn pack(&self, data: &mut [u8]) -> ProgramResult {
+        let encoded = self.try_to_vec()?;
         data[..encoded.len()].copy_from_slice(&encoded);
+        Ok(())
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct UnbondingRequest {
+    pub amount: u64,                     // 8 bytes
+    pub unlock_timestamp: UnixTimestamp, // 8 bytes
+}
+
+/// Unstaked tokens of a user waiting for the pool cooldown, oldest first.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug)]
+pub struct WithdrawalQueue {
+    pub request_count: u8, // 1 byte
+    pub requests: [UnbondingRequest; MAX_UNBONDING_REQUESTS],
+}
+
+impl WithdrawalQueue {
+    pub const LEN: usize = 1 + (8 + 8) * MAX_UNBONDING_REQUESTS;
+
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        Ok(WithdrawalQueue::try_from_slice(data)?)
+    }
+
+    pub fn pack(&self, data: &mut [u8]) -> ProgramResult {
+        let encoded = self.try_to_vec()?;
+        data[..encoded.len()].copy_from_slice(&encoded);
+        Ok(())
+    }
+
+    pub fn active_requests(&self) -> &[UnbondingRequest] {
+        &self.requests[..self.request_count as usize]
     }
 }
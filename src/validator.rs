@@ -0,0 +1,331 @@
+use crate::error::StakingError;
+use crate::pda_helper::PdaHelper;
+use crate::state::StakingPoolState;
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    pubkey::Pubkey, system_program,
+};
+use spl_token_2022::{extension::StateWithExtensions, state::Account};
+
+pub struct Validator;
+
+impl Validator {
+    pub fn check_signer(account: &AccountInfo) -> ProgramResult {
+        if !account.is_signer {
+            msg!("Account {} must be a signer", account.key);
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(())
+    }
+
+    /// Accepts SPL Token and Token-2022 as long as the token program owns `mint`.
+    pub fn check_token_program(token_program: &AccountInfo, mint: &AccountInfo) -> ProgramResult {
+        if !Self::is_token_program(token_program.key) || mint.owner != token_program.key {
+            msg!(
+                "Token program passed: {}, mint {} owner: {}",
+                token_program.key,
+                mint.key,
+                mint.owner
+            );
+            return Err(StakingError::InvalidTokenProgram.into());
+        }
+        Ok(())
+    }
+
+    pub fn check_system_program(system_program: &AccountInfo) -> ProgramResult {
+        if *system_program.key != system_program::id() {
+            msg!("System program passed: {}", system_program.key);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Ok(())
+    }
+
+    pub fn check_staking_pool(
+        program_id: &Pubkey,
+        staking_pool_pda: &AccountInfo,
+    ) -> Result<StakingPoolState, ProgramError> {
+        if staking_pool_pda.owner != program_id {
+            msg!(
+                "Staking pool pda {} is not owned by the program",
+                staking_pool_pda.key
+            );
+            return Err(StakingError::InvalidAccountOwner.into());
+        }
+        StakingPoolState::unpack(&staking_pool_pda.try_borrow_data()?)
+    }
+
+    pub fn check_admin(
+        staking_pool_state: &StakingPoolState,
+        admin: &AccountInfo,
+    ) -> ProgramResult {
+        Self::check_signer(admin)?;
+        if staking_pool_state.admin != *admin.key {
+            msg!(
+                "Only admin can perform this operation. Admin: {}, signer: {}",
+                staking_pool_state.admin,
+                admin.key
+            );
+            return Err(StakingError::Unauthorized.into());
+        }
+        Ok(())
+    }
+
+    /// Returns the bump seed of the user state pda. The account may still be empty.
+    pub fn check_user_state_pda(
+        program_id: &Pubkey,
+        staking_pool_pda: &AccountInfo,
+        user_authority: &AccountInfo,
+        user_state_pda: &AccountInfo,
+    ) -> Result<u8, ProgramError> {
+        Self::check_holder_state_pda(
+            program_id,
+            staking_pool_pda,
+            user_authority.key,
+            user_state_pda,
+        )
+    }
+
+    /// Like `check_user_state_pda` for a user who does not sign, such as a receipt token holder.
+    pub fn check_holder_state_pda(
+        program_id: &Pubkey,
+        staking_pool_pda: &AccountInfo,
+        holder: &Pubkey,
+        user_state_pda: &AccountInfo,
+    ) -> Result<u8, ProgramError> {
+        let (expected_user_state_pda, bump_seed) =
+            PdaHelper::find_user_state_pda(staking_pool_pda.key, holder, program_id);
+        if *user_state_pda.key != expected_user_state_pda {
+            msg!("User state pda passed: {}", user_state_pda.key);
+            msg!("User state pda computed: {}", expected_user_state_pda);
+            return Err(StakingError::InvalidUserStatePda.into());
+        }
+        if !user_state_pda.try_data_is_empty()? && user_state_pda.owner != program_id {
+            msg!(
+                "User state pda {} is not owned by the program",
+                user_state_pda.key
+            );
+            return Err(StakingError::InvalidAccountOwner.into());
+        }
+        Ok(bump_seed)
+    }
+
+    pub fn check_withdrawal_queue_pda(
+        program_id: &Pubkey,
+        staking_pool_pda: &AccountInfo,
+        user_authority: &AccountInfo,
+        withdrawal_queue_pda: &AccountInfo,
+    ) -> Result<u8, ProgramError> {
+        let (expected_withdrawal_queue_pda, bump_seed) = PdaHelper::find_withdrawal_queue_pda(
+            staking_pool_pda.key,
+            user_authority.key,
+            program_id,
+        );
+        if *withdrawal_queue_pda.key != expected_withdrawal_queue_pda {
+            msg!("Withdrawal queue pda passed: {}", withdrawal_queue_pda.key);
+            msg!(
+                "Withdrawal queue pda computed: {}",
+                expected_withdrawal_queue_pda
+            );
+            return Err(StakingError::InvalidWithdrawalQueuePda.into());
+        }
+        if !withdrawal_queue_pda.try_data_is_empty()? && withdrawal_queue_pda.owner != program_id {
+            msg!(
+                "Withdrawal queue pda {} is not owned by the program",
+                withdrawal_queue_pda.key
+            );
+            return Err(StakingError::InvalidAccountOwner.into());
+        }
+        Ok(bump_seed)
+    }
+
+    pub fn check_staking_token_mint(
+        staking_pool_state: &StakingPoolState,
+        staking_token_mint_account: &AccountInfo,
+    ) -> ProgramResult {
+        if staking_pool_state.staking_token_mint != *staking_token_mint_account.key {
+            msg!(
+                "Staking token mint passed: {}, expected: {}",
+                staking_token_mint_account.key,
+                staking_pool_state.staking_token_mint
+            );
+            return Err(StakingError::InvalidStakingTokenMint.into());
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the reward stream paying the passed mint.
+    pub fn check_rewards_token_mint(
+        staking_pool_state: &StakingPoolState,
+        rewards_token_mint_account: &AccountInfo,
+    ) -> Result<usize, ProgramError> {
+        staking_pool_state
+            .find_reward_stream(rewards_token_mint_account.key)
+            .ok_or_else(|| {
+                msg!(
+                    "Rewards token mint {} is not paid by the staking pool",
+                    rewards_token_mint_account.key
+                );
+                StakingError::InvalidRewardsTokenMint.into()
+            })
+    }
+
+    /// Checks `penalty_treasury` is a token account of the staking token mint.
+    pub fn check_penalty_treasury(
+        penalty_treasury: &AccountInfo,
+        staking_token_mint_account: &AccountInfo,
+    ) -> ProgramResult {
+        let is_staking_token_account = penalty_treasury.owner == staking_token_mint_account.owner
+            && StateWithExtensions::<Account>::unpack(&penalty_treasury.try_borrow_data()?)
+                .map(|account| account.base.mint == *staking_token_mint_account.key)
+                .unwrap_or(false);
+        if !is_staking_token_account {
+            msg!("Penalty treasury passed: {}", penalty_treasury.key);
+            return Err(StakingError::InvalidPenaltyTreasury.into());
+        }
+        Ok(())
+    }
+
+    /// Checks `fee_token_account` is a token account of the pool fee recipient for `mint`.
+    pub fn check_fee_token_account(
+        staking_pool_state: &StakingPoolState,
+        fee_token_account: &AccountInfo,
+        mint: &AccountInfo,
+    ) -> ProgramResult {
+        let is_fee_token_account = fee_token_account.owner == mint.owner
+            && StateWithExtensions::<Account>::unpack(&fee_token_account.try_borrow_data()?)
+                .map(|account| {
+                    account.base.owner == staking_pool_state.fee_recipient
+                        && account.base.mint == *mint.key
+                })
+                .unwrap_or(false);
+        if !is_fee_token_account {
+            msg!("Fee token account passed: {}", fee_token_account.key);
+            return Err(StakingError::InvalidFeeAccount.into());
+        }
+        Ok(())
+    }
+
+    /// Returns the bump seed of the receipt mint pda of a pool that has one.
+    pub fn check_receipt_mint(
+        program_id: &Pubkey,
+        staking_pool_state: &StakingPoolState,
+        staking_pool_pda: &AccountInfo,
+        receipt_mint: &AccountInfo,
+    ) -> Result<u8, ProgramError> {
+        let (expected_receipt_mint, bump_seed) =
+            PdaHelper::find_receipt_mint_pda(staking_pool_pda.key, program_id);
+        if *receipt_mint.key != expected_receipt_mint
+            || staking_pool_state.receipt_mint != expected_receipt_mint
+        {
+            msg!(
+                "Receipt mint passed: {}, pool receipt mint: {}",
+                receipt_mint.key,
+                staking_pool_state.receipt_mint
+            );
+            return Err(StakingError::InvalidReceiptMint.into());
+        }
+        Ok(bump_seed)
+    }
+
+    /// Returns the holder of `receipt_token_account`, a token account of the pool receipt mint.
+    pub fn check_receipt_token_account(
+        staking_pool_state: &StakingPoolState,
+        receipt_token_account: &AccountInfo,
+    ) -> Result<Pubkey, ProgramError> {
+        let holder = if Self::is_token_program(receipt_token_account.owner) {
+            StateWithExtensions::<Account>::unpack(&receipt_token_account.try_borrow_data()?)
+                .ok()
+                .filter(|account| account.base.mint == staking_pool_state.receipt_mint)
+                .map(|account| account.base.owner)
+        } else {
+            None
+        };
+        holder.ok_or_else(|| {
+            msg!(
+                "Receipt token account passed: {}",
+                receipt_token_account.key
+            );
+            StakingError::InvalidReceiptAccount.into()
+        })
+    }
+
+    /// Checks `receipt_token_account` is a receipt token account held by `holder`.
+    pub fn check_holder_receipt_token_account(
+        staking_pool_state: &StakingPoolState,
+        receipt_token_account: &AccountInfo,
+        holder: &Pubkey,
+    ) -> ProgramResult {
+        let account_holder =
+            Self::check_receipt_token_account(staking_pool_state, receipt_token_account)?;
+        if account_holder != *holder {
+            msg!("Receipt token account holder: {}", account_holder);
+            return Err(StakingError::InvalidReceiptAccount.into());
+        }
+        Ok(())
+    }
+
+    /// Returns the bump seed of the staking token escrow pda.
+    pub fn check_staking_token_escrow(
+        program_id: &Pubkey,
+        staking_pool_pda: &AccountInfo,
+        staking_token_mint_account: &AccountInfo,
+        staking_token_escrow_pda: &AccountInfo,
+    ) -> Result<u8, ProgramError> {
+        let (expected_escrow_pda, bump_seed) =
+            PdaHelper::find_staking_token_pda(staking_pool_pda.key, program_id);
+        if !Self::is_escrow(
+            staking_token_escrow_pda,
+            &expected_escrow_pda,
+            staking_token_mint_account.key,
+        )? {
+            msg!(
+                "Staking token escrow passed: {}, expected: {}",
+                staking_token_escrow_pda.key,
+                expected_escrow_pda
+            );
+            return Err(StakingError::InvalidStakingTokenEscrow.into());
+        }
+        Ok(bump_seed)
+    }
+
+    /// Returns the bump seed of the escrow pda of the reward stream paying `rewards_token_mint`.
+    pub fn check_rewards_token_escrow(
+        program_id: &Pubkey,
+        staking_pool_pda: &AccountInfo,
+        rewards_token_mint: &Pubkey,
+        rewards_token_escrow_pda: &AccountInfo,
+    ) -> Result<u8, ProgramError> {
+        let (expected_escrow_pda, bump_seed) =
+            PdaHelper::find_rewards_token_pda(staking_pool_pda.key, rewards_token_mint, program_id);
+        if !Self::is_escrow(
+            rewards_token_escrow_pda,
+            &expected_escrow_pda,
+            rewards_token_mint,
+        )? {
+            msg!(
+                "Rewards token escrow passed: {}, expected: {}",
+                rewards_token_escrow_pda.key,
+                expected_escrow_pda
+            );
+            return Err(StakingError::InvalidRewardsTokenEscrow.into());
+        }
+        Ok(bump_seed)
+    }
+
+    fn is_escrow(
+        escrow: &AccountInfo,
+        expected_escrow_pda: &Pubkey,
+        mint: &Pubkey,
+    ) -> Result<bool, ProgramError> {
+        if escrow.key != expected_escrow_pda || !Self::is_token_program(escrow.owner) {
+            return Ok(false);
+        }
+        let escrow_data = escrow.try_borrow_data()?;
+        let escrow_account = StateWithExtensions::<Account>::unpack(&escrow_data)?.base;
+        Ok(escrow_account.owner == *expected_escrow_pda && escrow_account.mint == *mint)
+    }
+
+    fn is_token_program(program_id: &Pubkey) -> bool {
+        *program_id == spl_token::id() || *program_id == spl_token_2022::id()
+    }
+}
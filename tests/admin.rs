@@ -0,0 +1,111 @@
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::signer::Signer;
+use staking::{error::StakingError, instruction};
+
+mod common;
+
+use common::*;
+
+fn propose_admin_ix(f: &PoolFixture, admin: &Pubkey, new_admin: &Pubkey) -> Instruction {
+    instruction::propose_admin(
+        &f.program_id,
+        admin,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        new_admin,
+    )
+}
+
+fn accept_admin_ix(f: &PoolFixture, pending_admin: &Pubkey) -> Instruction {
+    instruction::accept_admin(
+        &f.program_id,
+        pending_admin,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+    )
+}
+
+#[tokio::test]
+async fn test_propose_and_accept_admin() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let bob = f.bob.insecure_clone();
+
+    let ix = propose_admin_ix(&f, &auth.pubkey(), &bob.pubkey());
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let state = pool_state(&mut f).await;
+    assert_eq!(state.admin, auth.pubkey(), "Admin changes only on accept");
+    assert_eq!(state.pending_admin, bob.pubkey());
+
+    let ix = accept_admin_ix(&f, &bob.pubkey());
+    assert_eq!(process_ix(&mut f.ctx, ix, &bob).await, None);
+    let state = pool_state(&mut f).await;
+    assert_eq!(state.admin, bob.pubkey());
+    assert_eq!(state.pending_admin, Pubkey::default());
+
+    let set_reward_rate_ix = instruction::set_reward_rate(
+        &f.program_id,
+        &auth.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &f.rewards_token_mint_account.pubkey(),
+        1,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, set_reward_rate_ix, &auth).await,
+        custom_error(StakingError::Unauthorized),
+        "Previous admin must lose its rights"
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_admin_proposal() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let bob = f.bob.insecure_clone();
+
+    let ix = propose_admin_ix(&f, &auth.pubkey(), &bob.pubkey());
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = propose_admin_ix(&f, &auth.pubkey(), &Pubkey::default());
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    assert_eq!(pool_state(&mut f).await.pending_admin, Pubkey::default());
+
+    let ix = accept_admin_ix(&f, &bob.pubkey());
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &bob).await,
+        custom_error(StakingError::Unauthorized),
+        "Cancelled proposal cannot be accepted"
+    );
+    assert_eq!(pool_state(&mut f).await.admin, auth.pubkey());
+}
+
+#[tokio::test]
+async fn test_unauthorized_admin_transfer() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let bob = f.bob.insecure_clone();
+
+    let ix = propose_admin_ix(&f, &alice.pubkey(), &alice.pubkey());
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::Unauthorized),
+        "Only admin can propose a new admin"
+    );
+
+    let ix = propose_admin_ix(&f, &auth.pubkey(), &bob.pubkey());
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = accept_admin_ix(&f, &alice.pubkey());
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::Unauthorized),
+        "Only the pending admin can accept"
+    );
+    let state = pool_state(&mut f).await;
+    assert_eq!(state.admin, auth.pubkey());
+    assert_eq!(state.pending_admin, bob.pubkey());
+}
@@ -0,0 +1,274 @@
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::Transaction};
+use staking::{error::StakingError, instruction, pda_helper::PdaHelper};
+
+mod common;
+
+use common::*;
+
+fn close_user_state_ix(f: &PoolFixture) -> Instruction {
+    instruction::close_user_state(
+        &f.program_id,
+        &f.alice.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+    )
+}
+
+async fn user_state_exists(f: &mut PoolFixture) -> bool {
+    let (user_state_pda, _) =
+        PdaHelper::find_user_state_pda(&f.staking_pool_pda, &f.alice.pubkey(), &f.program_id);
+    f.ctx
+        .banks_client
+        .get_account(user_state_pda)
+        .await
+        .unwrap()
+        .is_some()
+}
+
+#[tokio::test]
+async fn test_unstake_closes_user_state() {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+
+    let ix = close_user_state_ix(&f);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::UserStateNotEmpty)
+    );
+
+    let lamports_before = f
+        .ctx
+        .banks_client
+        .get_balance(alice.pubkey())
+        .await
+        .unwrap();
+    let ix = alice_unstake_ix(&f, 100, true);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    assert!(!user_state_exists(&mut f).await);
+    let lamports_after = f
+        .ctx
+        .banks_client
+        .get_balance(alice.pubkey())
+        .await
+        .unwrap();
+    assert!(
+        lamports_after > lamports_before,
+        "Rent must be refunded to the user"
+    );
+
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        None,
+        "Closed user state must be recreated on the next stake"
+    );
+    assert!(user_state_exists(&mut f).await);
+}
+
+#[tokio::test]
+async fn test_close_user_state_requires_claimed_rewards() {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 1000).await;
+    notify_rewards(&mut f, &auth_rewards_token_account, 1000, 100).await;
+    warp_seconds(&mut f.ctx, 10).await;
+
+    let ix = alice_unstake_ix(&f, 100, true);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::UserStateNotEmpty),
+        "Unclaimed rewards must keep the user state open"
+    );
+    let ix = alice_unstake_ix(&f, 100, false);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let ix = close_user_state_ix(&f);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::UserStateNotEmpty)
+    );
+
+    let ix = alice_get_rewards_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let ix = close_user_state_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    assert!(!user_state_exists(&mut f).await);
+}
+
+#[tokio::test]
+async fn test_claim_and_unstake_closes_user_state_with_rewards() {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 1000).await;
+    notify_rewards(&mut f, &auth_rewards_token_account, 1000, 100).await;
+    warp_seconds(&mut f.ctx, 50).await;
+
+    let ix = alice_unstake_ix(&f, 50, true);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::UserStateNotEmpty),
+        "Closing must unstake the whole balance"
+    );
+    let blockhash = f.ctx.get_new_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[alice_get_rewards_ix(&f), alice_unstake_ix(&f, 100, true)],
+        Some(&alice.pubkey()),
+        &[&alice],
+        blockhash,
+    );
+    f.ctx.banks_client.process_transaction(tx).await.unwrap();
+    assert!(!user_state_exists(&mut f).await);
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        500
+    );
+}
+
+async fn create_admin_staking_token_account(f: &mut PoolFixture) -> Pubkey {
+    let auth = f.auth.insecure_clone();
+    let token_account = Keypair::new();
+    create_and_initialize_account_for_mint(
+        &mut f.ctx.banks_client.clone(),
+        f.ctx.last_blockhash,
+        &spl_token::id(),
+        &token_account,
+        &f.staking_token_mint_account,
+        &auth,
+        &auth,
+    )
+    .await
+    .unwrap();
+    token_account.pubkey()
+}
+
+fn close_pool_ix(
+    f: &PoolFixture,
+    admin: &Pubkey,
+    admin_staking_token_account: &Pubkey,
+    admin_rewards_token_account: &Pubkey,
+) -> Instruction {
+    instruction::close_pool(
+        &f.program_id,
+        admin,
+        admin_staking_token_account,
+        admin_rewards_token_account,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        &f.rewards_token_program,
+        f.pool_id,
+        &[],
+        false,
+    )
+}
+
+#[tokio::test]
+async fn test_close_pool() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 1000).await;
+    notify_rewards(&mut f, &auth_rewards_token_account, 1000, 100).await;
+    let auth_staking_token_account = create_admin_staking_token_account(&mut f).await;
+    // Tokens sent to the escrow outside the program must not block closing
+    let donation_ix = spl_token::instruction::transfer(
+        &spl_token::id(),
+        &f.alice_staking_token_account.pubkey(),
+        &f.staking_token_escrow_pda,
+        &alice.pubkey(),
+        &[],
+        7,
+    )
+    .unwrap();
+    assert_eq!(process_ix(&mut f.ctx, donation_ix, &alice).await, None);
+    warp_seconds(&mut f.ctx, 110).await;
+
+    let ix = close_pool_ix(
+        &f,
+        &auth.pubkey(),
+        &auth_staking_token_account,
+        &auth_rewards_token_account,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, ix.clone(), &auth).await,
+        custom_error(StakingError::PoolNotEmpty)
+    );
+    let unstake_ix = alice_unstake_ix(&f, 100, false);
+    assert_eq!(process_ix(&mut f.ctx, unstake_ix, &alice).await, None);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix.clone(), &auth).await,
+        custom_error(StakingError::RewardsOutstanding)
+    );
+    let get_rewards_ix = alice_get_rewards_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, get_rewards_ix, &alice).await, None);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix.clone(), &auth).await,
+        custom_error(StakingError::PoolNotEmpty),
+        "User states must be closed before the pool"
+    );
+    let alice_lamports_before = f
+        .ctx
+        .banks_client
+        .get_balance(alice.pubkey())
+        .await
+        .unwrap();
+    let close_user_state_ix = close_user_state_ix(&f);
+    assert_eq!(
+        process_ix(&mut f.ctx, close_user_state_ix, &auth).await,
+        None,
+        "Anyone can close an emptied user state"
+    );
+    let alice_lamports_after = f
+        .ctx
+        .banks_client
+        .get_balance(alice.pubkey())
+        .await
+        .unwrap();
+    assert!(
+        alice_lamports_after > alice_lamports_before,
+        "Rent must be refunded to the user"
+    );
+
+    let lamports_before = f.ctx.banks_client.get_balance(auth.pubkey()).await.unwrap();
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let lamports_after = f.ctx.banks_client.get_balance(auth.pubkey()).await.unwrap();
+    assert!(
+        lamports_after > lamports_before,
+        "Rent must be refunded to the admin"
+    );
+    assert_eq!(token_balance(&mut f, &auth_staking_token_account).await, 7);
+    for account in [
+        f.staking_pool_pda,
+        f.staking_token_escrow_pda,
+        f.rewards_token_escrow_pda,
+    ] {
+        assert_eq!(f.ctx.banks_client.get_account(account).await.unwrap(), None);
+    }
+}
+
+#[tokio::test]
+async fn test_close_pool_requires_admin() {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    let ix = close_pool_ix(
+        &f,
+        &alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &f.alice_rewards_token_account.pubkey(),
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::Unauthorized)
+    );
+}
@@ -0,0 +1,738 @@
+#![allow(dead_code)]
+
+use borsh::BorshDeserialize;
+use solana_program::{
+    clock::Clock,
+    hash::Hash,
+    instruction::{Instruction, InstructionError},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    program_pack::Pack,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+};
+use spl_token_2022::{
+    extension::{
+        transfer_fee::instruction::initialize_transfer_fee_config, BaseStateWithExtensions,
+        ExtensionType, StateWithExtensions,
+    },
+    state::{Account, Mint},
+};
+use staking::{
+    entrypoint::process_instruction,
+    error::StakingError,
+    instruction,
+    pda_helper::PdaHelper,
+    state::{StakingPoolState, UserState},
+};
+use std::str::FromStr;
+
+pub async fn create_and_initialize_account_for_mint(
+    banks_client: &mut BanksClient,
+    recent_blockhash: Hash,
+    token_program: &Pubkey,
+    token_account: &Keypair,
+    mint: &Keypair,
+    payer: &Keypair,
+    owner: &Keypair,
+) -> Result<(), ProgramError> {
+    let rent = banks_client.get_rent().await.unwrap();
+    let mint_account = banks_client
+        .get_account(mint.pubkey())
+        .await
+        .unwrap()
+        .expect("Could not fetch mint account");
+    let mint_extensions = StateWithExtensions::<Mint>::unpack(&mint_account.data)
+        .unwrap()
+        .get_extension_types()
+        .unwrap();
+    let account_len = ExtensionType::try_calculate_account_len::<Account>(
+        &ExtensionType::get_required_init_account_extensions(&mint_extensions),
+    )
+    .unwrap();
+    let account_rent = rent.minimum_balance(account_len);
+    let create_account_ix = solana_program::system_instruction::create_account(
+        &payer.pubkey(),
+        &token_account.pubkey(),
+        account_rent,
+        account_len as u64,
+        token_program,
+    );
+    let initialize_account_ix = spl_token_2022::instruction::initialize_account(
+        token_program,
+        &token_account.pubkey(),
+        &mint.pubkey(),
+        &owner.pubkey(),
+    )
+    .unwrap();
+    let initialize_account_tx = Transaction::new_signed_with_payer(
+        &[create_account_ix, initialize_account_ix],
+        Some(&payer.pubkey()),
+        &[payer, token_account],
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(initialize_account_tx)
+        .await
+        .unwrap();
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn mint_amount(
+    banks_client: &mut BanksClient,
+    recent_blockhash: Hash,
+    token_program: &Pubkey,
+    account: &Pubkey,
+    mint: &Pubkey,
+    mint_authority: &Keypair,
+    payer: &Keypair,
+    amount: f64,
+    mint_decimals: u8,
+) -> Result<(), ProgramError> {
+    let mint_amount = (amount * f64::powf(10., mint_decimals.into())) as u64;
+    let mint_ix = spl_token_2022::instruction::mint_to(
+        token_program,
+        mint,
+        account,
+        &mint_authority.pubkey(),
+        &[],
+        mint_amount,
+    )
+    .unwrap();
+    let mint_tx = Transaction::new_signed_with_payer(
+        &[mint_ix],
+        Some(&payer.pubkey()),
+        &[payer, mint_authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(mint_tx).await.unwrap();
+    Ok(())
+}
+
+pub async fn get_user_staking_state(banks_client: &mut BanksClient, user_pda: &Pubkey) {
+    let user_staking_state_info = banks_client
+        .get_account(*user_pda)
+        .await
+        .unwrap()
+        .expect("Could not fetch account information");
+    let user_staking_state =
+        UserState::try_from_slice(user_staking_state_info.data.as_slice()).unwrap();
+    println!();
+    println!("------------------ USER STAKING STATE ------------------");
+    println!("balance: {}", user_staking_state.balance);
+    println!(
+        "effective_balance: {}",
+        user_staking_state.effective_balance
+    );
+    println!(
+        "last_stake_timestamp: {}",
+        user_staking_state.last_stake_timestamp
+    );
+    println!(
+        "receipt_token_account: {}",
+        user_staking_state.receipt_token_account
+    );
+    for (index, stream) in user_staking_state.reward_streams.iter().enumerate() {
+        println!(
+            "stream {}: reward_per_token_paid: {} rewards: {}",
+            index, stream.reward_per_token_paid, stream.rewards
+        );
+    }
+    for position in user_staking_state.active_positions() {
+        println!(
+            "position: amount: {} effective_balance: {} lock_until: {}",
+            position.amount, position.effective_balance, position.lock_until
+        );
+    }
+    println!("--------------------------------------------------------");
+    println!();
+}
+
+pub async fn get_staking_state(banks_client: &mut BanksClient, staking_pool_pda: &Pubkey) {
+    let staking_state_info = banks_client
+        .get_account(*staking_pool_pda)
+        .await
+        .unwrap()
+        .expect("Could not fetch account information");
+    let staking_state =
+        StakingPoolState::try_from_slice(staking_state_info.data.as_slice()).unwrap();
+    println!();
+    println!("------------------ STAKING STATE ------------------");
+    println!("admin: {}", staking_state.admin);
+    println!("staking_token_mint: {}", staking_state.staking_token_mint);
+    println!("pool_id: {}", staking_state.pool_id);
+    println!("total_supply: {}", staking_state.total_supply);
+    println!(
+        "total_effective_supply: {}",
+        staking_state.total_effective_supply
+    );
+    println!(
+        "last_update_timestamp: {}",
+        staking_state.last_update_timestamp
+    );
+    println!("precision: {}", staking_state.precision);
+    println!("max_lock_duration: {}", staking_state.max_lock_duration);
+    println!("max_lock_multiplier: {}", staking_state.max_lock_multiplier);
+    println!("unbonding_period: {}", staking_state.unbonding_period);
+    println!("total_unbonding: {}", staking_state.total_unbonding);
+    println!("user_state_count: {}", staking_state.user_state_count);
+    println!("unstake_penalty_bps: {}", staking_state.unstake_penalty_bps);
+    println!(
+        "penalty_decay_period: {}",
+        staking_state.penalty_decay_period
+    );
+    println!("penalty_treasury: {}", staking_state.penalty_treasury);
+    println!("fee_bps: {}", staking_state.fee_bps);
+    println!("fee_recipient: {}", staking_state.fee_recipient);
+    println!("receipt_mint: {}", staking_state.receipt_mint);
+    println!("pending_admin: {}", staking_state.pending_admin);
+    for stream in staking_state.active_reward_streams() {
+        println!("reward stream mint: {}", stream.mint);
+        println!(
+            "  reward_per_token_stored: {}",
+            stream.reward_per_token_stored
+        );
+        println!("  reward_rate: {}", stream.reward_rate);
+        println!("  period_finish: {}", stream.period_finish);
+        println!("  rewards_unclaimed: {}", stream.rewards_unclaimed);
+        println!("  rewards_accrued: {}", stream.rewards_accrued);
+    }
+    println!("--------------------------------------------------------");
+    println!();
+}
+
+pub async fn create_and_initialize_mint(
+    banks_client: &mut BanksClient,
+    recent_blockhash: Hash,
+    payer: &Keypair,
+    mint_authority: &Keypair,
+    mint_account: &Keypair,
+    token_program: &Pubkey,
+    decimals: &u8,
+) -> Result<(), TransportError> {
+    let rent = banks_client.get_rent().await.unwrap();
+    let mint_rent = rent.minimum_balance(Mint::LEN);
+    let token_mint_a_account_ix = solana_program::system_instruction::create_account(
+        &payer.pubkey(),
+        &mint_account.pubkey(),
+        mint_rent,
+        Mint::LEN as u64,
+        token_program,
+    );
+    let token_mint_a_ix = spl_token_2022::instruction::initialize_mint(
+        token_program,
+        &mint_account.pubkey(),
+        &mint_authority.pubkey(),
+        None,
+        *decimals,
+    )
+    .unwrap();
+    let token_mint_a_tx = Transaction::new_signed_with_payer(
+        &[token_mint_a_account_ix, token_mint_a_ix],
+        Some(&payer.pubkey()),
+        &[payer, mint_account],
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(token_mint_a_tx)
+        .await
+        .unwrap();
+    Ok(())
+}
+
+/// Creates a Token-2022 mint charging `transfer_fee_bps` on every transfer, capped at `u64::MAX`.
+pub async fn create_and_initialize_transfer_fee_mint(
+    banks_client: &mut BanksClient,
+    recent_blockhash: Hash,
+    payer: &Keypair,
+    mint_authority: &Keypair,
+    mint_account: &Keypair,
+    transfer_fee_bps: u16,
+    decimals: u8,
+) -> Result<(), TransportError> {
+    let token_program = &spl_token_2022::id();
+    let mint_len =
+        ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferFeeConfig])
+            .unwrap();
+    let rent = banks_client.get_rent().await.unwrap();
+    let create_account_ix = solana_program::system_instruction::create_account(
+        &payer.pubkey(),
+        &mint_account.pubkey(),
+        rent.minimum_balance(mint_len),
+        mint_len as u64,
+        token_program,
+    );
+    let transfer_fee_ix = initialize_transfer_fee_config(
+        token_program,
+        &mint_account.pubkey(),
+        Some(&mint_authority.pubkey()),
+        Some(&mint_authority.pubkey()),
+        transfer_fee_bps,
+        u64::MAX,
+    )
+    .unwrap();
+    let initialize_mint_ix = spl_token_2022::instruction::initialize_mint(
+        token_program,
+        &mint_account.pubkey(),
+        &mint_authority.pubkey(),
+        None,
+        decimals,
+    )
+    .unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[create_account_ix, transfer_fee_ix, initialize_mint_ix],
+        Some(&payer.pubkey()),
+        &[payer, mint_account],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(tx).await.unwrap();
+    Ok(())
+}
+
+pub async fn warp_seconds(ctx: &mut ProgramTestContext, seconds: i64) {
+    let clock = ctx.banks_client.get_sysvar::<Clock>().await.unwrap();
+    ctx.warp_to_slot(clock.slot + 1).unwrap();
+    let mut warped_clock = ctx.banks_client.get_sysvar::<Clock>().await.unwrap();
+    warped_clock.unix_timestamp = clock.unix_timestamp + seconds;
+    ctx.set_sysvar(&warped_clock);
+    ctx.get_new_latest_blockhash().await.unwrap();
+}
+
+pub async fn token_balance(f: &mut PoolFixture, account: &Pubkey) -> u64 {
+    let account = f
+        .ctx
+        .banks_client
+        .get_account(*account)
+        .await
+        .unwrap()
+        .unwrap();
+    StateWithExtensions::<Account>::unpack(&account.data)
+        .unwrap()
+        .base
+        .amount
+}
+
+pub async fn pool_state(f: &mut PoolFixture) -> StakingPoolState {
+    let account = f
+        .ctx
+        .banks_client
+        .get_account(f.staking_pool_pda)
+        .await
+        .unwrap()
+        .unwrap();
+    StakingPoolState::unpack(&account.data).unwrap()
+}
+
+pub struct PoolFixture {
+    pub ctx: ProgramTestContext,
+    pub program_id: Pubkey,
+    pub auth: Keypair,
+    pub alice: Keypair,
+    pub bob: Keypair,
+    pub staking_token_mint_account: Keypair,
+    pub rewards_token_mint_account: Keypair,
+    pub staking_token_program: Pubkey,
+    pub rewards_token_program: Pubkey,
+    pub pool_id: u64,
+    pub staking_pool_pda: Pubkey,
+    pub staking_token_escrow_pda: Pubkey,
+    pub rewards_token_escrow_pda: Pubkey,
+    pub alice_staking_token_account: Keypair,
+    pub alice_rewards_token_account: Keypair,
+}
+
+pub const POOL_ID: u64 = 0;
+
+/// How `setup_pool_with_mints` creates a mint.
+#[derive(Clone, Copy)]
+pub enum MintKind {
+    SplToken,
+    /// Token-2022 mint with a transfer fee in basis points
+    TransferFee(u16),
+}
+
+impl MintKind {
+    pub fn token_program(&self) -> Pubkey {
+        match self {
+            MintKind::SplToken => spl_token::id(),
+            MintKind::TransferFee(_) => spl_token_2022::id(),
+        }
+    }
+}
+
+pub fn program_id() -> Pubkey {
+    Pubkey::from_str("3emgBhpukxUExLJ1AnMa5NzDHJYZLNNWqtTccHT4mk2j").unwrap()
+}
+
+pub fn staking_program_test(program_id: Pubkey) -> ProgramTest {
+    ProgramTest::new("staking", program_id, processor!(process_instruction))
+}
+
+/// Starts the program, creates both mints, initializes a pool and funds alice with staking tokens.
+pub async fn setup_pool(program_id: Pubkey) -> PoolFixture {
+    setup_pool_with(staking_program_test(program_id), program_id).await
+}
+
+pub async fn setup_pool_with(program_test: ProgramTest, program_id: Pubkey) -> PoolFixture {
+    setup_pool_with_mints(
+        program_test,
+        program_id,
+        MintKind::SplToken,
+        MintKind::SplToken,
+    )
+    .await
+}
+
+pub async fn setup_pool_with_mints(
+    program_test: ProgramTest,
+    program_id: Pubkey,
+    staking_mint_kind: MintKind,
+    rewards_mint_kind: MintKind,
+) -> PoolFixture {
+    let mut ctx = program_test.start_with_context().await;
+    let mut banks_client = ctx.banks_client.clone();
+    let recent_blockhash = ctx.last_blockhash;
+    let staking_token_program = staking_mint_kind.token_program();
+    let rewards_token_program = rewards_mint_kind.token_program();
+
+    let auth = Keypair::new();
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    banks_client
+        .process_transaction(Transaction::new_signed_with_payer(
+            &[
+                system_instruction::transfer(&ctx.payer.pubkey(), &auth.pubkey(), 1_000_000_000),
+                system_instruction::transfer(&ctx.payer.pubkey(), &alice.pubkey(), 1_000_000_000),
+                system_instruction::transfer(&ctx.payer.pubkey(), &bob.pubkey(), 1_000_000_000),
+            ],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            recent_blockhash,
+        ))
+        .await
+        .unwrap();
+
+    let staking_token_mint_account = Keypair::new();
+    let rewards_token_mint_account = Keypair::new();
+    let mint_decimals = 9;
+    for (mint, kind) in [
+        (&staking_token_mint_account, staking_mint_kind),
+        (&rewards_token_mint_account, rewards_mint_kind),
+    ] {
+        match kind {
+            MintKind::SplToken => create_and_initialize_mint(
+                &mut banks_client,
+                recent_blockhash,
+                &auth,
+                &auth,
+                mint,
+                &spl_token::id(),
+                &mint_decimals,
+            )
+            .await
+            .unwrap(),
+            MintKind::TransferFee(transfer_fee_bps) => create_and_initialize_transfer_fee_mint(
+                &mut banks_client,
+                recent_blockhash,
+                &auth,
+                &auth,
+                mint,
+                transfer_fee_bps,
+                mint_decimals,
+            )
+            .await
+            .unwrap(),
+        }
+    }
+
+    let alice_staking_token_account = Keypair::new();
+    let alice_rewards_token_account = Keypair::new();
+    for (account, mint, token_program) in [
+        (
+            &alice_staking_token_account,
+            &staking_token_mint_account,
+            &staking_token_program,
+        ),
+        (
+            &alice_rewards_token_account,
+            &rewards_token_mint_account,
+            &rewards_token_program,
+        ),
+    ] {
+        create_and_initialize_account_for_mint(
+            &mut banks_client,
+            recent_blockhash,
+            token_program,
+            account,
+            mint,
+            &auth,
+            &alice,
+        )
+        .await
+        .unwrap();
+    }
+    mint_amount(
+        &mut banks_client,
+        recent_blockhash,
+        &staking_token_program,
+        &alice_staking_token_account.pubkey(),
+        &staking_token_mint_account.pubkey(),
+        &auth,
+        &auth,
+        1000.0,
+        mint_decimals,
+    )
+    .await
+    .unwrap();
+
+    let (staking_pool_pda, _) = PdaHelper::find_staking_pool_pda(
+        &staking_token_mint_account.pubkey(),
+        &rewards_token_mint_account.pubkey(),
+        POOL_ID,
+        &program_id,
+    );
+    let (staking_token_escrow_pda, _) =
+        PdaHelper::find_staking_token_pda(&staking_pool_pda, &program_id);
+    let (rewards_token_escrow_pda, _) = PdaHelper::find_rewards_token_pda(
+        &staking_pool_pda,
+        &rewards_token_mint_account.pubkey(),
+        &program_id,
+    );
+    let init_ix = instruction::init(
+        &program_id,
+        &auth.pubkey(),
+        &staking_token_mint_account.pubkey(),
+        &rewards_token_mint_account.pubkey(),
+        &staking_token_program,
+        &rewards_token_program,
+        POOL_ID,
+        100,
+        10_000_000_000,
+        None,
+    );
+    banks_client
+        .process_transaction(Transaction::new_signed_with_payer(
+            &[init_ix],
+            Some(&auth.pubkey()),
+            &[&auth],
+            recent_blockhash,
+        ))
+        .await
+        .unwrap();
+    ctx.get_new_latest_blockhash().await.unwrap();
+
+    PoolFixture {
+        ctx,
+        program_id,
+        auth,
+        alice,
+        bob,
+        staking_token_mint_account,
+        rewards_token_mint_account,
+        staking_token_program,
+        rewards_token_program,
+        pool_id: POOL_ID,
+        staking_pool_pda,
+        staking_token_escrow_pda,
+        rewards_token_escrow_pda,
+        alice_staking_token_account,
+        alice_rewards_token_account,
+    }
+}
+
+/// Sends `ix` signed by `signer` and returns the transaction error, if any.
+pub async fn process_ix(
+    ctx: &mut ProgramTestContext,
+    ix: Instruction,
+    signer: &Keypair,
+) -> Option<TransactionError> {
+    let blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    let tx =
+        Transaction::new_signed_with_payer(&[ix], Some(&signer.pubkey()), &[signer], blockhash);
+    ctx.banks_client
+        .process_transaction(tx)
+        .await
+        .err()
+        .map(|err| err.unwrap())
+}
+
+pub fn custom_error(error: StakingError) -> Option<TransactionError> {
+    Some(TransactionError::InstructionError(
+        0,
+        InstructionError::Custom(error as u32),
+    ))
+}
+
+/// Creates an admin rewards token account holding `amount` base units.
+pub async fn fund_admin_rewards(f: &mut PoolFixture, amount: u64) -> Pubkey {
+    let auth = f.auth.insecure_clone();
+    let auth_rewards_token_account = Keypair::new();
+    let mut banks_client = f.ctx.banks_client.clone();
+    create_and_initialize_account_for_mint(
+        &mut banks_client,
+        f.ctx.last_blockhash,
+        &f.rewards_token_program,
+        &auth_rewards_token_account,
+        &f.rewards_token_mint_account,
+        &auth,
+        &auth,
+    )
+    .await
+    .unwrap();
+    mint_amount(
+        &mut banks_client,
+        f.ctx.last_blockhash,
+        &f.rewards_token_program,
+        &auth_rewards_token_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &auth,
+        &auth,
+        amount as f64,
+        0,
+    )
+    .await
+    .unwrap();
+    auth_rewards_token_account.pubkey()
+}
+
+pub async fn notify_rewards(
+    f: &mut PoolFixture,
+    admin_rewards_token_account: &Pubkey,
+    amount: u64,
+    duration: u64,
+) {
+    let auth = f.auth.insecure_clone();
+    let ix = instruction::notify_reward_amount(
+        &f.program_id,
+        &auth.pubkey(),
+        admin_rewards_token_account,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &f.rewards_token_mint_account.pubkey(),
+        &f.rewards_token_program,
+        amount,
+        duration,
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+}
+
+pub fn alice_stake_ix(f: &PoolFixture, amount: u64) -> Instruction {
+    instruction::stake(
+        &f.program_id,
+        &f.alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+        amount,
+        0,
+        None,
+    )
+}
+
+pub fn alice_unstake_ix(f: &PoolFixture, amount: u64, close: bool) -> Instruction {
+    instruction::unstake(
+        &f.program_id,
+        &f.alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+        amount,
+        close,
+        None,
+        None,
+    )
+}
+
+pub fn alice_emergency_withdraw_ix(f: &PoolFixture) -> Instruction {
+    instruction::emergency_withdraw(
+        &f.program_id,
+        &f.alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+        None,
+        None,
+    )
+}
+
+pub fn alice_get_rewards_ix(f: &PoolFixture) -> Instruction {
+    instruction::get_rewards(
+        &f.program_id,
+        &f.alice.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &(
+            f.alice_rewards_token_account.pubkey(),
+            f.rewards_token_mint_account.pubkey(),
+            f.rewards_token_program,
+        ),
+        &[],
+        &[],
+        None,
+    )
+}
+
+/// Creates reward and staking token accounts for bob, funded with `amount` staking tokens.
+pub async fn fund_bob(f: &mut PoolFixture, amount: u64) -> (Pubkey, Pubkey) {
+    let auth = f.auth.insecure_clone();
+    let bob = f.bob.insecure_clone();
+    let mut banks_client = f.ctx.banks_client.clone();
+    let bob_staking_token_account = Keypair::new();
+    let bob_rewards_token_account = Keypair::new();
+    for (account, mint, token_program) in [
+        (
+            &bob_staking_token_account,
+            &f.staking_token_mint_account,
+            f.staking_token_program,
+        ),
+        (
+            &bob_rewards_token_account,
+            &f.rewards_token_mint_account,
+            f.rewards_token_program,
+        ),
+    ] {
+        create_and_initialize_account_for_mint(
+            &mut banks_client,
+            f.ctx.last_blockhash,
+            &token_program,
+            account,
+            mint,
+            &auth,
+            &bob,
+        )
+        .await
+        .unwrap();
+    }
+    mint_amount(
+        &mut banks_client,
+        f.ctx.last_blockhash,
+        &f.staking_token_program,
+        &bob_staking_token_account.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &auth,
+        &auth,
+        amount as f64,
+        0,
+    )
+    .await
+    .unwrap();
+    (
+        bob_staking_token_account.pubkey(),
+        bob_rewards_token_account.pubkey(),
+    )
+}
@@ -0,0 +1,195 @@
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use staking::{error::StakingError, instruction, pda_helper::PdaHelper, state::UserState};
+
+mod common;
+
+use common::*;
+
+const SAME_MINT_POOL_ID: u64 = 1;
+
+/// Creates a second pool paying rewards in the staking token, where alice stakes 100 tokens and
+/// 1000 rewards are emitted over 100 seconds. Returns the emptied admin staking token account.
+async fn setup_same_mint_pool(f: &mut PoolFixture) -> Pubkey {
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let staking_mint = f.staking_token_mint_account.pubkey();
+    let init_ix = instruction::init(
+        &f.program_id,
+        &auth.pubkey(),
+        &staking_mint,
+        &staking_mint,
+        &f.staking_token_program,
+        &f.staking_token_program,
+        SAME_MINT_POOL_ID,
+        0,
+        10_000_000_000,
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, init_ix, &auth).await, None);
+    let stake_ix = instruction::stake(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &staking_mint,
+        &staking_mint,
+        &f.staking_token_program,
+        SAME_MINT_POOL_ID,
+        100,
+        0,
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, stake_ix, &alice).await, None);
+
+    let auth_staking_token_account = Keypair::new();
+    let mut banks_client = f.ctx.banks_client.clone();
+    create_and_initialize_account_for_mint(
+        &mut banks_client,
+        f.ctx.last_blockhash,
+        &f.staking_token_program,
+        &auth_staking_token_account,
+        &f.staking_token_mint_account,
+        &auth,
+        &auth,
+    )
+    .await
+    .unwrap();
+    mint_amount(
+        &mut banks_client,
+        f.ctx.last_blockhash,
+        &f.staking_token_program,
+        &auth_staking_token_account.pubkey(),
+        &staking_mint,
+        &auth,
+        &auth,
+        1000.0,
+        0,
+    )
+    .await
+    .unwrap();
+    let notify_ix = instruction::notify_reward_amount(
+        &f.program_id,
+        &auth.pubkey(),
+        &auth_staking_token_account.pubkey(),
+        &staking_mint,
+        &staking_mint,
+        SAME_MINT_POOL_ID,
+        &staking_mint,
+        &f.staking_token_program,
+        1000,
+        100,
+    );
+    assert_eq!(process_ix(&mut f.ctx, notify_ix, &auth).await, None);
+    auth_staking_token_account.pubkey()
+}
+
+#[tokio::test]
+async fn test_compound_stakes_rewards() {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    setup_same_mint_pool(&mut f).await;
+    let staking_mint = f.staking_token_mint_account.pubkey();
+    warp_seconds(&mut f.ctx, 50).await;
+
+    let compound_ix = instruction::compound(
+        &f.program_id,
+        &alice.pubkey(),
+        &staking_mint,
+        &staking_mint,
+        &f.staking_token_program,
+        SAME_MINT_POOL_ID,
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, compound_ix, &alice).await, None);
+
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        &staking_mint,
+        &staking_mint,
+        SAME_MINT_POOL_ID,
+        &f.program_id,
+    );
+    let (user_state_pda, _) =
+        PdaHelper::find_user_state_pda(&staking_pool, &alice.pubkey(), &f.program_id);
+    let user_state_account = f
+        .ctx
+        .banks_client
+        .get_account(user_state_pda)
+        .await
+        .unwrap()
+        .unwrap();
+    let user_state = UserState::unpack(&user_state_account.data).unwrap();
+    assert_eq!(user_state.balance, 600);
+    assert_eq!(user_state.reward_streams[0].rewards, 0);
+    let (staking_token_escrow, _) = PdaHelper::find_staking_token_pda(&staking_pool, &f.program_id);
+    let (rewards_token_escrow, _) =
+        PdaHelper::find_rewards_token_pda(&staking_pool, &staking_mint, &f.program_id);
+    assert_eq!(token_balance(&mut f, &staking_token_escrow).await, 600);
+    assert_eq!(token_balance(&mut f, &rewards_token_escrow).await, 500);
+}
+
+#[tokio::test]
+async fn test_compound_requires_staking_token_rewards() {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+
+    let compound_ix = instruction::compound(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+        None,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, compound_ix, &alice).await,
+        custom_error(StakingError::CompoundMintMismatch)
+    );
+}
+
+#[tokio::test]
+async fn test_compound_pays_reward_fee() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let auth_staking_token_account = setup_same_mint_pool(&mut f).await;
+    let staking_mint = f.staking_token_mint_account.pubkey();
+    let set_fee_ix = instruction::set_reward_fee(
+        &f.program_id,
+        &auth.pubkey(),
+        &staking_mint,
+        &staking_mint,
+        SAME_MINT_POOL_ID,
+        &auth.pubkey(),
+        1_000,
+    );
+    assert_eq!(process_ix(&mut f.ctx, set_fee_ix, &auth).await, None);
+    warp_seconds(&mut f.ctx, 50).await;
+
+    let compound_ix = instruction::compound(
+        &f.program_id,
+        &alice.pubkey(),
+        &staking_mint,
+        &staking_mint,
+        &f.staking_token_program,
+        SAME_MINT_POOL_ID,
+        Some(&auth_staking_token_account),
+    );
+    assert_eq!(process_ix(&mut f.ctx, compound_ix, &alice).await, None);
+    assert_eq!(token_balance(&mut f, &auth_staking_token_account).await, 50);
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        &staking_mint,
+        &staking_mint,
+        SAME_MINT_POOL_ID,
+        &f.program_id,
+    );
+    let (staking_token_escrow, _) = PdaHelper::find_staking_token_pda(&staking_pool, &f.program_id);
+    assert_eq!(
+        token_balance(&mut f, &staking_token_escrow).await,
+        550,
+        "Only the rewards left after the fee are staked"
+    );
+}
@@ -0,0 +1,243 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_program_test::{processor, tokio};
+use solana_sdk::{
+    signature::Keypair, signer::Signer, system_instruction, transaction::Transaction,
+};
+use spl_token::state::Account;
+use staking::{
+    cpi::{self, GetRewardsAccounts, StakeAccounts, UnstakeAccounts},
+    pda_helper::PdaHelper,
+    state::UserState,
+};
+
+mod common;
+
+use common::*;
+
+/// Minimal program holding tokens in a PDA and staking them through `staking::cpi`.
+///
+/// Data is a tag byte (0 stake, 1 unstake, 2 get rewards) followed by a little endian amount.
+fn process_vault(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let vault = next_account_info(accounts_iter)?;
+    let vault_token_account = next_account_info(accounts_iter)?;
+    let user_state = next_account_info(accounts_iter)?;
+    let staking_pool = next_account_info(accounts_iter)?;
+    let escrow = next_account_info(accounts_iter)?;
+    let mint = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let staking_program = next_account_info(accounts_iter)?;
+    let (_, bump) = Pubkey::find_program_address(&[b"vault"], program_id);
+    let signer_seeds: &[&[&[u8]]] = &[&[b"vault", &[bump]]];
+    let amount = data
+        .get(1..9)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .unwrap_or_default();
+    match data[0] {
+        0 => cpi::stake(
+            staking_program.key,
+            StakeAccounts {
+                user: vault.clone(),
+                user_staking_token_account: vault_token_account.clone(),
+                staking_token_escrow: escrow.clone(),
+                user_state: user_state.clone(),
+                staking_pool: staking_pool.clone(),
+                staking_token_mint: mint.clone(),
+                token_program: token_program.clone(),
+                system_program: system_program.clone(),
+                receipt_accounts: None,
+            },
+            amount,
+            0,
+            signer_seeds,
+        ),
+        1 => cpi::unstake(
+            staking_program.key,
+            UnstakeAccounts {
+                user: vault.clone(),
+                user_staking_token_account: vault_token_account.clone(),
+                user_state: user_state.clone(),
+                staking_pool: staking_pool.clone(),
+                staking_token_escrow: escrow.clone(),
+                staking_token_mint: mint.clone(),
+                token_program: token_program.clone(),
+                penalty_destination: None,
+                receipt_accounts: None,
+            },
+            amount,
+            false,
+            signer_seeds,
+        ),
+        _ => cpi::get_rewards(
+            staking_program.key,
+            GetRewardsAccounts {
+                user: vault.clone(),
+                user_rewards_token_account: vault_token_account.clone(),
+                user_state: user_state.clone(),
+                staking_pool: staking_pool.clone(),
+                rewards_token_escrow: escrow.clone(),
+                rewards_token_mint: mint.clone(),
+                token_program: token_program.clone(),
+                fee_token_account: None,
+                user_receipt_token_account: None,
+            },
+            signer_seeds,
+        ),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn vault_ix(
+    vault_program_id: &Pubkey,
+    f: &PoolFixture,
+    vault_token_account: &Pubkey,
+    escrow: &Pubkey,
+    mint: &Pubkey,
+    tag: u8,
+    amount: u64,
+) -> Instruction {
+    let (vault, _) = Pubkey::find_program_address(&[b"vault"], vault_program_id);
+    let (user_state, _) =
+        PdaHelper::find_user_state_pda(&f.staking_pool_pda, &vault, &f.program_id);
+    let mut data = vec![tag];
+    data.extend_from_slice(&amount.to_le_bytes());
+    Instruction::new_with_bytes(
+        *vault_program_id,
+        &data,
+        vec![
+            AccountMeta::new(vault, false),
+            AccountMeta::new(*vault_token_account, false),
+            AccountMeta::new(user_state, false),
+            AccountMeta::new(f.staking_pool_pda, false),
+            AccountMeta::new(*escrow, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(f.program_id, false),
+        ],
+    )
+}
+
+async fn create_vault_token_account(f: &mut PoolFixture, vault: &Pubkey, mint: &Pubkey) -> Pubkey {
+    let token_account = Keypair::new();
+    let rent = f.ctx.banks_client.get_rent().await.unwrap();
+    let auth = f.auth.insecure_clone();
+    let ixs = [
+        system_instruction::create_account(
+            &auth.pubkey(),
+            &token_account.pubkey(),
+            rent.minimum_balance(Account::LEN),
+            Account::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            &token_account.pubkey(),
+            mint,
+            vault,
+        )
+        .unwrap(),
+    ];
+    let blockhash = f.ctx.get_new_latest_blockhash().await.unwrap();
+    f.ctx
+        .banks_client
+        .process_transaction(Transaction::new_signed_with_payer(
+            &ixs,
+            Some(&auth.pubkey()),
+            &[&auth, &token_account],
+            blockhash,
+        ))
+        .await
+        .unwrap();
+    token_account.pubkey()
+}
+
+#[tokio::test]
+async fn test_vault_stakes_through_cpi() {
+    let program_id = program_id();
+    let vault_program_id = Pubkey::new_unique();
+    let mut program_test = staking_program_test(program_id);
+    program_test.add_program("vault", vault_program_id, processor!(process_vault));
+    let mut f = setup_pool_with(program_test, program_id).await;
+
+    let (vault, _) = Pubkey::find_program_address(&[b"vault"], &vault_program_id);
+    let auth = f.auth.insecure_clone();
+    let fund_vault_ix = system_instruction::transfer(&auth.pubkey(), &vault, 100_000_000);
+    assert_eq!(process_ix(&mut f.ctx, fund_vault_ix, &auth).await, None);
+
+    let staking_mint = f.staking_token_mint_account.pubkey();
+    let rewards_mint = f.rewards_token_mint_account.pubkey();
+    let vault_staking_account = create_vault_token_account(&mut f, &vault, &staking_mint).await;
+    let vault_rewards_account = create_vault_token_account(&mut f, &vault, &rewards_mint).await;
+    let blockhash = f.ctx.get_new_latest_blockhash().await.unwrap();
+    mint_amount(
+        &mut f.ctx.banks_client.clone(),
+        blockhash,
+        &spl_token::id(),
+        &vault_staking_account,
+        &staking_mint,
+        &auth,
+        &auth,
+        1.0,
+        0,
+    )
+    .await
+    .unwrap();
+
+    let stake_ix = vault_ix(
+        &vault_program_id,
+        &f,
+        &vault_staking_account,
+        &f.staking_token_escrow_pda,
+        &staking_mint,
+        0,
+        1,
+    );
+    assert_eq!(process_ix(&mut f.ctx, stake_ix, &auth).await, None);
+    assert_eq!(token_balance(&mut f, &vault_staking_account).await, 0);
+
+    let (user_state, _) = PdaHelper::find_user_state_pda(&f.staking_pool_pda, &vault, &program_id);
+    let user_state_account = f
+        .ctx
+        .banks_client
+        .get_account(user_state)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        UserState::unpack(&user_state_account.data).unwrap().balance,
+        1,
+        "Vault stake must be recorded under the vault pda"
+    );
+
+    let get_rewards_ix = vault_ix(
+        &vault_program_id,
+        &f,
+        &vault_rewards_account,
+        &f.rewards_token_escrow_pda,
+        &rewards_mint,
+        2,
+        0,
+    );
+    assert_eq!(process_ix(&mut f.ctx, get_rewards_ix, &auth).await, None);
+
+    let unstake_ix = vault_ix(
+        &vault_program_id,
+        &f,
+        &vault_staking_account,
+        &f.staking_token_escrow_pda,
+        &staking_mint,
+        1,
+        1,
+    );
+    assert_eq!(process_ix(&mut f.ctx, unstake_ix, &auth).await, None);
+    assert_eq!(token_balance(&mut f, &vault_staking_account).await, 1);
+}
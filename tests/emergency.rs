@@ -0,0 +1,89 @@
+use solana_program_test::tokio;
+use solana_sdk::signer::Signer;
+use staking::{error::StakingError, instruction, pda_helper::PdaHelper, state::UserState};
+
+mod common;
+
+use common::*;
+
+#[tokio::test]
+async fn test_emergency_withdraw_returns_principal() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    let initial_balance = token_balance(&mut f, &alice_staking_token_account).await;
+
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 1000).await;
+    notify_rewards(&mut f, &auth_rewards_token_account, 1000, 100).await;
+    warp_seconds(&mut f.ctx, 50).await;
+
+    let emergency_withdraw_ix = instruction::emergency_withdraw(
+        &f.program_id,
+        &alice.pubkey(),
+        &alice_staking_token_account,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+        None,
+        None,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, emergency_withdraw_ix.clone(), &alice).await,
+        None
+    );
+    assert_eq!(
+        token_balance(&mut f, &alice_staking_token_account).await,
+        initial_balance
+    );
+    assert_eq!(token_balance(&mut f, &alice_rewards_token_account).await, 0);
+
+    let (user_state_pda, _) =
+        PdaHelper::find_user_state_pda(&f.staking_pool_pda, &alice.pubkey(), &f.program_id);
+    let user_state_account = f
+        .ctx
+        .banks_client
+        .get_account(user_state_pda)
+        .await
+        .unwrap()
+        .unwrap();
+    let user_state = UserState::unpack(&user_state_account.data).unwrap();
+    assert_eq!(user_state.balance, 0);
+    assert_eq!(user_state.reward_streams[0].rewards, 0);
+    let state = pool_state(&mut f).await;
+    assert_eq!(state.total_supply, 0);
+    assert_eq!(
+        (
+            state.reward_streams[0].rewards_unclaimed,
+            state.reward_streams[0].rewards_accrued
+        ),
+        (0, 0),
+        "Forfeited rewards are not owed"
+    );
+
+    assert_eq!(
+        process_ix(&mut f.ctx, emergency_withdraw_ix, &alice).await,
+        custom_error(StakingError::ZeroAmount)
+    );
+
+    let withdraw_ix = instruction::withdraw_excess_rewards(
+        &f.program_id,
+        &auth.pubkey(),
+        &auth_rewards_token_account,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &f.rewards_token_mint_account.pubkey(),
+        &f.rewards_token_program,
+        500,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, withdraw_ix, &auth).await,
+        None,
+        "Forfeited rewards must be recoverable by the admin"
+    );
+}
@@ -0,0 +1,96 @@
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{instruction::InstructionError, signer::Signer, transaction::TransactionError};
+use staking::{error::StakingError, instruction};
+
+mod common;
+
+use common::*;
+
+fn set_reward_fee_ix(f: &PoolFixture, admin: &Pubkey, fee_bps: u64) -> Instruction {
+    instruction::set_reward_fee(
+        &f.program_id,
+        admin,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &f.auth.pubkey(),
+        fee_bps,
+    )
+}
+
+fn alice_get_rewards_with_fee_ix(f: &PoolFixture, fee_token_account: &Pubkey) -> Instruction {
+    instruction::get_rewards(
+        &f.program_id,
+        &f.alice.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &(
+            f.alice_rewards_token_account.pubkey(),
+            f.rewards_token_mint_account.pubkey(),
+            f.rewards_token_program,
+        ),
+        &[],
+        &[*fee_token_account],
+        None,
+    )
+}
+
+#[tokio::test]
+async fn test_get_rewards_pays_fee_to_recipient() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 1000).await;
+    notify_rewards(&mut f, &auth_rewards_token_account, 1000, 100).await;
+    warp_seconds(&mut f.ctx, 110).await;
+    let ix = set_reward_fee_ix(&f, &auth.pubkey(), 250);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+
+    let ix = alice_get_rewards_ix(&f);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        Some(TransactionError::InstructionError(
+            0,
+            InstructionError::NotEnoughAccountKeys
+        )),
+        "A fee account is required while the pool charges a fee"
+    );
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    let ix = alice_get_rewards_with_fee_ix(&f, &alice_rewards_token_account);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::InvalidFeeAccount)
+    );
+    let ix = alice_get_rewards_with_fee_ix(&f, &auth_rewards_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        975
+    );
+    assert_eq!(token_balance(&mut f, &auth_rewards_token_account).await, 25);
+}
+
+#[tokio::test]
+async fn test_reward_fee_validation() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let ix = set_reward_fee_ix(&f, &alice.pubkey(), 100);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::Unauthorized)
+    );
+    let ix = set_reward_fee_ix(&f, &auth.pubkey(), 1_001);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &auth).await,
+        custom_error(StakingError::RewardFeeTooHigh)
+    );
+    let ix = set_reward_fee_ix(&f, &auth.pubkey(), 1_000);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let state = pool_state(&mut f).await;
+    assert_eq!((state.fee_bps, state.fee_recipient), (1_000, auth.pubkey()));
+}
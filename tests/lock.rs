@@ -0,0 +1,401 @@
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::signer::Signer;
+use staking::{
+    error::StakingError,
+    instruction,
+    pda_helper::PdaHelper,
+    state::{LockedPosition, UserState},
+};
+
+mod common;
+
+use common::*;
+
+const MAX_LOCK_DURATION: u64 = 100;
+/// Stakes locked for `MAX_LOCK_DURATION` earn twice as much
+const MAX_LOCK_MULTIPLIER: u64 = 20_000;
+
+fn set_lock_multiplier_ix(
+    f: &PoolFixture,
+    admin: &Pubkey,
+    max_lock_duration: u64,
+    max_lock_multiplier: u64,
+) -> Instruction {
+    instruction::set_lock_multiplier(
+        &f.program_id,
+        admin,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        max_lock_duration,
+        max_lock_multiplier,
+    )
+}
+
+fn stake_ix(
+    f: &PoolFixture,
+    user: &Pubkey,
+    user_staking_token_account: &Pubkey,
+    amount: u64,
+    lock_duration: u64,
+) -> Instruction {
+    instruction::stake(
+        &f.program_id,
+        user,
+        user_staking_token_account,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+        amount,
+        lock_duration,
+        None,
+    )
+}
+
+async fn user_state(f: &mut PoolFixture, user: &Pubkey) -> UserState {
+    let (user_state_pda, _) =
+        PdaHelper::find_user_state_pda(&f.staking_pool_pda, user, &f.program_id);
+    let account = f
+        .ctx
+        .banks_client
+        .get_account(user_state_pda)
+        .await
+        .unwrap()
+        .unwrap();
+    UserState::unpack(&account.data).unwrap()
+}
+
+async fn now(f: &mut PoolFixture) -> i64 {
+    f.ctx
+        .banks_client
+        .get_sysvar::<solana_program::clock::Clock>()
+        .await
+        .unwrap()
+        .unix_timestamp
+}
+
+#[tokio::test]
+async fn test_locked_stake_earns_boosted_rewards() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let bob = f.bob.insecure_clone();
+    let (bob_staking_token_account, bob_rewards_token_account) = fund_bob(&mut f, 100).await;
+    let ix = set_lock_multiplier_ix(&f, &auth.pubkey(), MAX_LOCK_DURATION, MAX_LOCK_MULTIPLIER);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+    let ix = stake_ix(
+        &f,
+        &alice.pubkey(),
+        &alice_staking_token_account,
+        100,
+        MAX_LOCK_DURATION,
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let ix = stake_ix(&f, &bob.pubkey(), &bob_staking_token_account, 100, 0);
+    assert_eq!(process_ix(&mut f.ctx, ix, &bob).await, None);
+    let state = pool_state(&mut f).await;
+    assert_eq!(
+        (state.total_supply, state.total_effective_supply),
+        (200, 300)
+    );
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 3000).await;
+    notify_rewards(&mut f, &auth_rewards_token_account, 3000, 100).await;
+    warp_seconds(&mut f.ctx, 50).await;
+
+    let ix = alice_unstake_ix(&f, 1, false);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::StakeLocked)
+    );
+    let ix = alice_emergency_withdraw_ix(&f);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::StakeLocked)
+    );
+    let ix = alice_get_rewards_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        1000,
+        "The locked stake earns twice as much"
+    );
+    let bob_get_rewards_ix = instruction::get_rewards(
+        &f.program_id,
+        &bob.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &(
+            bob_rewards_token_account,
+            f.rewards_token_mint_account.pubkey(),
+            f.rewards_token_program,
+        ),
+        &[],
+        &[],
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, bob_get_rewards_ix, &bob).await, None);
+    assert_eq!(token_balance(&mut f, &bob_rewards_token_account).await, 500);
+
+    warp_seconds(&mut f.ctx, MAX_LOCK_DURATION as i64).await;
+    let ix = alice_unstake_ix(&f, 100, false);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        None,
+        "Expired locks can be unstaked"
+    );
+    let state = pool_state(&mut f).await;
+    assert_eq!(
+        (state.total_supply, state.total_effective_supply),
+        (100, 100)
+    );
+}
+
+#[tokio::test]
+async fn test_expired_lock_earns_unboosted_rewards() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let bob = f.bob.insecure_clone();
+    let (bob_staking_token_account, bob_rewards_token_account) = fund_bob(&mut f, 100).await;
+    let ix = set_lock_multiplier_ix(&f, &auth.pubkey(), MAX_LOCK_DURATION, MAX_LOCK_MULTIPLIER);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+    let ix = stake_ix(
+        &f,
+        &alice.pubkey(),
+        &alice_staking_token_account,
+        100,
+        MAX_LOCK_DURATION,
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let ix = stake_ix(&f, &bob.pubkey(), &bob_staking_token_account, 100, 0);
+    assert_eq!(process_ix(&mut f.ctx, ix, &bob).await, None);
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 3000).await;
+    notify_rewards(
+        &mut f,
+        &auth_rewards_token_account,
+        3000,
+        2 * MAX_LOCK_DURATION,
+    )
+    .await;
+    warp_seconds(&mut f.ctx, 2 * MAX_LOCK_DURATION as i64).await;
+
+    let ix = alice_get_rewards_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        1000 + 750,
+        "The boost stops earning when the lock expires"
+    );
+    let bob_get_rewards_ix = instruction::get_rewards(
+        &f.program_id,
+        &bob.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &(
+            bob_rewards_token_account,
+            f.rewards_token_mint_account.pubkey(),
+            f.rewards_token_program,
+        ),
+        &[],
+        &[],
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, bob_get_rewards_ix, &bob).await, None);
+    assert_eq!(
+        token_balance(&mut f, &bob_rewards_token_account).await,
+        500 + 750
+    );
+    let state = pool_state(&mut f).await;
+    assert_eq!(state.total_effective_supply, 200);
+}
+
+#[tokio::test]
+async fn test_lock_multiplier_validation() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+
+    let ix = stake_ix(&f, &alice.pubkey(), &alice_staking_token_account, 100, 1);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::LockTooLong),
+        "Locking is disabled until the admin sets a curve"
+    );
+    let ix = set_lock_multiplier_ix(&f, &alice.pubkey(), MAX_LOCK_DURATION, MAX_LOCK_MULTIPLIER);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::Unauthorized)
+    );
+    let ix = set_lock_multiplier_ix(&f, &auth.pubkey(), MAX_LOCK_DURATION, 9_999);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &auth).await,
+        custom_error(StakingError::InvalidLockMultiplier)
+    );
+    let ix = set_lock_multiplier_ix(&f, &auth.pubkey(), MAX_LOCK_DURATION, MAX_LOCK_MULTIPLIER);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = stake_ix(
+        &f,
+        &alice.pubkey(),
+        &alice_staking_token_account,
+        100,
+        MAX_LOCK_DURATION + 1,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::LockTooLong)
+    );
+}
+
+#[tokio::test]
+async fn test_top_up_keeps_existing_lock() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let ix = set_lock_multiplier_ix(&f, &auth.pubkey(), MAX_LOCK_DURATION, MAX_LOCK_MULTIPLIER);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+    let start = now(&mut f).await;
+    let ix = stake_ix(
+        &f,
+        &alice.pubkey(),
+        &alice_staking_token_account,
+        100,
+        MAX_LOCK_DURATION,
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    warp_seconds(&mut f.ctx, 50).await;
+
+    let ix = stake_ix(&f, &alice.pubkey(), &alice_staking_token_account, 100, 80);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let state = user_state(&mut f, &alice.pubkey()).await;
+    assert_eq!(
+        state.active_positions(),
+        &[
+            LockedPosition {
+                amount: 100,
+                effective_balance: 200,
+                lock_until: start + MAX_LOCK_DURATION as i64,
+            },
+            LockedPosition {
+                amount: 100,
+                effective_balance: 180,
+                lock_until: start + 130,
+            },
+        ],
+        "The top-up does not reprice the existing position"
+    );
+    assert_eq!(state.effective_balance, 380);
+    let pool = pool_state(&mut f).await;
+    assert_eq!(pool.total_effective_supply, 380);
+    assert_eq!(pool.active_lock_expiries().len(), 2);
+
+    warp_seconds(&mut f.ctx, 60).await;
+    let ix = alice_unstake_ix(&f, 101, false);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::StakeLocked),
+        "Only the first position has expired"
+    );
+    let ix = alice_unstake_ix(&f, 100, false);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let state = user_state(&mut f, &alice.pubkey()).await;
+    assert_eq!(state.active_positions().len(), 1);
+    assert_eq!((state.balance, state.effective_balance), (100, 180));
+    let pool = pool_state(&mut f).await;
+    assert_eq!(pool.total_effective_supply, 180);
+    assert_eq!(pool.active_lock_expiries().len(), 1);
+}
+
+#[tokio::test]
+async fn test_pool_drops_boost_at_expiry() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let bob = f.bob.insecure_clone();
+    let (bob_staking_token_account, bob_rewards_token_account) = fund_bob(&mut f, 100).await;
+    let ix = set_lock_multiplier_ix(&f, &auth.pubkey(), MAX_LOCK_DURATION, MAX_LOCK_MULTIPLIER);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+    let ix = stake_ix(
+        &f,
+        &alice.pubkey(),
+        &alice_staking_token_account,
+        100,
+        MAX_LOCK_DURATION,
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let ix = stake_ix(&f, &bob.pubkey(), &bob_staking_token_account, 100, 0);
+    assert_eq!(process_ix(&mut f.ctx, ix, &bob).await, None);
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 3000).await;
+    notify_rewards(
+        &mut f,
+        &auth_rewards_token_account,
+        3000,
+        2 * MAX_LOCK_DURATION,
+    )
+    .await;
+    warp_seconds(&mut f.ctx, 2 * MAX_LOCK_DURATION as i64).await;
+
+    let bob_get_rewards_ix = instruction::get_rewards(
+        &f.program_id,
+        &bob.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &(
+            bob_rewards_token_account,
+            f.rewards_token_mint_account.pubkey(),
+            f.rewards_token_program,
+        ),
+        &[],
+        &[],
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, bob_get_rewards_ix, &bob).await, None);
+    assert_eq!(
+        token_balance(&mut f, &bob_rewards_token_account).await,
+        500 + 750,
+        "The boost left the supply at expiry although alice never settled"
+    );
+    let pool = pool_state(&mut f).await;
+    assert_eq!(pool.total_effective_supply, 200);
+    assert_eq!(pool.active_lock_expiries().len(), 1);
+
+    let ix = instruction::release_expired_locks(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &bob).await,
+        None,
+        "Anyone can release expired locks"
+    );
+    let pool = pool_state(&mut f).await;
+    assert_eq!(pool.total_effective_supply, 200);
+    assert!(pool.active_lock_expiries().is_empty());
+    let state = user_state(&mut f, &alice.pubkey()).await;
+    assert!(state.active_positions().is_empty());
+    assert_eq!(state.effective_balance, 100);
+    assert_eq!(state.reward_streams[0].rewards, 1000 + 750);
+
+    let ix = alice_get_rewards_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        1000 + 750
+    );
+}
@@ -0,0 +1,161 @@
+use staking::{error::StakingError, math};
+
+const PRECISION: u64 = 10_000_000_000;
+
+#[test]
+fn test_reward_per_token_without_stakers() {
+    assert_eq!(
+        math::reward_per_token(42, u64::MAX, u64::MAX, PRECISION, 0),
+        Ok(42)
+    );
+}
+
+#[test]
+fn test_reward_per_token_with_9_decimal_mint() {
+    // 1000 tokens per second for 30 days, single lamport staked
+    let reward_rate = 1_000 * 1_000_000_000;
+    let elapsed = 30 * 24 * 60 * 60;
+    let reward_per_token = math::reward_per_token(0, reward_rate, elapsed, PRECISION, 1).unwrap();
+    assert_eq!(
+        reward_per_token,
+        reward_rate as u128 * elapsed as u128 * PRECISION as u128
+    );
+    assert_eq!(
+        math::earned(1, reward_per_token, 0, PRECISION, 0),
+        Ok(reward_rate * elapsed)
+    );
+}
+
+#[test]
+fn test_reward_per_token_max_values() {
+    assert_eq!(
+        math::reward_per_token(0, u64::MAX, u64::MAX, 1, u64::MAX),
+        Ok(u64::MAX as u128)
+    );
+    assert_eq!(
+        math::reward_per_token(0, u64::MAX, u64::MAX, u64::MAX, 1),
+        Err(StakingError::ArithmeticOverflow)
+    );
+    assert_eq!(
+        math::reward_per_token(u128::MAX, 0, u64::MAX, PRECISION, 1),
+        Ok(u128::MAX)
+    );
+    assert_eq!(
+        math::reward_per_token(u128::MAX, 1, 1, 1, 1),
+        Err(StakingError::ArithmeticOverflow)
+    );
+}
+
+#[test]
+fn test_earned_max_values() {
+    let precision = PRECISION as u128;
+    assert_eq!(
+        math::earned(u64::MAX, precision, 0, PRECISION, 0),
+        Ok(u64::MAX)
+    );
+    assert_eq!(
+        math::earned(u64::MAX, 2 * precision, 0, PRECISION, 0),
+        Err(StakingError::ArithmeticOverflow)
+    );
+    assert_eq!(
+        math::earned(1, precision, 0, PRECISION, u64::MAX),
+        Err(StakingError::ArithmeticOverflow)
+    );
+    assert_eq!(
+        math::earned(u64::MAX, u128::MAX, 0, PRECISION, 0),
+        Err(StakingError::ArithmeticOverflow)
+    );
+}
+
+#[test]
+fn test_earned_paid_above_reward_per_token() {
+    assert_eq!(
+        math::earned(1, 0, 1, PRECISION, 0),
+        Err(StakingError::ArithmeticOverflow)
+    );
+}
+
+#[test]
+fn test_rewards_owed_rounds_accrued_up() {
+    let precision = PRECISION as u128;
+    assert_eq!(math::rewards_owed(5, 0, PRECISION), Ok(5));
+    assert_eq!(math::rewards_owed(5, 1, PRECISION), Ok(6));
+    assert_eq!(math::rewards_owed(5, 2 * precision, PRECISION), Ok(7));
+    assert_eq!(
+        math::rewards_owed(u64::MAX, 1, PRECISION),
+        Err(StakingError::ArithmeticOverflow)
+    );
+    assert_eq!(
+        math::rewards_owed(0, 1, 0),
+        Err(StakingError::InvalidPrecision)
+    );
+}
+
+#[test]
+fn test_locked_effective_balance_curve() {
+    assert_eq!(math::locked_effective_balance(100, 50, 0, 30_000), Ok(100));
+    assert_eq!(math::locked_effective_balance(100, 0, 100, 30_000), Ok(100));
+    assert_eq!(
+        math::locked_effective_balance(100, 50, 100, 30_000),
+        Ok(200)
+    );
+    assert_eq!(
+        math::locked_effective_balance(100, 100, 100, 30_000),
+        Ok(300)
+    );
+    assert_eq!(
+        math::locked_effective_balance(100, 1_000, 100, 30_000),
+        Ok(300),
+        "Locks past the maximum duration are capped"
+    );
+    assert_eq!(
+        math::locked_effective_balance(u64::MAX, 100, 100, 20_000),
+        Err(StakingError::ArithmeticOverflow)
+    );
+}
+
+#[test]
+fn test_earned_from_accrued_rounds_down() {
+    assert_eq!(
+        math::earned_from_accrued(250 * PRECISION as u128 + 1, PRECISION, 5),
+        Ok(255)
+    );
+    assert_eq!(math::earned_from_accrued(0, PRECISION, 5), Ok(5));
+    assert_eq!(
+        math::earned_from_accrued(1, 0, 0),
+        Err(StakingError::InvalidPrecision)
+    );
+}
+
+#[test]
+fn test_unstake_penalty_decays_linearly() {
+    assert_eq!(math::unstake_penalty(1_000, 1_000, 100, 0), Ok(100));
+    assert_eq!(math::unstake_penalty(1_000, 1_000, 100, 50), Ok(50));
+    assert_eq!(math::unstake_penalty(1_000, 1_000, 100, 100), Ok(0));
+    assert_eq!(
+        math::unstake_penalty(1_000, 1_000, 0, 0),
+        Ok(0),
+        "Pools without a decay period charge no penalty"
+    );
+    assert_eq!(math::unstake_penalty(999, 1_000, 100, 1), Ok(98));
+}
+
+#[test]
+fn test_weighted_stake_timestamp() {
+    assert_eq!(math::weighted_stake_timestamp(100, 1_000, 200, 1), Ok(100));
+    assert_eq!(math::weighted_stake_timestamp(100, 100, 200, 100), Ok(150));
+    assert_eq!(
+        math::weighted_stake_timestamp(100, 0, 200, 100),
+        Ok(200),
+        "A first stake starts the decay now"
+    );
+    assert_eq!(math::weighted_stake_timestamp(100, 0, 200, 0), Ok(200));
+}
+
+#[test]
+fn test_reward_fee_rounds_down() {
+    assert_eq!(math::reward_fee(1_000, 250), Ok(25));
+    assert_eq!(math::reward_fee(39, 250), Ok(0));
+    assert_eq!(math::reward_fee(1_000, 0), Ok(0));
+    assert_eq!(math::reward_fee(u64::MAX, 1_000), Ok(u64::MAX / 10));
+}
@@ -0,0 +1,143 @@
+use solana_program::instruction::Instruction;
+use solana_program_test::tokio;
+use solana_sdk::signer::Signer;
+use staking::{error::StakingError, instruction};
+
+mod common;
+
+use common::*;
+
+fn pause_ix(f: &PoolFixture, staking: bool, claiming: bool, accrual: bool) -> Instruction {
+    instruction::pause(
+        &f.program_id,
+        &f.auth.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        staking,
+        claiming,
+        accrual,
+    )
+}
+
+fn unpause_ix(f: &PoolFixture, staking: bool, claiming: bool, accrual: bool) -> Instruction {
+    instruction::unpause(
+        &f.program_id,
+        &f.auth.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        staking,
+        claiming,
+        accrual,
+    )
+}
+
+/// Pool where alice stakes 100 tokens and 1000 rewards are emitted over 100 seconds.
+async fn setup_rewarded_pool() -> PoolFixture {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 1000).await;
+    notify_rewards(&mut f, &auth_rewards_token_account, 1000, 100).await;
+    f
+}
+
+#[tokio::test]
+async fn test_paused_staking_still_allows_unstake() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+
+    let ix = pause_ix(&f, true, false, false);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::StakingPaused)
+    );
+    let ix = alice_unstake_ix(&f, 100, false);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        None,
+        "Users must always be able to exit"
+    );
+
+    let ix = unpause_ix(&f, true, false, false);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+}
+
+#[tokio::test]
+async fn test_paused_claiming_keeps_rewards() {
+    let mut f = setup_rewarded_pool().await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+
+    let ix = pause_ix(&f, false, true, false);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    warp_seconds(&mut f.ctx, 10).await;
+    let ix = alice_get_rewards_ix(&f);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::ClaimingPaused)
+    );
+
+    let ix = unpause_ix(&f, false, true, false);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = alice_get_rewards_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        100
+    );
+}
+
+#[tokio::test]
+async fn test_paused_accrual_stops_rewards() {
+    let mut f = setup_rewarded_pool().await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+
+    warp_seconds(&mut f.ctx, 20).await;
+    let ix = pause_ix(&f, false, false, true);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    warp_seconds(&mut f.ctx, 30).await;
+    let ix = unpause_ix(&f, false, false, true);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    warp_seconds(&mut f.ctx, 10).await;
+
+    let ix = alice_get_rewards_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        300,
+        "Nothing must accrue while accrual is paused"
+    );
+}
+
+#[tokio::test]
+async fn test_pause_requires_admin() {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    let ix = instruction::pause(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        true,
+        true,
+        true,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::Unauthorized)
+    );
+}
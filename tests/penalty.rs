@@ -0,0 +1,359 @@
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use staking::{error::StakingError, instruction, pda_helper::PdaHelper};
+
+mod common;
+
+use common::*;
+
+const PENALTY_POOL_ID: u64 = 1;
+const DECAY_PERIOD: u64 = 100;
+
+fn set_unstake_penalty_ix(
+    f: &PoolFixture,
+    rewards_mint: &Pubkey,
+    pool_id: u64,
+    penalty_bps: u64,
+) -> Instruction {
+    instruction::set_unstake_penalty(
+        &f.program_id,
+        &f.auth.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        rewards_mint,
+        pool_id,
+        penalty_bps,
+        DECAY_PERIOD,
+    )
+}
+
+fn init_penalty_pool_ix(
+    f: &PoolFixture,
+    rewards_mint: &Pubkey,
+    penalty_treasury: Option<&Pubkey>,
+) -> Instruction {
+    instruction::init(
+        &f.program_id,
+        &f.auth.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        rewards_mint,
+        &f.staking_token_program,
+        &f.staking_token_program,
+        PENALTY_POOL_ID,
+        0,
+        10_000_000_000,
+        penalty_treasury,
+    )
+}
+
+fn stake_ix(f: &PoolFixture, user: &Pubkey, user_staking_token_account: &Pubkey) -> Instruction {
+    let staking_mint = f.staking_token_mint_account.pubkey();
+    instruction::stake(
+        &f.program_id,
+        user,
+        user_staking_token_account,
+        &staking_mint,
+        &staking_mint,
+        &f.staking_token_program,
+        PENALTY_POOL_ID,
+        100,
+        0,
+        None,
+    )
+}
+
+fn alice_unstake_from_penalty_pool_ix(
+    f: &PoolFixture,
+    penalty_treasury: Option<&Pubkey>,
+) -> Instruction {
+    let staking_mint = f.staking_token_mint_account.pubkey();
+    instruction::unstake(
+        &f.program_id,
+        &f.alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &staking_mint,
+        &staking_mint,
+        &f.staking_token_program,
+        PENALTY_POOL_ID,
+        100,
+        false,
+        penalty_treasury,
+        None,
+    )
+}
+
+/// Creates a staking token account of the admin to collect penalties.
+async fn create_treasury(f: &mut PoolFixture) -> Pubkey {
+    let auth = f.auth.insecure_clone();
+    let treasury = Keypair::new();
+    let mut banks_client = f.ctx.banks_client.clone();
+    create_and_initialize_account_for_mint(
+        &mut banks_client,
+        f.ctx.last_blockhash,
+        &f.staking_token_program,
+        &treasury,
+        &f.staking_token_mint_account,
+        &auth,
+        &auth,
+    )
+    .await
+    .unwrap();
+    treasury.pubkey()
+}
+
+#[tokio::test]
+async fn test_unstake_penalty_paid_to_remaining_stakers() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let bob = f.bob.insecure_clone();
+    let staking_mint = f.staking_token_mint_account.pubkey();
+    let rewards_mint = f.rewards_token_mint_account.pubkey();
+    let ix = set_unstake_penalty_ix(&f, &rewards_mint, f.pool_id, 1_000);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &auth).await,
+        custom_error(StakingError::PenaltyDestinationMissing),
+        "Without a treasury the pool must pay rewards in the staking token"
+    );
+
+    let ix = init_penalty_pool_ix(&f, &staking_mint, None);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = set_unstake_penalty_ix(&f, &staking_mint, PENALTY_POOL_ID, 10_001);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &auth).await,
+        custom_error(StakingError::InvalidUnstakePenalty)
+    );
+    let ix = set_unstake_penalty_ix(&f, &staking_mint, PENALTY_POOL_ID, 1_000);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let (bob_staking_token_account, _) = fund_bob(&mut f, 100).await;
+    let ix = stake_ix(&f, &alice.pubkey(), &f.alice_staking_token_account.pubkey());
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let ix = stake_ix(&f, &bob.pubkey(), &bob_staking_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &bob).await, None);
+
+    warp_seconds(&mut f.ctx, DECAY_PERIOD as i64 / 2).await;
+    let ix = alice_unstake_from_penalty_pool_ix(&f, None);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_staking_token_account).await,
+        1_000_000_000_000 - 5,
+        "Half of the 10% penalty has decayed"
+    );
+    let bob_get_rewards_ix = instruction::get_rewards(
+        &f.program_id,
+        &bob.pubkey(),
+        &staking_mint,
+        &staking_mint,
+        PENALTY_POOL_ID,
+        &(
+            bob_staking_token_account,
+            staking_mint,
+            f.staking_token_program,
+        ),
+        &[],
+        &[],
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, bob_get_rewards_ix, &bob).await, None);
+    assert_eq!(token_balance(&mut f, &bob_staking_token_account).await, 5);
+}
+
+#[tokio::test]
+async fn test_unstake_penalty_paid_to_treasury() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let rewards_mint = f.rewards_token_mint_account.pubkey();
+    let treasury = create_treasury(&mut f).await;
+
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    let ix = init_penalty_pool_ix(&f, &rewards_mint, Some(&alice_rewards_token_account));
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &auth).await,
+        custom_error(StakingError::InvalidPenaltyTreasury)
+    );
+    let ix = init_penalty_pool_ix(&f, &rewards_mint, Some(&treasury));
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = set_unstake_penalty_ix(&f, &rewards_mint, PENALTY_POOL_ID, 10_000);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let staking_mint = f.staking_token_mint_account.pubkey();
+    let stake_ix = instruction::stake(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &staking_mint,
+        &rewards_mint,
+        &f.staking_token_program,
+        PENALTY_POOL_ID,
+        100,
+        0,
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, stake_ix, &alice).await, None);
+
+    warp_seconds(&mut f.ctx, DECAY_PERIOD as i64 / 2).await;
+    let unstake_ix = |penalty_treasury: Option<&Pubkey>| {
+        instruction::unstake(
+            &f.program_id,
+            &alice.pubkey(),
+            &f.alice_staking_token_account.pubkey(),
+            &staking_mint,
+            &rewards_mint,
+            &f.staking_token_program,
+            PENALTY_POOL_ID,
+            100,
+            false,
+            penalty_treasury,
+            None,
+        )
+    };
+    let wrong_treasury_ix = unstake_ix(None);
+    let treasury_ix = unstake_ix(Some(&treasury));
+    assert_eq!(
+        process_ix(&mut f.ctx, wrong_treasury_ix, &alice).await,
+        custom_error(StakingError::InvalidPenaltyDestination)
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, treasury_ix, &alice).await,
+        None,
+        "The treasury is paid even when nobody else is staked"
+    );
+    assert_eq!(token_balance(&mut f, &treasury).await, 50);
+    let (staking_pool, _) = PdaHelper::find_staking_pool_pda(
+        &staking_mint,
+        &rewards_mint,
+        PENALTY_POOL_ID,
+        &f.program_id,
+    );
+    let (staking_token_escrow, _) = PdaHelper::find_staking_token_pda(&staking_pool, &f.program_id);
+    assert_eq!(token_balance(&mut f, &staking_token_escrow).await, 0);
+}
+
+#[tokio::test]
+async fn test_emergency_withdraw_pays_penalty() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let staking_mint = f.staking_token_mint_account.pubkey();
+    let rewards_mint = f.rewards_token_mint_account.pubkey();
+    let treasury = create_treasury(&mut f).await;
+    let ix = init_penalty_pool_ix(&f, &rewards_mint, Some(&treasury));
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = set_unstake_penalty_ix(&f, &rewards_mint, PENALTY_POOL_ID, 10_000);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let stake_ix = instruction::stake(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &staking_mint,
+        &rewards_mint,
+        &f.staking_token_program,
+        PENALTY_POOL_ID,
+        100,
+        0,
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, stake_ix, &alice).await, None);
+
+    warp_seconds(&mut f.ctx, DECAY_PERIOD as i64 / 2).await;
+    let emergency_withdraw_ix = instruction::emergency_withdraw(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &staking_mint,
+        &rewards_mint,
+        &f.staking_token_program,
+        PENALTY_POOL_ID,
+        None,
+        Some(&treasury),
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, emergency_withdraw_ix, &alice).await,
+        None
+    );
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_staking_token_account).await,
+        1_000_000_000_000 - 50,
+        "Forfeiting rewards does not waive the penalty"
+    );
+    assert_eq!(token_balance(&mut f, &treasury).await, 50);
+}
+
+#[tokio::test]
+async fn test_unstake_request_pays_penalty() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let staking_mint = f.staking_token_mint_account.pubkey();
+    let rewards_mint = f.rewards_token_mint_account.pubkey();
+    let treasury = create_treasury(&mut f).await;
+    let ix = init_penalty_pool_ix(&f, &rewards_mint, Some(&treasury));
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = set_unstake_penalty_ix(&f, &rewards_mint, PENALTY_POOL_ID, 10_000);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = instruction::set_unbonding_period(
+        &f.program_id,
+        &auth.pubkey(),
+        &staking_mint,
+        &rewards_mint,
+        PENALTY_POOL_ID,
+        DECAY_PERIOD,
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let stake_ix = instruction::stake(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &staking_mint,
+        &rewards_mint,
+        &f.staking_token_program,
+        PENALTY_POOL_ID,
+        100,
+        0,
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, stake_ix, &alice).await, None);
+
+    warp_seconds(&mut f.ctx, DECAY_PERIOD as i64 / 2).await;
+    let request_unstake_ix = instruction::request_unstake(
+        &f.program_id,
+        &alice.pubkey(),
+        &staking_mint,
+        &rewards_mint,
+        &f.staking_token_program,
+        PENALTY_POOL_ID,
+        100,
+        Some(&treasury),
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, request_unstake_ix, &alice).await,
+        None
+    );
+    assert_eq!(
+        token_balance(&mut f, &treasury).await,
+        50,
+        "The penalty is withheld when the request is queued"
+    );
+
+    warp_seconds(&mut f.ctx, DECAY_PERIOD as i64).await;
+    let complete_unstake_ix = instruction::complete_unstake(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &staking_mint,
+        &rewards_mint,
+        &f.staking_token_program,
+        PENALTY_POOL_ID,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, complete_unstake_ix, &alice).await,
+        None
+    );
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_staking_token_account).await,
+        1_000_000_000_000 - 50
+    );
+}
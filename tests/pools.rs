@@ -0,0 +1,100 @@
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::signer::Signer;
+use staking::{error::StakingError, instruction, pda_helper::PdaHelper, state::StakingPoolState};
+
+mod common;
+
+use common::*;
+
+const SECOND_POOL_ID: u64 = 1;
+
+async fn pool_total_supply(f: &mut PoolFixture, staking_pool: Pubkey) -> u64 {
+    let account = f
+        .ctx
+        .banks_client
+        .get_account(staking_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    StakingPoolState::unpack(&account.data)
+        .unwrap()
+        .total_supply
+}
+
+#[tokio::test]
+async fn test_pools_for_same_mints_are_independent() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let staking_mint = f.staking_token_mint_account.pubkey();
+    let rewards_mint = f.rewards_token_mint_account.pubkey();
+
+    let init_ix = instruction::init(
+        &f.program_id,
+        &auth.pubkey(),
+        &staking_mint,
+        &rewards_mint,
+        &f.staking_token_program,
+        &f.rewards_token_program,
+        SECOND_POOL_ID,
+        100,
+        10_000_000_000,
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, init_ix, &auth).await, None);
+    let (second_pool, _) = PdaHelper::find_staking_pool_pda(
+        &staking_mint,
+        &rewards_mint,
+        SECOND_POOL_ID,
+        &f.program_id,
+    );
+    let (second_escrow, _) = PdaHelper::find_staking_token_pda(&second_pool, &f.program_id);
+    assert_ne!(second_pool, f.staking_pool_pda);
+    assert_ne!(second_escrow, f.staking_token_escrow_pda);
+
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let stake_into_second_pool_ix = instruction::stake(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &staking_mint,
+        &rewards_mint,
+        &f.staking_token_program,
+        SECOND_POOL_ID,
+        30,
+        0,
+        None,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, stake_into_second_pool_ix, &alice).await,
+        None
+    );
+
+    let first_pool = f.staking_pool_pda;
+    let first_escrow = f.staking_token_escrow_pda;
+    assert_eq!(pool_total_supply(&mut f, first_pool).await, 100);
+    assert_eq!(pool_total_supply(&mut f, second_pool).await, 30);
+    assert_eq!(token_balance(&mut f, &first_escrow).await, 100);
+    assert_eq!(token_balance(&mut f, &second_escrow).await, 30);
+
+    let unstake_from_second_pool_ix = instruction::unstake(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &staking_mint,
+        &rewards_mint,
+        &f.staking_token_program,
+        SECOND_POOL_ID,
+        100,
+        false,
+        None,
+        None,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, unstake_from_second_pool_ix, &alice).await,
+        custom_error(StakingError::UnstakeExceedsBalance),
+        "A stake in one pool must not withdraw funds of another"
+    );
+}
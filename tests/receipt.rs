@@ -0,0 +1,600 @@
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{
+    program_pack::Pack, signature::Keypair, signer::Signer, transaction::Transaction,
+};
+use spl_token_2022::state::Account;
+use staking::{error::StakingError, instruction, pda_helper::PdaHelper};
+
+mod common;
+
+use common::*;
+
+fn create_receipt_mint_ix(f: &PoolFixture, admin: &Pubkey) -> Instruction {
+    instruction::create_receipt_mint(
+        &f.program_id,
+        admin,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+    )
+}
+
+fn stake_ix(f: &PoolFixture, lock_duration: u64, receipt_token_account: &Pubkey) -> Instruction {
+    instruction::stake(
+        &f.program_id,
+        &f.alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+        100,
+        lock_duration,
+        Some(receipt_token_account),
+    )
+}
+
+fn sync_ix(f: &PoolFixture, holder: &Pubkey, receipt_token_account: &Pubkey) -> Instruction {
+    instruction::sync_receipt_balance(
+        &f.program_id,
+        &f.auth.pubkey(),
+        holder,
+        receipt_token_account,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+    )
+}
+
+fn get_rewards_ix(f: &PoolFixture, receipt_token_account: &Pubkey) -> Instruction {
+    instruction::get_rewards(
+        &f.program_id,
+        &f.alice.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &(
+            f.alice_rewards_token_account.pubkey(),
+            f.rewards_token_mint_account.pubkey(),
+            f.rewards_token_program,
+        ),
+        &[],
+        &[],
+        Some(receipt_token_account),
+    )
+}
+
+/// Transfers all 100 staked receipts from `from`, owned by `owner`.
+fn transfer_receipts_ix(
+    f: &PoolFixture,
+    from: &Pubkey,
+    to: &Pubkey,
+    owner: &Pubkey,
+) -> Instruction {
+    let (receipt_mint, _) = PdaHelper::find_receipt_mint_pda(&f.staking_pool_pda, &f.program_id);
+    spl_token_2022::instruction::transfer_checked(
+        &f.staking_token_program,
+        from,
+        &receipt_mint,
+        to,
+        owner,
+        &[],
+        100,
+        9,
+    )
+    .unwrap()
+}
+
+/// Creates a receipt token account owned by `owner`, paid by the admin.
+async fn create_receipt_token_account(
+    f: &mut PoolFixture,
+    staking_pool: &Pubkey,
+    owner: &Pubkey,
+) -> Pubkey {
+    let (receipt_mint, _) = PdaHelper::find_receipt_mint_pda(staking_pool, &f.program_id);
+    let receipt_token_account = Keypair::new();
+    let rent = f.ctx.banks_client.get_rent().await.unwrap();
+    let create_account_ix = solana_program::system_instruction::create_account(
+        &f.auth.pubkey(),
+        &receipt_token_account.pubkey(),
+        rent.minimum_balance(Account::LEN),
+        Account::LEN as u64,
+        &f.staking_token_program,
+    );
+    let initialize_account_ix = spl_token_2022::instruction::initialize_account3(
+        &f.staking_token_program,
+        &receipt_token_account.pubkey(),
+        &receipt_mint,
+        owner,
+    )
+    .unwrap();
+    let blockhash = f.ctx.get_new_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[create_account_ix, initialize_account_ix],
+        Some(&f.auth.pubkey()),
+        &[&f.auth, &receipt_token_account],
+        blockhash,
+    );
+    f.ctx.banks_client.process_transaction(tx).await.unwrap();
+    receipt_token_account.pubkey()
+}
+
+#[tokio::test]
+async fn test_transferred_receipts_earn_for_the_new_holder() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let bob = f.bob.insecure_clone();
+    let ix = create_receipt_mint_ix(&f, &auth.pubkey());
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let (receipt_mint, _) = PdaHelper::find_receipt_mint_pda(&f.staking_pool_pda, &f.program_id);
+    assert_eq!(pool_state(&mut f).await.receipt_mint, receipt_mint);
+    let staking_pool = f.staking_pool_pda;
+    let alice_receipt_token_account =
+        create_receipt_token_account(&mut f, &staking_pool, &alice.pubkey()).await;
+    let bob_receipt_token_account =
+        create_receipt_token_account(&mut f, &staking_pool, &bob.pubkey()).await;
+    let (bob_staking_token_account, bob_rewards_token_account) = fund_bob(&mut f, 0).await;
+
+    let ix = stake_ix(&f, 0, &alice_receipt_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    assert_eq!(
+        token_balance(&mut f, &alice_receipt_token_account).await,
+        100
+    );
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 1000).await;
+    notify_rewards(&mut f, &auth_rewards_token_account, 1000, 100).await;
+    warp_seconds(&mut f.ctx, 50).await;
+    // Settled before sending, so the receipts keep what they earned so far
+    let ix = sync_ix(&f, &alice.pubkey(), &alice_receipt_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+
+    let transfer_ix = spl_token_2022::instruction::transfer_checked(
+        &f.staking_token_program,
+        &alice_receipt_token_account,
+        &receipt_mint,
+        &bob_receipt_token_account,
+        &alice.pubkey(),
+        &[],
+        100,
+        9,
+    )
+    .unwrap();
+    assert_eq!(process_ix(&mut f.ctx, transfer_ix, &alice).await, None);
+    for (holder, receipt_token_account) in [
+        (alice.pubkey(), alice_receipt_token_account),
+        (bob.pubkey(), bob_receipt_token_account),
+    ] {
+        let ix = sync_ix(&f, &holder, &receipt_token_account);
+        assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    }
+    let state = pool_state(&mut f).await;
+    assert_eq!(state.total_supply, 100);
+    warp_seconds(&mut f.ctx, 60).await;
+
+    let ix = get_rewards_ix(&f, &alice_receipt_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let bob_get_rewards_ix = instruction::get_rewards(
+        &f.program_id,
+        &bob.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &(
+            bob_rewards_token_account,
+            f.rewards_token_mint_account.pubkey(),
+            f.rewards_token_program,
+        ),
+        &[],
+        &[],
+        Some(&bob_receipt_token_account),
+    );
+    assert_eq!(process_ix(&mut f.ctx, bob_get_rewards_ix, &bob).await, None);
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        500
+    );
+    assert_eq!(token_balance(&mut f, &bob_rewards_token_account).await, 500);
+
+    let bob_unstake_ix = instruction::unstake(
+        &f.program_id,
+        &bob.pubkey(),
+        &bob_staking_token_account,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+        100,
+        false,
+        None,
+        Some(&bob_receipt_token_account),
+    );
+    assert_eq!(process_ix(&mut f.ctx, bob_unstake_ix, &bob).await, None);
+    assert_eq!(token_balance(&mut f, &bob_staking_token_account).await, 100);
+    assert_eq!(token_balance(&mut f, &bob_receipt_token_account).await, 0);
+    assert_eq!(pool_state(&mut f).await.total_supply, 0);
+}
+
+#[tokio::test]
+async fn test_claiming_syncs_transferred_receipts() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let bob = f.bob.insecure_clone();
+    let ix = create_receipt_mint_ix(&f, &auth.pubkey());
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let (receipt_mint, _) = PdaHelper::find_receipt_mint_pda(&f.staking_pool_pda, &f.program_id);
+    let staking_pool = f.staking_pool_pda;
+    let alice_receipt_token_account =
+        create_receipt_token_account(&mut f, &staking_pool, &alice.pubkey()).await;
+    let bob_receipt_token_account =
+        create_receipt_token_account(&mut f, &staking_pool, &bob.pubkey()).await;
+    let ix = stake_ix(&f, 0, &alice_receipt_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+
+    let transfer_ix = spl_token_2022::instruction::transfer_checked(
+        &f.staking_token_program,
+        &alice_receipt_token_account,
+        &receipt_mint,
+        &bob_receipt_token_account,
+        &alice.pubkey(),
+        &[],
+        100,
+        9,
+    )
+    .unwrap();
+    assert_eq!(process_ix(&mut f.ctx, transfer_ix, &alice).await, None);
+    let ix = get_rewards_ix(&f, &bob_receipt_token_account);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::InvalidReceiptAccount)
+    );
+    let ix = get_rewards_ix(&f, &alice_receipt_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    assert_eq!(pool_state(&mut f).await.total_supply, 0);
+    let ix = instruction::emergency_withdraw(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+        Some(&alice_receipt_token_account),
+        None,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::ZeroAmount),
+        "Transferred receipts are no longer withdrawable"
+    );
+}
+
+#[tokio::test]
+async fn test_sent_receipts_earn_nothing_for_the_sender() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let bob = f.bob.insecure_clone();
+    let ix = create_receipt_mint_ix(&f, &auth.pubkey());
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let staking_pool = f.staking_pool_pda;
+    let alice_receipt_token_account =
+        create_receipt_token_account(&mut f, &staking_pool, &alice.pubkey()).await;
+    let bob_receipt_token_account =
+        create_receipt_token_account(&mut f, &staking_pool, &bob.pubkey()).await;
+    let (_, bob_rewards_token_account) = fund_bob(&mut f, 0).await;
+    let ix = stake_ix(&f, 0, &alice_receipt_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 1000).await;
+    notify_rewards(&mut f, &auth_rewards_token_account, 1000, 100).await;
+    warp_seconds(&mut f.ctx, 50).await;
+
+    // Hop the same receipts to bob and back without alice settling in between
+    let ix = transfer_receipts_ix(
+        &f,
+        &alice_receipt_token_account,
+        &bob_receipt_token_account,
+        &alice.pubkey(),
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let ix = sync_ix(&f, &bob.pubkey(), &bob_receipt_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = transfer_receipts_ix(
+        &f,
+        &bob_receipt_token_account,
+        &alice_receipt_token_account,
+        &bob.pubkey(),
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &bob).await, None);
+    warp_seconds(&mut f.ctx, 50).await;
+
+    let ix = get_rewards_ix(&f, &alice_receipt_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let bob_get_rewards_ix = instruction::get_rewards(
+        &f.program_id,
+        &bob.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &(
+            bob_rewards_token_account,
+            f.rewards_token_mint_account.pubkey(),
+            f.rewards_token_program,
+        ),
+        &[],
+        &[],
+        Some(&bob_receipt_token_account),
+    );
+    assert_eq!(process_ix(&mut f.ctx, bob_get_rewards_ix, &bob).await, None);
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    let paid = token_balance(&mut f, &alice_rewards_token_account).await
+        + token_balance(&mut f, &bob_rewards_token_account).await;
+    assert!(
+        paid <= 1000,
+        "Hopping receipts paid {} while the principal earned 1000",
+        paid
+    );
+    assert_eq!(
+        token_balance(&mut f, &bob_rewards_token_account).await,
+        0,
+        "Bob sent the receipts back before settling"
+    );
+    let state = pool_state(&mut f).await;
+    assert_eq!(
+        (state.total_supply, state.total_effective_supply),
+        (100, 100)
+    );
+}
+
+#[tokio::test]
+async fn test_unstake_burns_receipts_and_pays_penalty() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let staking_mint = f.staking_token_mint_account.pubkey();
+    let pool_id = 1;
+    let ix = instruction::init(
+        &f.program_id,
+        &auth.pubkey(),
+        &staking_mint,
+        &staking_mint,
+        &f.staking_token_program,
+        &f.staking_token_program,
+        pool_id,
+        0,
+        10_000_000_000,
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = instruction::set_unstake_penalty(
+        &f.program_id,
+        &auth.pubkey(),
+        &staking_mint,
+        &staking_mint,
+        pool_id,
+        1_000,
+        100,
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = instruction::create_receipt_mint(
+        &f.program_id,
+        &auth.pubkey(),
+        &staking_mint,
+        &staking_mint,
+        &f.staking_token_program,
+        pool_id,
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let (staking_pool, _) =
+        PdaHelper::find_staking_pool_pda(&staking_mint, &staking_mint, pool_id, &f.program_id);
+    let alice_receipt_token_account =
+        create_receipt_token_account(&mut f, &staking_pool, &alice.pubkey()).await;
+    let ix = instruction::stake(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &staking_mint,
+        &staking_mint,
+        &f.staking_token_program,
+        pool_id,
+        100,
+        0,
+        Some(&alice_receipt_token_account),
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+
+    warp_seconds(&mut f.ctx, 50).await;
+    let ix = instruction::unstake(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &staking_mint,
+        &staking_mint,
+        &f.staking_token_program,
+        pool_id,
+        60,
+        false,
+        None,
+        Some(&alice_receipt_token_account),
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    assert_eq!(
+        token_balance(&mut f, &alice_receipt_token_account).await,
+        40
+    );
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_staking_token_account).await,
+        1_000_000_000_000 - 40 - 3,
+        "Half of the 10% penalty has decayed"
+    );
+}
+
+#[tokio::test]
+async fn test_closed_pool_id_reuses_receipt_mint() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let bob = f.bob.insecure_clone();
+    let ix = create_receipt_mint_ix(&f, &auth.pubkey());
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let (receipt_mint, _) = PdaHelper::find_receipt_mint_pda(&f.staking_pool_pda, &f.program_id);
+    let staking_pool = f.staking_pool_pda;
+    let alice_receipt_token_account =
+        create_receipt_token_account(&mut f, &staking_pool, &alice.pubkey()).await;
+    let bob_receipt_token_account =
+        create_receipt_token_account(&mut f, &staking_pool, &bob.pubkey()).await;
+    let (bob_staking_token_account, _) = fund_bob(&mut f, 0).await;
+    let ix = stake_ix(&f, 0, &alice_receipt_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let transfer_ix = spl_token_2022::instruction::transfer_checked(
+        &f.staking_token_program,
+        &alice_receipt_token_account,
+        &receipt_mint,
+        &bob_receipt_token_account,
+        &alice.pubkey(),
+        &[],
+        100,
+        9,
+    )
+    .unwrap();
+    assert_eq!(process_ix(&mut f.ctx, transfer_ix, &alice).await, None);
+    let ix = sync_ix(&f, &alice.pubkey(), &alice_receipt_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = instruction::close_user_state(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+
+    let auth_staking_token_account = Keypair::new();
+    create_and_initialize_account_for_mint(
+        &mut f.ctx.banks_client.clone(),
+        f.ctx.last_blockhash,
+        &f.staking_token_program,
+        &auth_staking_token_account,
+        &f.staking_token_mint_account,
+        &auth,
+        &auth,
+    )
+    .await
+    .unwrap();
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 0).await;
+    let close_pool_ix = instruction::close_pool(
+        &f.program_id,
+        &auth.pubkey(),
+        &auth_staking_token_account.pubkey(),
+        &auth_rewards_token_account,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        &f.rewards_token_program,
+        f.pool_id,
+        &[],
+        true,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, close_pool_ix.clone(), &auth).await,
+        custom_error(StakingError::PoolNotEmpty),
+        "Unsynced receipts must stay redeemable"
+    );
+    let ix = sync_ix(&f, &bob.pubkey(), &bob_receipt_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = instruction::unstake(
+        &f.program_id,
+        &bob.pubkey(),
+        &bob_staking_token_account,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+        100,
+        true,
+        None,
+        Some(&bob_receipt_token_account),
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &bob).await, None);
+    assert_eq!(process_ix(&mut f.ctx, close_pool_ix, &auth).await, None);
+
+    let ix = instruction::init(
+        &f.program_id,
+        &auth.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        &f.rewards_token_program,
+        f.pool_id,
+        100,
+        10_000_000_000,
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = create_receipt_mint_ix(&f, &auth.pubkey());
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    assert_eq!(pool_state(&mut f).await.receipt_mint, receipt_mint);
+    let ix = stake_ix(&f, 0, &alice_receipt_token_account);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let state = pool_state(&mut f).await;
+    assert_eq!(state.total_supply, 100);
+    assert_eq!(state.user_state_count, 1);
+}
+
+#[tokio::test]
+async fn test_receipt_mint_validation() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let bob = f.bob.insecure_clone();
+    let ix = create_receipt_mint_ix(&f, &alice.pubkey());
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::Unauthorized)
+    );
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let ix = create_receipt_mint_ix(&f, &auth.pubkey());
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &auth).await,
+        custom_error(StakingError::PoolNotEmpty),
+        "Existing stakes are not backed by receipt tokens"
+    );
+    let ix = alice_unstake_ix(&f, 100, false);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let ix = create_receipt_mint_ix(&f, &auth.pubkey());
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+
+    let staking_pool = f.staking_pool_pda;
+    let alice_receipt_token_account =
+        create_receipt_token_account(&mut f, &staking_pool, &alice.pubkey()).await;
+    let bob_receipt_token_account =
+        create_receipt_token_account(&mut f, &staking_pool, &bob.pubkey()).await;
+    let ix = stake_ix(&f, 100, &alice_receipt_token_account);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::ReceiptMintUnsupported)
+    );
+    let ix = stake_ix(&f, 0, &bob_receipt_token_account);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::InvalidReceiptAccount)
+    );
+    let ix = instruction::set_unbonding_period(
+        &f.program_id,
+        &auth.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        100,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &auth).await,
+        custom_error(StakingError::ReceiptMintUnsupported)
+    );
+}
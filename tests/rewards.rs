@@ -0,0 +1,213 @@
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::signer::Signer;
+use staking::{error::StakingError, instruction};
+
+mod common;
+
+use common::*;
+
+const REWARD_AMOUNT: u64 = 1000;
+const REWARD_DURATION: u64 = 100;
+const OVERFUNDED_AMOUNT: u64 = 500;
+
+/// Pool where alice stakes 100 tokens, rewards are notified and the escrow receives
+/// `OVERFUNDED_AMOUNT` tokens on top of the campaign. Returns the admin rewards token account.
+async fn setup_overfunded_pool() -> (PoolFixture, Pubkey) {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+
+    let auth_rewards_token_account =
+        fund_admin_rewards(&mut f, REWARD_AMOUNT + OVERFUNDED_AMOUNT).await;
+    let stake_ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, stake_ix, &alice).await, None);
+    notify_rewards(
+        &mut f,
+        &auth_rewards_token_account,
+        REWARD_AMOUNT,
+        REWARD_DURATION,
+    )
+    .await;
+
+    let transfer_ix = spl_token::instruction::transfer(
+        &spl_token::id(),
+        &auth_rewards_token_account,
+        &f.rewards_token_escrow_pda,
+        &auth.pubkey(),
+        &[],
+        OVERFUNDED_AMOUNT,
+    )
+    .unwrap();
+    assert_eq!(process_ix(&mut f.ctx, transfer_ix, &auth).await, None);
+
+    (f, auth_rewards_token_account)
+}
+
+#[tokio::test]
+async fn test_withdraw_excess_rewards_keeps_owed_rewards() {
+    let (mut f, auth_rewards_token_account) = setup_overfunded_pool().await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let staking_mint = f.staking_token_mint_account.pubkey();
+    let rewards_mint = f.rewards_token_mint_account.pubkey();
+
+    warp_seconds(&mut f.ctx, (REWARD_DURATION / 2) as i64).await;
+
+    let withdraw_ix = |amount| {
+        instruction::withdraw_excess_rewards(
+            &f.program_id,
+            &auth.pubkey(),
+            &auth_rewards_token_account,
+            &staking_mint,
+            &rewards_mint,
+            f.pool_id,
+            &rewards_mint,
+            &f.rewards_token_program,
+            amount,
+        )
+    };
+    assert_eq!(
+        process_ix(&mut f.ctx, withdraw_ix(OVERFUNDED_AMOUNT + 1), &auth).await,
+        custom_error(StakingError::InsufficientExcessRewards),
+        "Accrued and still to be emitted rewards must stay in the escrow"
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, withdraw_ix(OVERFUNDED_AMOUNT), &auth).await,
+        None
+    );
+    assert_eq!(
+        token_balance(&mut f, &auth_rewards_token_account).await,
+        OVERFUNDED_AMOUNT
+    );
+
+    warp_seconds(&mut f.ctx, REWARD_DURATION as i64).await;
+
+    let get_rewards_ix = alice_get_rewards_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, get_rewards_ix, &alice).await, None);
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        REWARD_AMOUNT,
+        "Stakers must still be able to claim the whole campaign"
+    );
+    let rewards_token_escrow_pda = f.rewards_token_escrow_pda;
+    assert_eq!(token_balance(&mut f, &rewards_token_escrow_pda).await, 0);
+}
+
+#[tokio::test]
+async fn test_withdraw_excess_rewards_requires_admin() {
+    let (mut f, _) = setup_overfunded_pool().await;
+    let alice = f.alice.insecure_clone();
+    let withdraw_ix = instruction::withdraw_excess_rewards(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.alice_rewards_token_account.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &f.rewards_token_mint_account.pubkey(),
+        &f.rewards_token_program,
+        1,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, withdraw_ix, &alice).await,
+        custom_error(StakingError::Unauthorized)
+    );
+}
+
+#[tokio::test]
+async fn test_set_reward_rate_keeps_period_funded() {
+    let (mut f, _) = setup_overfunded_pool().await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let rewards_mint = f.rewards_token_mint_account.pubkey();
+    warp_seconds(&mut f.ctx, (REWARD_DURATION / 2) as i64).await;
+
+    // 500 of the escrow is owed, the other 1000 funds the last 50 seconds at 20 per second
+    let set_reward_rate_ix = |reward_rate| {
+        instruction::set_reward_rate(
+            &f.program_id,
+            &auth.pubkey(),
+            &f.staking_token_mint_account.pubkey(),
+            &rewards_mint,
+            f.pool_id,
+            &rewards_mint,
+            reward_rate,
+        )
+    };
+    assert_eq!(
+        process_ix(&mut f.ctx, set_reward_rate_ix(21), &auth).await,
+        custom_error(StakingError::InsufficientRewardEscrow)
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, set_reward_rate_ix(20), &auth).await,
+        None
+    );
+
+    warp_seconds(&mut f.ctx, REWARD_DURATION as i64).await;
+    let get_rewards_ix = alice_get_rewards_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, get_rewards_ix, &alice).await, None);
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        REWARD_AMOUNT + OVERFUNDED_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn test_init_reward_rate_is_inert_until_notified() {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    assert_eq!(pool_state(&mut f).await.reward_streams[0].reward_rate, 100);
+    let stake_ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, stake_ix, &alice).await, None);
+    warp_seconds(&mut f.ctx, REWARD_DURATION as i64).await;
+
+    let get_rewards_ix = alice_get_rewards_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, get_rewards_ix, &alice).await, None);
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        0,
+        "Nothing accrues before a reward period is funded"
+    );
+}
+
+#[tokio::test]
+async fn test_notify_without_duration_pays_at_the_configured_rate() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, REWARD_AMOUNT).await;
+
+    // Less than one second of rewards at the Init rate of 100 per second
+    let notify_ix = |amount| {
+        instruction::notify_reward_amount(
+            &f.program_id,
+            &auth.pubkey(),
+            &auth_rewards_token_account,
+            &f.staking_token_mint_account.pubkey(),
+            &f.rewards_token_mint_account.pubkey(),
+            f.pool_id,
+            &f.rewards_token_mint_account.pubkey(),
+            &f.rewards_token_program,
+            amount,
+            0,
+        )
+    };
+    assert_eq!(
+        process_ix(&mut f.ctx, notify_ix(99), &auth).await,
+        custom_error(StakingError::ZeroDuration)
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, notify_ix(REWARD_AMOUNT), &auth).await,
+        None
+    );
+
+    let pool = pool_state(&mut f).await;
+    assert_eq!(pool.reward_streams[0].reward_rate, 100);
+    assert_eq!(
+        pool.reward_streams[0].period_finish,
+        pool.last_update_timestamp + (REWARD_AMOUNT / 100) as i64
+    );
+}
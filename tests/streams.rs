@@ -0,0 +1,233 @@
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use staking::{error::StakingError, instruction, pda_helper::PdaHelper};
+
+mod common;
+
+use common::*;
+
+/// Second reward token paid next to the one the pool was created with.
+struct PartnerStream {
+    mint: Keypair,
+    auth_token_account: Pubkey,
+    alice_token_account: Pubkey,
+}
+
+fn add_reward_stream_ix(f: &PoolFixture, admin: &Pubkey, stream_mint: &Pubkey) -> Instruction {
+    instruction::add_reward_stream(
+        &f.program_id,
+        admin,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        stream_mint,
+        &spl_token::id(),
+    )
+}
+
+/// Adds a partner reward stream and funds the admin with `amount` partner tokens.
+async fn add_partner_stream(f: &mut PoolFixture, amount: u64) -> PartnerStream {
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let mut banks_client = f.ctx.banks_client.clone();
+    let blockhash = f.ctx.last_blockhash;
+    let mint = Keypair::new();
+    create_and_initialize_mint(
+        &mut banks_client,
+        blockhash,
+        &auth,
+        &auth,
+        &mint,
+        &spl_token::id(),
+        &6,
+    )
+    .await
+    .unwrap();
+    let auth_token_account = Keypair::new();
+    let alice_token_account = Keypair::new();
+    for (account, owner) in [(&auth_token_account, &auth), (&alice_token_account, &alice)] {
+        create_and_initialize_account_for_mint(
+            &mut banks_client,
+            blockhash,
+            &spl_token::id(),
+            account,
+            &mint,
+            &auth,
+            owner,
+        )
+        .await
+        .unwrap();
+    }
+    mint_amount(
+        &mut banks_client,
+        blockhash,
+        &spl_token::id(),
+        &auth_token_account.pubkey(),
+        &mint.pubkey(),
+        &auth,
+        &auth,
+        amount as f64,
+        0,
+    )
+    .await
+    .unwrap();
+
+    let ix = add_reward_stream_ix(f, &auth.pubkey(), &mint.pubkey());
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    PartnerStream {
+        mint,
+        auth_token_account: auth_token_account.pubkey(),
+        alice_token_account: alice_token_account.pubkey(),
+    }
+}
+
+#[tokio::test]
+async fn test_rewards_are_paid_per_stream() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let partner = add_partner_stream(&mut f, 2000).await;
+
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 1000).await;
+    notify_rewards(&mut f, &auth_rewards_token_account, 1000, 100).await;
+    let notify_partner_ix = instruction::notify_reward_amount(
+        &f.program_id,
+        &auth.pubkey(),
+        &partner.auth_token_account,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &partner.mint.pubkey(),
+        &spl_token::id(),
+        2000,
+        100,
+    );
+    assert_eq!(process_ix(&mut f.ctx, notify_partner_ix, &auth).await, None);
+    warp_seconds(&mut f.ctx, 110).await;
+
+    let claim_partner_ix = instruction::get_rewards(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &(
+            partner.alice_token_account,
+            partner.mint.pubkey(),
+            spl_token::id(),
+        ),
+        &[],
+        &[],
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, claim_partner_ix, &alice).await, None);
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &partner.alice_token_account).await,
+        2000
+    );
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        0,
+        "Only the chosen stream is paid"
+    );
+
+    let claim_all_ix = instruction::get_rewards(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &(
+            alice_rewards_token_account,
+            f.rewards_token_mint_account.pubkey(),
+            f.rewards_token_program,
+        ),
+        &[(
+            partner.alice_token_account,
+            partner.mint.pubkey(),
+            spl_token::id(),
+        )],
+        &[],
+        None,
+    );
+    assert_eq!(process_ix(&mut f.ctx, claim_all_ix, &alice).await, None);
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        1000
+    );
+    assert_eq!(
+        token_balance(&mut f, &partner.alice_token_account).await,
+        2000
+    );
+
+    let ix = alice_unstake_ix(&f, 100, true);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let auth_staking_token_account = Keypair::new();
+    create_and_initialize_account_for_mint(
+        &mut f.ctx.banks_client.clone(),
+        f.ctx.last_blockhash,
+        &spl_token::id(),
+        &auth_staking_token_account,
+        &f.staking_token_mint_account,
+        &auth,
+        &auth,
+    )
+    .await
+    .unwrap();
+    let close_pool_ix = instruction::close_pool(
+        &f.program_id,
+        &auth.pubkey(),
+        &auth_staking_token_account.pubkey(),
+        &auth_rewards_token_account,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        &f.rewards_token_program,
+        f.pool_id,
+        &[(
+            partner.auth_token_account,
+            partner.mint.pubkey(),
+            spl_token::id(),
+        )],
+        false,
+    );
+    assert_eq!(process_ix(&mut f.ctx, close_pool_ix, &auth).await, None);
+    let (partner_escrow, _) = PdaHelper::find_rewards_token_pda(
+        &f.staking_pool_pda,
+        &partner.mint.pubkey(),
+        &f.program_id,
+    );
+    assert_eq!(
+        f.ctx
+            .banks_client
+            .get_account(partner_escrow)
+            .await
+            .unwrap(),
+        None,
+        "Escrows of added streams are closed with the pool"
+    );
+}
+
+#[tokio::test]
+async fn test_add_reward_stream_validation() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let partner_mint = Keypair::new().pubkey();
+
+    let ix = add_reward_stream_ix(&f, &alice.pubkey(), &partner_mint);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::Unauthorized)
+    );
+    let rewards_mint = f.rewards_token_mint_account.pubkey();
+    let ix = add_reward_stream_ix(&f, &auth.pubkey(), &rewards_mint);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &auth).await,
+        custom_error(StakingError::RewardStreamExists)
+    );
+}
@@ -1,226 +1,27 @@
-use borsh::BorshDeserialize;
+use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    hash::Hash,
-    instruction::{AccountMeta, Instruction},
-    program_error::ProgramError,
+    instruction::{AccountMeta, Instruction, InstructionError},
     pubkey::Pubkey,
     system_program,
 };
-use solana_program_test::{processor, tokio, BanksClient, ProgramTest};
+use solana_program_test::{processor, tokio, ProgramTest};
 use solana_sdk::{
     program_pack::Pack, signature::Keypair, signer::Signer, system_instruction,
-    transaction::Transaction, transport::TransportError,
-};
-use spl_token::{
-    id,
-    state::{Account, Mint},
+    transaction::{Transaction, TransactionError},
 };
+use spl_token::{id, state::Account};
 use staking::{
     entrypoint::process_instruction,
-    instruction::StakingInstruction,
-    state::{StakingPoolState, UserState},
+    error::StakingError,
+    instruction::{self, StakingInstruction},
+    pda_helper::PdaHelper,
+    state::StakingPoolState,
 };
 use std::str::FromStr;
 
-async fn create_and_initialize_account_for_mint(
-    banks_client: &mut BanksClient,
-    recent_blockhash: Hash,
-    token_program: &Pubkey,
-    token_account: &Keypair,
-    mint: &Keypair,
-    payer: &Keypair,
-    owner: &Keypair,
-) -> Result<(), ProgramError> {
-    let rent = banks_client.get_rent().await.unwrap();
-    let account_rent = rent.minimum_balance(Account::LEN);
-    let create_account_ix = solana_program::system_instruction::create_account(
-        &payer.pubkey(),
-        &token_account.pubkey(),
-        account_rent,
-        Account::LEN as u64,
-        token_program,
-    );
-    let initialize_account_ix = spl_token::instruction::initialize_account(
-        token_program,
-        &token_account.pubkey(),
-        &mint.pubkey(),
-        &owner.pubkey(),
-    )
-    .unwrap();
-    let initialize_account_tx = Transaction::new_signed_with_payer(
-        &[create_account_ix, initialize_account_ix],
-        Some(&payer.pubkey()),
-        &[payer, token_account],
-        recent_blockhash,
-    );
-    banks_client
-        .process_transaction(initialize_account_tx)
-        .await
-        .unwrap();
-    Ok(())
-}
+mod common;
 
-async fn mint_amount(
-    banks_client: &mut BanksClient,
-    recent_blockhash: Hash,
-    token_program: &Pubkey,
-    account: &Pubkey,
-    mint: &Pubkey,
-    mint_authority: &Keypair,
-    payer: &Keypair,
-    amount: f64,
-    mint_decimals: u8,
-) -> Result<(), ProgramError> {
-    let mint_amount = (amount * f64::powf(10., mint_decimals.into())) as u64;
-    let mint_ix = spl_token::instruction::mint_to(
-        token_program,
-        mint,
-        account,
-        &mint_authority.pubkey(),
-        &[],
-        mint_amount,
-    )
-    .unwrap();
-    let mint_tx = Transaction::new_signed_with_payer(
-        &[mint_ix],
-        Some(&payer.pubkey()),
-        &[payer, mint_authority],
-        recent_blockhash,
-    );
-    banks_client.process_transaction(mint_tx).await.unwrap();
-    Ok(())
-}
-
-async fn get_user_staking_state(banks_client: &mut BanksClient, user_pda: &Pubkey) {
-    let user_staking_state_info = banks_client
-        .get_account(*user_pda)
-        .await
-        .unwrap()
-        .expect("Could not fetch account information");
-    let user_staking_state =
-        UserState::try_from_slice(user_staking_state_info.data.as_slice()).unwrap();
-    println!();
-    println!("------------------ USER STAKING STATE ------------------");
-    println!("balance: {}", user_staking_state.balance);
-    println!(
-        "reward_per_token_paid: {}",
-        user_staking_state.reward_per_token_paid
-    );
-    println!("rewards: {}", user_staking_state.rewards);
-    println!("--------------------------------------------------------");
-    println!();
-}
-
-async fn get_staking_state(banks_client: &mut BanksClient, staking_pool_pda: &Pubkey) {
-    let staking_state_info = banks_client
-        .get_account(*staking_pool_pda)
-        .await
-        .unwrap()
-        .expect("Could not fetch account information");
-    let staking_state =
-        StakingPoolState::try_from_slice(staking_state_info.data.as_slice()).unwrap();
-    println!();
-    println!("------------------ STAKING STATE ------------------");
-    println!("admin: {}", staking_state.admin);
-    println!("staking_token_mint: {}", staking_state.staking_token_mint);
-    println!("reward_token_mint: {}", staking_state.reward_token_mint);
-    println!("total_supply: {}", staking_state.total_supply);
-    println!(
-        "reward_per_token_stored: {}",
-        staking_state.reward_per_token_stored
-    );
-    println!(
-        "last_update_timestamp: {}",
-        staking_state.last_update_timestamp
-    );
-    println!("--------------------------------------------------------");
-    println!();
-}
-
-async fn create_and_initialize_mint(
-    banks_client: &mut BanksClient,
-    recent_blockhash: Hash,
-    payer: &Keypair,
-    mint_authority: &Keypair,
-    mint_account: &Keypair,
-    token_program: &Pubkey,
-    decimals: &u8,
-) -> Result<(), TransportError> {
-    let rent = banks_client.get_rent().await.unwrap();
-    let mint_rent = rent.minimum_balance(Mint::LEN);
-    let token_mint_a_account_ix = solana_program::system_instruction::create_account(
-        &payer.pubkey(),
-        &mint_account.pubkey(),
-        mint_rent,
-        Mint::LEN as u64,
-        token_program,
-    );
-    let token_mint_a_ix = spl_token::instruction::initialize_mint(
-        token_program,
-        &mint_account.pubkey(),
-        &mint_authority.pubkey(),
-        None,
-        *decimals,
-    )
-    .unwrap();
-    let token_mint_a_tx = Transaction::new_signed_with_payer(
-        &[token_mint_a_account_ix, token_mint_a_ix],
-        Some(&payer.pubkey()),
-        &[payer, mint_account],
-        recent_blockhash,
-    );
-    banks_client
-        .process_transaction(token_mint_a_tx)
-        .await
-        .unwrap();
-    Ok(())
-}
-
-pub fn find_staking_token_pda(
-    staking_token_mint_account: &Keypair,
-    program_id: &Pubkey,
-) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[&staking_token_mint_account.pubkey().to_bytes(), b"staking-token"],
-        program_id,
-    )
-}
-
-pub fn find_rewards_token_pda(
-    rewards_token_mint_account: &Keypair,
-    program_id: &Pubkey,
-) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[&rewards_token_mint_account.pubkey().to_bytes(), b"rewards-token"],
-        program_id,
-    )
-}
-
-pub fn find_staking_pool_pda(
-    staking_token_mint_account: &Keypair,
-    rewards_token_mint_account: &Keypair,
-    program_id: &Pubkey,
-) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[
-            &staking_token_mint_account.pubkey().to_bytes(),
-            &rewards_token_mint_account.pubkey().to_bytes(),
-            b"staking-pool",
-        ],
-        program_id,
-    )
-}
-
-pub fn find_user_state_pda(
-    staking_pool_pda: &Pubkey,
-    user_account: &Pubkey,
-    program_id: &Pubkey,
-) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[&staking_pool_pda.to_bytes(), &user_account.to_bytes(), b"user-state"],
-        program_id,
-    )
-}
+use common::*;
 
 #[tokio::test]
 async fn test_init_staking() {
@@ -255,7 +56,12 @@ async fn test_init_staking() {
     let alice_staking_token_account = Keypair::new();
     let alice_rewards_token_account = Keypair::new();
 
-    let (staking_pool_pda, _) = find_staking_pool_pda(&staking_token_mint_account, &rewards_token_mint_account, &program_id);
+    let (staking_pool_pda, _) = PdaHelper::find_staking_pool_pda(
+        &staking_token_mint_account.pubkey(),
+        &rewards_token_mint_account.pubkey(),
+        POOL_ID,
+        &program_id,
+    );
 
     let token_program = &id();
 
@@ -326,7 +132,7 @@ async fn test_init_staking() {
     let minted_amount = (amount * f64::powf(10., mint_decimals.into())) as u64;
 
     let token_account_info = banks_client
-        .get_account(alice_staking_token_account.pubkey().clone())
+        .get_account(alice_staking_token_account.pubkey())
         .await
         .unwrap()
         .expect("Could not fetch account information");
@@ -338,8 +144,14 @@ async fn test_init_staking() {
 
     /*-------------------- INITIALIZE ---------------------*/
     
-    let (staking_token_escrow_pda, _) = find_staking_token_pda(&staking_token_mint_account, &program_id);
-    let (rewards_token_escrow_pda, _) = find_rewards_token_pda(&rewards_token_mint_account, &program_id);
+    let (staking_token_escrow_pda, _) =
+        PdaHelper::find_staking_token_pda(&staking_pool_pda, &program_id);
+    let (rewards_token_escrow_pda, _) =
+        PdaHelper::find_rewards_token_pda(
+        &staking_pool_pda,
+        &rewards_token_mint_account.pubkey(),
+        &program_id,
+    );
 
     let init_accounts = vec![
         AccountMeta::new(auth.pubkey(), true),
@@ -351,8 +163,19 @@ async fn test_init_staking() {
         AccountMeta::new_readonly(system_program::id(), false),
         AccountMeta::new_readonly(*token_program, false),
         AccountMeta::new_readonly(solana_program::sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*token_program, false),
     ];
-    let init_ix = Instruction::new_with_borsh(program_id, &StakingInstruction::Init, init_accounts);
+    let init_ix = Instruction::new_with_bytes(
+        program_id,
+        &StakingInstruction::Init {
+            reward_rate: 100,
+            precision: 10_000_000_000,
+            pool_id: POOL_ID,
+        }
+        .try_to_vec()
+        .unwrap(),
+        init_accounts,
+    );
     let init_tx = Transaction::new_signed_with_payer(
         &[init_ix],
         Some(&auth.pubkey()),
@@ -361,11 +184,157 @@ async fn test_init_staking() {
     );
     ctx.banks_client.process_transaction(init_tx).await.unwrap();
 
+    println!("Staking state after initialization");
+    get_staking_state(&mut banks_client, &staking_pool_pda).await;
+
+    /*-----------------------------------------------------*/
+
+    /*------------------ RE-INITIALIZE --------------------*/
+
+    let reinit_ix = Instruction::new_with_bytes(
+        program_id,
+        &StakingInstruction::Init {
+            reward_rate: 0,
+            precision: 1,
+            pool_id: POOL_ID,
+        }
+        .try_to_vec()
+        .unwrap(),
+        vec![
+            AccountMeta::new(alice.pubkey(), true),
+            AccountMeta::new(staking_pool_pda, false),
+            AccountMeta::new_readonly(staking_token_mint_account.pubkey(), false),
+            AccountMeta::new_readonly(rewards_token_mint_account.pubkey(), false),
+            AccountMeta::new(staking_token_escrow_pda, false),
+            AccountMeta::new(rewards_token_escrow_pda, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new_readonly(solana_program::sysvar::rent::id(), false),
+            AccountMeta::new_readonly(*token_program, false),
+        ],
+    );
+    let reinit_tx = Transaction::new_signed_with_payer(
+        &[reinit_ix],
+        Some(&alice.pubkey()),
+        &[&alice],
+        ctx.last_blockhash,
+    );
+    assert_eq!(
+        ctx.banks_client
+            .process_transaction(reinit_tx)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakingError::AlreadyInitialized as u32)
+        ),
+        "Existing staking pool cannot be initialized again"
+    );
+
+    let staking_state_info = banks_client
+        .get_account(staking_pool_pda)
+        .await
+        .unwrap()
+        .expect("Could not fetch account information");
+    let staking_state =
+        StakingPoolState::try_from_slice(staking_state_info.data.as_slice()).unwrap();
+    assert_eq!(staking_state.admin, auth.pubkey(), "Admin must not change");
+    assert_eq!(staking_state.reward_streams[0].reward_rate, 100, "Reward rate must not change");
+
+    /*-----------------------------------------------------*/
+
+    /*------------------ SET REWARD RATE ------------------*/
+
+    let new_reward_rate = 200;
+    let set_reward_rate_data = StakingInstruction::SetRewardRate {
+        reward_rate: new_reward_rate,
+    }
+    .try_to_vec()
+    .unwrap();
+
+    let not_admin_ix = Instruction::new_with_bytes(
+        program_id,
+        &set_reward_rate_data,
+        vec![
+            AccountMeta::new_readonly(alice.pubkey(), true),
+            AccountMeta::new(staking_pool_pda, false),
+            AccountMeta::new_readonly(rewards_token_mint_account.pubkey(), false),
+        ],
+    );
+    let not_admin_tx = Transaction::new_signed_with_payer(
+        &[not_admin_ix],
+        Some(&alice.pubkey()),
+        &[&alice],
+        ctx.last_blockhash,
+    );
+    assert_eq!(
+        ctx.banks_client
+            .process_transaction(not_admin_tx)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakingError::Unauthorized as u32)
+        ),
+        "Only admin can change reward rate"
+    );
+
+    let set_reward_rate_ix = Instruction::new_with_bytes(
+        program_id,
+        &set_reward_rate_data,
+        vec![
+            AccountMeta::new_readonly(auth.pubkey(), true),
+            AccountMeta::new(staking_pool_pda, false),
+            AccountMeta::new_readonly(rewards_token_mint_account.pubkey(), false),
+        ],
+    );
+    let set_reward_rate_tx = Transaction::new_signed_with_payer(
+        &[set_reward_rate_ix],
+        Some(&auth.pubkey()),
+        &[&auth],
+        ctx.last_blockhash,
+    );
+    ctx.banks_client
+        .process_transaction(set_reward_rate_tx)
+        .await
+        .unwrap();
+
+    let staking_state_info = banks_client
+        .get_account(staking_pool_pda)
+        .await
+        .unwrap()
+        .expect("Could not fetch account information");
+    let staking_state =
+        StakingPoolState::try_from_slice(staking_state_info.data.as_slice()).unwrap();
+    assert_eq!(
+        staking_state.reward_streams[0].reward_rate, new_reward_rate,
+        "Set reward rate operation was incorrect"
+    );
+
+    /*-----------------------------------------------------*/
+
+    /*--------------- NOTIFY REWARD AMOUNT ----------------*/
+
+    let auth_rewards_token_account = Keypair::new();
+    create_and_initialize_account_for_mint(
+        &mut banks_client,
+        recent_blockhash,
+        &spl_token::id(),
+        &auth_rewards_token_account,
+        &rewards_token_mint_account,
+        &auth,
+        &auth,
+    )
+    .await
+    .unwrap();
+
     mint_amount(
         &mut banks_client,
         recent_blockhash,
         &spl_token::id(),
-        &rewards_token_escrow_pda,
+        &auth_rewards_token_account.pubkey(),
         &rewards_token_mint_account.pubkey(),
         &auth,
         &auth,
@@ -375,33 +344,124 @@ async fn test_init_staking() {
     .await
     .unwrap();
 
-    println!("Staking state after initialization");
+    let reward_duration = 24 * 60 * 60;
+    let reward_amount = 100 * reward_duration;
+
+    let not_admin_ix = Instruction::new_with_bytes(
+        program_id,
+        &StakingInstruction::NotifyRewardAmount {
+            amount: reward_amount,
+            duration: reward_duration,
+        }
+        .try_to_vec()
+        .unwrap(),
+        vec![
+            AccountMeta::new_readonly(alice.pubkey(), true),
+            AccountMeta::new(alice_rewards_token_account.pubkey(), false),
+            AccountMeta::new(staking_pool_pda, false),
+            AccountMeta::new(rewards_token_escrow_pda, false),
+            AccountMeta::new_readonly(rewards_token_mint_account.pubkey(), false),
+            AccountMeta::new_readonly(*token_program, false),
+        ],
+    );
+    let not_admin_tx = Transaction::new_signed_with_payer(
+        &[not_admin_ix],
+        Some(&alice.pubkey()),
+        &[&alice],
+        ctx.last_blockhash,
+    );
+    assert_eq!(
+        ctx.banks_client
+            .process_transaction(not_admin_tx)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakingError::Unauthorized as u32)
+        ),
+        "Only admin can fund rewards"
+    );
+
+    let notify_reward_amount_ix = Instruction::new_with_bytes(
+        program_id,
+        &StakingInstruction::NotifyRewardAmount {
+            amount: reward_amount,
+            duration: reward_duration,
+        }
+        .try_to_vec()
+        .unwrap(),
+        vec![
+            AccountMeta::new_readonly(auth.pubkey(), true),
+            AccountMeta::new(auth_rewards_token_account.pubkey(), false),
+            AccountMeta::new(staking_pool_pda, false),
+            AccountMeta::new(rewards_token_escrow_pda, false),
+            AccountMeta::new_readonly(rewards_token_mint_account.pubkey(), false),
+            AccountMeta::new_readonly(*token_program, false),
+        ],
+    );
+    let notify_reward_amount_tx = Transaction::new_signed_with_payer(
+        &[notify_reward_amount_ix],
+        Some(&auth.pubkey()),
+        &[&auth],
+        ctx.last_blockhash,
+    );
+    ctx.banks_client
+        .process_transaction(notify_reward_amount_tx)
+        .await
+        .unwrap();
+
+    println!("Staking state after funding rewards");
     get_staking_state(&mut banks_client, &staking_pool_pda).await;
 
+    let staking_state_info = banks_client
+        .get_account(staking_pool_pda)
+        .await
+        .unwrap()
+        .expect("Could not fetch account information");
+    let staking_state =
+        StakingPoolState::try_from_slice(staking_state_info.data.as_slice()).unwrap();
+    assert_eq!(
+        staking_state.reward_streams[0].reward_rate,
+        reward_amount / reward_duration,
+        "Reward rate must be amount / duration"
+    );
+    assert_eq!(
+        staking_state.reward_streams[0].period_finish,
+        staking_state.last_update_timestamp + reward_duration as i64,
+        "Reward period must end after duration"
+    );
+
+    let token_account_info = banks_client
+        .get_account(rewards_token_escrow_pda)
+        .await
+        .unwrap()
+        .expect("Could not fetch account information");
+    let account_data = Account::unpack(&token_account_info.data).unwrap();
+    assert_eq!(
+        account_data.amount, reward_amount,
+        "Rewards were not transferred to the escrow"
+    );
+
     /*-----------------------------------------------------*/
 
     /*----------------------- STAKE -----------------------*/
 
     let (user_state_pda, _) =
-        find_user_state_pda(&staking_pool_pda, &alice.pubkey(), &program_id);
+        PdaHelper::find_user_state_pda(&staking_pool_pda, &alice.pubkey(), &program_id);
 
-    let stake_accounts = vec![
-        AccountMeta::new_readonly(alice.pubkey(), true),
-        AccountMeta::new(alice_staking_token_account.pubkey(), false),
-        AccountMeta::new(staking_token_escrow_pda, false),
-        AccountMeta::new(user_state_pda, false),
-        AccountMeta::new(staking_pool_pda, false),
-        AccountMeta::new_readonly(staking_token_mint_account.pubkey(), false),
-        AccountMeta::new_readonly(*token_program, false),
-        AccountMeta::new_readonly(system_program::id(), false),
-    ];
     let stake_amount = 100;
-    let stake_ix = Instruction::new_with_borsh(
-        program_id,
-        &StakingInstruction::Stake {
-            amount: stake_amount,
-        },
-        stake_accounts,
+    let stake_ix = instruction::stake(
+        &program_id,
+        &alice.pubkey(),
+        &alice_staking_token_account.pubkey(),
+        &staking_token_mint_account.pubkey(),
+        &rewards_token_mint_account.pubkey(),
+        token_program,
+        POOL_ID,
+        stake_amount,
+        0,
+        None,
     );
     let stake_tx = Transaction::new_signed_with_payer(
         &[stake_ix],
@@ -421,7 +481,7 @@ async fn test_init_staking() {
     get_staking_state(&mut banks_client, &staking_pool_pda).await;
 
     let token_account_info = banks_client
-        .get_account(alice_staking_token_account.pubkey().clone())
+        .get_account(alice_staking_token_account.pubkey())
         .await
         .unwrap()
         .expect("Could not fetch account information");
@@ -436,25 +496,21 @@ async fn test_init_staking() {
 
     /*----------------------- UNSTAKE ---------------------*/
 
-    let (staking_token_escrow_pda_owner, _) = find_staking_token_pda(&staking_token_mint_account, &program_id);
+    warp_seconds(&mut ctx, 60 * 60).await;
 
-    let unstake_accounts = vec![
-        AccountMeta::new_readonly(alice.pubkey(), true),
-        AccountMeta::new(alice_staking_token_account.pubkey(), false),
-        AccountMeta::new(user_state_pda, false),
-        AccountMeta::new(staking_pool_pda, false),
-        AccountMeta::new(staking_token_escrow_pda, false),
-        AccountMeta::new_readonly(staking_token_escrow_pda_owner, false),
-        AccountMeta::new_readonly(staking_token_mint_account.pubkey(), false),
-        AccountMeta::new_readonly(*token_program, false),
-    ];
     let unstake_amount = 50;
-    let unstake_ix = Instruction::new_with_borsh(
-        program_id,
-        &StakingInstruction::Unstake {
-            amount: unstake_amount,
-        },
-        unstake_accounts,
+    let unstake_ix = instruction::unstake(
+        &program_id,
+        &alice.pubkey(),
+        &alice_staking_token_account.pubkey(),
+        &staking_token_mint_account.pubkey(),
+        &rewards_token_mint_account.pubkey(),
+        token_program,
+        POOL_ID,
+        unstake_amount,
+        false,
+        None,
+        None,
     );
     let unstake_tx = Transaction::new_signed_with_payer(
         &[unstake_ix],
@@ -474,7 +530,7 @@ async fn test_init_staking() {
     get_staking_state(&mut banks_client, &staking_pool_pda).await;
 
     let token_account_info = banks_client
-        .get_account(alice_staking_token_account.pubkey().clone())
+        .get_account(alice_staking_token_account.pubkey())
         .await
         .unwrap()
         .expect("Could not fetch account information");
@@ -489,30 +545,30 @@ async fn test_init_staking() {
 
     /*-------------------- GET REWARDS -------------------*/
 
+    warp_seconds(&mut ctx, 60 * 60).await;
+
     let token_account_info = banks_client
-        .get_account(alice_rewards_token_account.pubkey().clone())
+        .get_account(alice_rewards_token_account.pubkey())
         .await
         .unwrap()
         .expect("Could not fetch account information");
     let account_data = Account::unpack(&token_account_info.data).unwrap();
     println!("Alice rewards token account balance before withdrawing rewards {}", account_data.amount);
 
-    let (rewards_token_escrow_pda_owner, _) = find_rewards_token_pda(&rewards_token_mint_account, &program_id);
-
-    let rewards_accounts = vec![
-        AccountMeta::new_readonly(alice.pubkey(), true),
-        AccountMeta::new(alice_rewards_token_account.pubkey(), false),
-        AccountMeta::new(user_state_pda, false),
-        AccountMeta::new(staking_pool_pda, false),
-        AccountMeta::new(rewards_token_escrow_pda, false),
-        AccountMeta::new_readonly(rewards_token_escrow_pda_owner, false),
-        AccountMeta::new_readonly(rewards_token_mint_account.pubkey(), false),
-        AccountMeta::new_readonly(*token_program, false),
-    ];
-    let rewards_ix = Instruction::new_with_borsh(
-        program_id,
-        &StakingInstruction::GetRewards,
-        rewards_accounts,
+    let rewards_ix = instruction::get_rewards(
+        &program_id,
+        &alice.pubkey(),
+        &staking_token_mint_account.pubkey(),
+        &rewards_token_mint_account.pubkey(),
+        POOL_ID,
+        &(
+            alice_rewards_token_account.pubkey(),
+            rewards_token_mint_account.pubkey(),
+            *token_program,
+        ),
+        &[],
+        &[],
+        None,
     );
     let rewards_tx = Transaction::new_signed_with_payer(
         &[rewards_ix],
@@ -532,10 +588,70 @@ async fn test_init_staking() {
     get_staking_state(&mut banks_client, &staking_pool_pda).await;
 
     let token_account_info = banks_client
-        .get_account(alice_rewards_token_account.pubkey().clone())
+        .get_account(alice_rewards_token_account.pubkey())
         .await
         .unwrap()
         .expect("Could not fetch account information");
     let account_data = Account::unpack(&token_account_info.data).unwrap();
     println!("Alice rewards token account balance after withdrawing rewards {}", account_data.amount);
+    // 100 tokens per second for an hour with 100 staked, then an hour with 50 staked
+    let first_rewards = 100 * 60 * 60 * 2;
+    assert_eq!(
+        account_data.amount, first_rewards,
+        "Get rewards operation was incorrect"
+    );
+
+    /*----------------------------------------------------*/
+
+    /*--------------- REWARD PERIOD FINISH ---------------*/
+
+    warp_seconds(&mut ctx, 2 * reward_duration as i64).await;
+
+    let rewards_ix = instruction::get_rewards(
+        &program_id,
+        &alice.pubkey(),
+        &staking_token_mint_account.pubkey(),
+        &rewards_token_mint_account.pubkey(),
+        POOL_ID,
+        &(
+            alice_rewards_token_account.pubkey(),
+            rewards_token_mint_account.pubkey(),
+            *token_program,
+        ),
+        &[],
+        &[],
+        None,
+    );
+    let rewards_tx = Transaction::new_signed_with_payer(
+        &[rewards_ix],
+        Some(&alice.pubkey()),
+        &[&alice],
+        ctx.last_blockhash,
+    );
+    ctx.banks_client
+        .process_transaction(rewards_tx)
+        .await
+        .unwrap();
+
+    println!("Alice gets rewards after the reward period finished");
+    get_user_staking_state(&mut banks_client, &user_state_pda).await;
+
+    let token_account_info = banks_client
+        .get_account(alice_rewards_token_account.pubkey())
+        .await
+        .unwrap()
+        .expect("Could not fetch account information");
+    let account_data = Account::unpack(&token_account_info.data).unwrap();
+    assert_eq!(
+        account_data.amount, reward_amount,
+        "Rewards must stop accruing at the end of the reward period"
+    );
+
+    let token_account_info = banks_client
+        .get_account(rewards_token_escrow_pda)
+        .await
+        .unwrap()
+        .expect("Could not fetch account information");
+    let account_data = Account::unpack(&token_account_info.data).unwrap();
+    assert_eq!(account_data.amount, 0, "Rewards escrow must be drained");
 }
@@ -0,0 +1,124 @@
+use solana_program_test::tokio;
+use solana_sdk::{program_pack::Pack, signer::Signer};
+use spl_token_2022::state::Account;
+use staking::{error::StakingError, instruction, pda_helper::PdaHelper, state::UserState};
+
+mod common;
+
+use common::*;
+
+/// Pool staking a Token-2022 mint with a zero transfer fee and paying SPL Token rewards.
+async fn setup_token_2022_pool() -> PoolFixture {
+    setup_pool_with_mints(
+        staking_program_test(program_id()),
+        program_id(),
+        MintKind::TransferFee(0),
+        MintKind::SplToken,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_token_2022_staking_mint() {
+    let mut f = setup_token_2022_pool().await;
+    let alice = f.alice.insecure_clone();
+    let staking_token_escrow = f
+        .ctx
+        .banks_client
+        .get_account(f.staking_token_escrow_pda)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(staking_token_escrow.owner, spl_token_2022::id());
+    assert!(
+        staking_token_escrow.data.len() > Account::LEN,
+        "Escrow must be sized for the transfer fee extension"
+    );
+
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let auth_rewards_token_account = fund_admin_rewards(&mut f, 1000).await;
+    notify_rewards(&mut f, &auth_rewards_token_account, 1000, 100).await;
+    warp_seconds(&mut f.ctx, 110).await;
+
+    let ix = alice_unstake_ix(&f, 100, false);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let ix = alice_get_rewards_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+    let alice_rewards_token_account = f.alice_rewards_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_staking_token_account).await,
+        1_000_000_000_000
+    );
+    assert_eq!(
+        token_balance(&mut f, &alice_rewards_token_account).await,
+        1000
+    );
+}
+
+#[tokio::test]
+async fn test_token_program_must_own_mint() {
+    let mut f = setup_token_2022_pool().await;
+    let alice = f.alice.insecure_clone();
+    let ix = instruction::stake(
+        &f.program_id,
+        &alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &spl_token::id(),
+        f.pool_id,
+        100,
+        0,
+        None,
+    );
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::InvalidTokenProgram)
+    );
+}
+
+#[tokio::test]
+async fn test_stake_credits_amount_received_after_transfer_fee() {
+    let mut f = setup_pool_with_mints(
+        staking_program_test(program_id()),
+        program_id(),
+        MintKind::TransferFee(100),
+        MintKind::SplToken,
+    )
+    .await;
+    let alice = f.alice.insecure_clone();
+
+    let ix = alice_stake_ix(&f, 10_000);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let staking_token_escrow_pda = f.staking_token_escrow_pda;
+    assert_eq!(
+        token_balance(&mut f, &staking_token_escrow_pda).await,
+        9_900
+    );
+    assert_eq!(pool_state(&mut f).await.total_supply, 9_900);
+    let (user_state_pda, _) =
+        PdaHelper::find_user_state_pda(&f.staking_pool_pda, &alice.pubkey(), &f.program_id);
+    let user_state_account = f
+        .ctx
+        .banks_client
+        .get_account(user_state_pda)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        UserState::unpack(&user_state_account.data).unwrap().balance,
+        9_900,
+        "Only the tokens the escrow received are staked"
+    );
+
+    let ix = alice_unstake_ix(&f, 9_901, false);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::UnstakeExceedsBalance)
+    );
+    let ix = alice_unstake_ix(&f, 9_900, false);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    assert_eq!(token_balance(&mut f, &staking_token_escrow_pda).await, 0);
+}
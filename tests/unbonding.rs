@@ -0,0 +1,197 @@
+use solana_program::{instruction::Instruction, pubkey::Pubkey, system_instruction};
+use solana_program_test::tokio;
+use solana_sdk::signer::Signer;
+use staking::{
+    error::StakingError, instruction, pda_helper::PdaHelper, state::MAX_UNBONDING_REQUESTS,
+};
+
+mod common;
+
+use common::*;
+
+const UNBONDING_PERIOD: u64 = 100;
+
+fn set_unbonding_period_ix(f: &PoolFixture, admin: &Pubkey, unbonding_period: u64) -> Instruction {
+    instruction::set_unbonding_period(
+        &f.program_id,
+        admin,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        unbonding_period,
+    )
+}
+
+fn alice_request_unstake_ix(f: &PoolFixture, amount: u64) -> Instruction {
+    instruction::request_unstake(
+        &f.program_id,
+        &f.alice.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+        amount,
+        None,
+    )
+}
+
+fn alice_complete_unstake_ix(f: &PoolFixture) -> Instruction {
+    instruction::complete_unstake(
+        &f.program_id,
+        &f.alice.pubkey(),
+        &f.alice_staking_token_account.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+    )
+}
+
+#[tokio::test]
+async fn test_unstake_through_withdrawal_queue() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let ix = set_unbonding_period_ix(&f, &auth.pubkey(), UNBONDING_PERIOD);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+
+    let ix = alice_unstake_ix(&f, 60, false);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::UnbondingRequired)
+    );
+    let ix = alice_request_unstake_ix(&f, 60);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let state = pool_state(&mut f).await;
+    assert_eq!(
+        (state.total_supply, state.total_unbonding),
+        (40, 60),
+        "Queued tokens stop earning rewards"
+    );
+    let ix = alice_complete_unstake_ix(&f);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::UnbondingNotFinished)
+    );
+
+    warp_seconds(&mut f.ctx, UNBONDING_PERIOD as i64).await;
+    let ix = alice_complete_unstake_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_staking_token_account).await,
+        1_000_000_000_000 - 40
+    );
+    let state = pool_state(&mut f).await;
+    assert_eq!((state.total_supply, state.total_unbonding), (40, 0));
+    let (withdrawal_queue_pda, _) =
+        PdaHelper::find_withdrawal_queue_pda(&f.staking_pool_pda, &alice.pubkey(), &f.program_id);
+    assert!(
+        f.ctx
+            .banks_client
+            .get_account(withdrawal_queue_pda)
+            .await
+            .unwrap()
+            .is_none(),
+        "An emptied queue is closed"
+    );
+}
+
+#[tokio::test]
+async fn test_emergency_withdraw_queues_during_cooldown() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let ix = set_unbonding_period_ix(&f, &auth.pubkey(), UNBONDING_PERIOD);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+
+    let ix = alice_emergency_withdraw_ix(&f);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        None,
+        "Emergency withdrawals stay available but do not skip the cooldown"
+    );
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_staking_token_account).await,
+        1_000_000_000_000 - 100
+    );
+    let state = pool_state(&mut f).await;
+    assert_eq!((state.total_supply, state.total_unbonding), (0, 100));
+
+    warp_seconds(&mut f.ctx, UNBONDING_PERIOD as i64).await;
+    let ix = alice_complete_unstake_ix(&f);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    assert_eq!(
+        token_balance(&mut f, &alice_staking_token_account).await,
+        1_000_000_000_000
+    );
+}
+
+#[tokio::test]
+async fn test_prefunded_withdrawal_queue() {
+    let mut f = setup_pool(program_id()).await;
+    let auth = f.auth.insecure_clone();
+    let alice = f.alice.insecure_clone();
+    let ix = set_unbonding_period_ix(&f, &auth.pubkey(), UNBONDING_PERIOD);
+    assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let (withdrawal_queue_pda, _) =
+        PdaHelper::find_withdrawal_queue_pda(&f.staking_pool_pda, &alice.pubkey(), &f.program_id);
+    let rent_exempt_empty = f
+        .ctx
+        .banks_client
+        .get_rent()
+        .await
+        .unwrap()
+        .minimum_balance(0);
+
+    for amount in [60, 40] {
+        // Anyone can send lamports to the queue address before it is created
+        let ix =
+            system_instruction::transfer(&auth.pubkey(), &withdrawal_queue_pda, rent_exempt_empty);
+        assert_eq!(process_ix(&mut f.ctx, ix, &auth).await, None);
+        let ix = alice_request_unstake_ix(&f, amount);
+        assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+        warp_seconds(&mut f.ctx, UNBONDING_PERIOD as i64).await;
+        let ix = alice_complete_unstake_ix(&f);
+        assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    }
+    let alice_staking_token_account = f.alice_staking_token_account.pubkey();
+    assert_eq!(
+        token_balance(&mut f, &alice_staking_token_account).await,
+        1_000_000_000_000
+    );
+}
+
+#[tokio::test]
+async fn test_unbonding_validation() {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    let ix = set_unbonding_period_ix(&f, &alice.pubkey(), UNBONDING_PERIOD);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::Unauthorized)
+    );
+    let ix = alice_stake_ix(&f, 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    let ix = alice_request_unstake_ix(&f, 101);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::UnstakeExceedsBalance)
+    );
+    for amount in 1..=MAX_UNBONDING_REQUESTS as u64 {
+        let ix = alice_request_unstake_ix(&f, amount);
+        assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    }
+    let ix = alice_request_unstake_ix(&f, 1);
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::WithdrawalQueueFull)
+    );
+}
@@ -0,0 +1,198 @@
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::TransactionError};
+use staking::{error::StakingError, instruction, pda_helper::PdaHelper};
+
+mod common;
+
+use common::*;
+
+fn stake_ix(f: &PoolFixture, user: &Pubkey, amount: u64) -> Instruction {
+    instruction::stake(
+        &f.program_id,
+        user,
+        &f.alice_staking_token_account.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+        amount,
+        0,
+        None,
+    )
+}
+
+fn unstake_ix(f: &PoolFixture, user: &Pubkey, amount: u64) -> Instruction {
+    instruction::unstake(
+        &f.program_id,
+        user,
+        &f.alice_staking_token_account.pubkey(),
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        &f.staking_token_program,
+        f.pool_id,
+        amount,
+        false,
+        None,
+        None,
+    )
+}
+
+fn get_rewards_ix(f: &PoolFixture, user: &Pubkey) -> Instruction {
+    instruction::get_rewards(
+        &f.program_id,
+        user,
+        &f.staking_token_mint_account.pubkey(),
+        &f.rewards_token_mint_account.pubkey(),
+        f.pool_id,
+        &(
+            f.alice_rewards_token_account.pubkey(),
+            f.rewards_token_mint_account.pubkey(),
+            f.rewards_token_program,
+        ),
+        &[],
+        &[],
+        None,
+    )
+}
+
+/// Pool with alice holding a 100 token stake.
+async fn setup_staked_pool() -> PoolFixture {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    let ix = stake_ix(&f, &alice.pubkey(), 100);
+    assert_eq!(process_ix(&mut f.ctx, ix, &alice).await, None);
+    f
+}
+
+#[tokio::test]
+async fn test_stake_requires_signer() {
+    let mut f = setup_pool(program_id()).await;
+    let bob = f.bob.insecure_clone();
+    let mut ix = stake_ix(&f, &f.alice.pubkey(), 100);
+    ix.accounts[0].is_signer = false;
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &bob).await,
+        Some(TransactionError::InstructionError(
+            0,
+            InstructionError::MissingRequiredSignature
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_stake_rejects_fake_escrow() {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    let mut ix = stake_ix(&f, &alice.pubkey(), 100);
+    let fake_escrow = Keypair::new();
+    create_and_initialize_account_for_mint(
+        &mut f.ctx.banks_client.clone(),
+        f.ctx.last_blockhash,
+        &spl_token::id(),
+        &fake_escrow,
+        &f.staking_token_mint_account,
+        &alice,
+        &alice,
+    )
+    .await
+    .unwrap();
+    ix.accounts[2].pubkey = fake_escrow.pubkey();
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::InvalidStakingTokenEscrow)
+    );
+}
+
+#[tokio::test]
+async fn test_stake_rejects_spoofed_token_program() {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    let mut ix = stake_ix(&f, &alice.pubkey(), 100);
+    ix.accounts[6].pubkey = Keypair::new().pubkey();
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::InvalidTokenProgram)
+    );
+}
+
+#[tokio::test]
+async fn test_stake_rejects_wrong_mint() {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    let mut ix = stake_ix(&f, &alice.pubkey(), 100);
+    ix.accounts[5].pubkey = f.rewards_token_mint_account.pubkey();
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::InvalidStakingTokenMint)
+    );
+}
+
+#[tokio::test]
+async fn test_stake_rejects_pool_not_owned_by_program() {
+    let mut f = setup_pool(program_id()).await;
+    let alice = f.alice.insecure_clone();
+    let mut ix = stake_ix(&f, &alice.pubkey(), 100);
+    ix.accounts[4].pubkey = f.bob.pubkey();
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::InvalidAccountOwner)
+    );
+}
+
+#[tokio::test]
+async fn test_unstake_rejects_foreign_user_state() {
+    let mut f = setup_staked_pool().await;
+    let bob = f.bob.insecure_clone();
+    let mut ix = unstake_ix(&f, &bob.pubkey(), 100);
+    let (alice_user_state_pda, _) =
+        PdaHelper::find_user_state_pda(&f.staking_pool_pda, &f.alice.pubkey(), &f.program_id);
+    ix.accounts[2].pubkey = alice_user_state_pda;
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &bob).await,
+        custom_error(StakingError::InvalidUserStatePda)
+    );
+}
+
+#[tokio::test]
+async fn test_unstake_rejects_fake_escrow() {
+    let mut f = setup_staked_pool().await;
+    let alice = f.alice.insecure_clone();
+    let mut ix = unstake_ix(&f, &alice.pubkey(), 100);
+    ix.accounts[4].pubkey = f.alice_staking_token_account.pubkey();
+    ix.accounts[5].pubkey = f.alice_staking_token_account.pubkey();
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::InvalidStakingTokenEscrow)
+    );
+}
+
+#[tokio::test]
+async fn test_get_rewards_rejects_foreign_user_state() {
+    let mut f = setup_staked_pool().await;
+    let bob = f.bob.insecure_clone();
+    let mut ix = get_rewards_ix(&f, &bob.pubkey());
+    let (alice_user_state_pda, _) =
+        PdaHelper::find_user_state_pda(&f.staking_pool_pda, &f.alice.pubkey(), &f.program_id);
+    ix.accounts[2].pubkey = alice_user_state_pda;
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &bob).await,
+        custom_error(StakingError::InvalidUserStatePda)
+    );
+}
+
+#[tokio::test]
+async fn test_get_rewards_rejects_fake_escrow() {
+    let mut f = setup_staked_pool().await;
+    let alice = f.alice.insecure_clone();
+    let mut ix = get_rewards_ix(&f, &alice.pubkey());
+    ix.accounts[4].pubkey = f.alice_rewards_token_account.pubkey();
+    ix.accounts[5].pubkey = f.alice_rewards_token_account.pubkey();
+    assert_eq!(
+        process_ix(&mut f.ctx, ix, &alice).await,
+        custom_error(StakingError::InvalidRewardsTokenEscrow)
+    );
+}